@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::cp::{ContentProcessor, DirEntry, ParentContext};
+use crate::fs;
+use crate::wd::{Depth, SiblingInfo};
+
+/////////////////////////////////////////////////////////////////////////
+//// ContentCollection
+
+/// A collection of content processor items (e.g. content of a dir in
+/// `Position::OpenDirWithContent`).
+///
+/// Besides sequences, items may be collected into maps keyed by their names
+/// (see [`KeyedItem`]), so a dir can be checked for a given entry without a
+/// linear scan. Implement this trait for other types (e.g. `SmallVec`) to use
+/// them with [`WithCollection`].
+///
+/// [`KeyedItem`]: trait.KeyedItem.html
+/// [`WithCollection`]: struct.WithCollection.html
+pub trait ContentCollection<T>: Sized {
+    /// Collects items into collection
+    fn from_items(items: impl Iterator<Item = T>) -> Self;
+
+    /// Empty collection
+    fn empty() -> Self {
+        Self::from_items(std::iter::empty())
+    }
+}
+
+impl<T> ContentCollection<T> for Vec<T> {
+    fn from_items(items: impl Iterator<Item = T>) -> Self {
+        items.collect()
+    }
+}
+
+impl<T> ContentCollection<T> for VecDeque<T> {
+    fn from_items(items: impl Iterator<Item = T>) -> Self {
+        items.collect()
+    }
+}
+
+impl<T: KeyedItem> ContentCollection<T> for BTreeMap<T::Key, T>
+where
+    T::Key: Ord,
+{
+    fn from_items(items: impl Iterator<Item = T>) -> Self {
+        items.map(|item| (item.key(), item)).collect()
+    }
+}
+
+impl<T: KeyedItem> ContentCollection<T> for HashMap<T::Key, T>
+where
+    T::Key: Eq + Hash,
+{
+    fn from_items(items: impl Iterator<Item = T>) -> Self {
+        items.map(|item| (item.key(), item)).collect()
+    }
+}
+
+/// An item which can be a value of a map collection
+pub trait KeyedItem {
+    /// Key type
+    type Key;
+
+    /// Key of this item (unique within a dir)
+    fn key(&self) -> Self::Key;
+}
+
+/// Keyed by file name
+impl<E: fs::FsDirEntry> KeyedItem for DirEntry<E> {
+    type Key = E::FileName;
+
+    fn key(&self) -> Self::Key {
+        self.file_name().clone()
+    }
+}
+
+/// Keyed by the key of the first item
+impl<A: KeyedItem, B> KeyedItem for (A, B) {
+    type Key = A::Key;
+
+    fn key(&self) -> Self::Key {
+        self.0.key()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// WithCollection
+
+/// Content processor which collects items of another one into `C` (see
+/// `WalkDirBuilder::collection`).
+pub struct WithCollection<CP, C> {
+    inner: CP,
+    _collection: PhantomData<fn() -> C>,
+}
+
+impl<CP, C> WithCollection<CP, C> {
+    /// Wrap the processor
+    pub fn new(processor: CP) -> Self {
+        Self { inner: processor, _collection: PhantomData }
+    }
+
+    /// The wrapped processor
+    pub fn inner(&self) -> &CP {
+        &self.inner
+    }
+}
+
+impl<CP: Default, C> Default for WithCollection<CP, C> {
+    fn default() -> Self {
+        Self::new(CP::default())
+    }
+}
+
+impl<CP: fmt::Debug, C> fmt::Debug for WithCollection<CP, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithCollection")
+            .field("inner", &self.inner)
+            .field("collection", &std::any::type_name::<C>())
+            .finish()
+    }
+}
+
+impl<E, CP, C> ContentProcessor<E> for WithCollection<CP, C>
+where
+    E: fs::FsDirEntry,
+    CP: ContentProcessor<E>,
+    C: ContentCollection<CP::Item>,
+{
+    type Item = CP::Item;
+    type Collection = C;
+    type Scratch = CP::Scratch;
+
+    fn allow_push(&self, fsdent: &E, parent: ParentContext<'_, Self::Item, Self::Scratch>) -> bool {
+        self.inner.allow_push(fsdent, parent)
+    }
+
+    fn process_root_direntry(
+        &self,
+        fsdent: &mut E::RootDirEntry,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        self.inner.process_root_direntry(fsdent, follow_link, is_dir, depth, ctx)
+    }
+
+    fn process_direntry(
+        &self,
+        fsdent: &mut E,
+        parent: ParentContext<'_, Self::Item, Self::Scratch>,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        self.inner.process_direntry(fsdent, parent, follow_link, is_dir, depth, ctx)
+    }
+
+    fn take_error(&self) -> Option<E::Error> {
+        self.inner.take_error()
+    }
+
+    fn process_ahead(&self) -> bool {
+        self.inner.process_ahead()
+    }
+
+    fn set_sibling_info(&self, item: &mut Self::Item, info: SiblingInfo) {
+        self.inner.set_sibling_info(item, info)
+    }
+
+    fn is_dir(item: &Self::Item) -> bool {
+        CP::is_dir(item)
+    }
+
+    fn collect(&self, iter: impl Iterator<Item = Self::Item>) -> Self::Collection {
+        C::from_items(iter)
+    }
+
+    fn empty_collection() -> Self::Collection {
+        C::empty()
+    }
+}
@@ -0,0 +1,496 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use crate::cp::{ContentProcessor, ParentContext};
+use crate::fs::{self, FsRootDirEntry};
+use crate::wd::{Depth, IntoSome, SiblingInfo};
+
+/////////////////////////////////////////////////////////////////////////
+//// Processed
+
+/// An item produced by [`map`], [`filter_map`] or [`from_fn`]: a value
+/// together with what the walker has to know about its entry.
+///
+/// Items of dirs made by `map` and `filter_map` also keep the item `I` of
+/// the inner processor, which gets it as the parent of their content.
+///
+/// Derefs to the value.
+///
+/// [`map`]: fn.map.html
+/// [`filter_map`]: fn.filter_map.html
+/// [`from_fn`]: fn.from_fn.html
+#[derive(Clone)]
+pub struct Processed<T, I = ()> {
+    value: T,
+    is_dir: bool,
+    sibling_info: Option<SiblingInfo>,
+    inner: Option<I>,
+}
+
+impl<T, I> Processed<T, I> {
+    fn new(value: T, is_dir: bool) -> Self {
+        Self { value, is_dir, sibling_info: None, inner: None }
+    }
+
+    /// The value
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Unwraps the value
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// Is the entry a dir (which is descended into)?
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Position among siblings (only when WalkDirBuilder::track_siblings is set)
+    pub fn sibling_info(&self) -> Option<&SiblingInfo> {
+        self.sibling_info.as_ref()
+    }
+}
+
+impl<T, I> Deref for Processed<T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: fmt::Debug, I> fmt::Debug for Processed<T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Processed")
+            .field("value", &self.value)
+            .field("is_dir", &self.is_dir)
+            .field("sibling_info", &self.sibling_info)
+            .finish()
+    }
+}
+
+/// The item of the inner processor is not compared
+impl<T: PartialEq, I> PartialEq for Processed<T, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.is_dir == other.is_dir && self.sibling_info == other.sibling_info
+    }
+}
+
+impl<T: Eq, I> Eq for Processed<T, I> {}
+
+/////////////////////////////////////////////////////////////////////////
+//// Map, FilterMap
+
+/// Content processor which maps items of another one (see [`map`])
+///
+/// [`map`]: fn.map.html
+pub struct Map<CP, F> {
+    inner: CP,
+    f: Mutex<F>,
+}
+
+/// Maps each item of `processor` with `f`.
+///
+/// ```no_run
+/// use walkdir2::{DirEntry, WalkDirBuilder};
+/// use walkdir2::cp::{self, DirEntryContentProcessor};
+///
+/// let sizes = cp::map(DirEntryContentProcessor::default(), |dent: DirEntry| {
+///     (dent.path().to_path_buf(), dent.metadata().map(|md| md.len()).unwrap_or(0))
+/// });
+/// for entry in WalkDirBuilder::with_context("foo", Default::default(), sizes).into_classic() {
+///     let (path, size) = entry.unwrap().into_value();
+///     println!("{} {}", size, path.display());
+/// }
+/// ```
+pub fn map<CP, F>(processor: CP, f: F) -> Map<CP, F> {
+    Map { inner: processor, f: Mutex::new(f) }
+}
+
+impl<CP: fmt::Debug, F> fmt::Debug for Map<CP, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Map").field("inner", &self.inner).finish()
+    }
+}
+
+/// Content processor which maps and filters items of another one (see [`filter_map`])
+///
+/// [`filter_map`]: fn.filter_map.html
+pub struct FilterMap<CP, F> {
+    inner: CP,
+    f: Mutex<F>,
+}
+
+/// Maps each item of `processor` with `f`. Entries for which `f` returns
+/// `None` are not yielded, and such dirs are not descended into.
+pub fn filter_map<CP, F>(processor: CP, f: F) -> FilterMap<CP, F> {
+    FilterMap { inner: processor, f: Mutex::new(f) }
+}
+
+impl<CP: fmt::Debug, F> fmt::Debug for FilterMap<CP, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterMap").field("inner", &self.inner).finish()
+    }
+}
+
+macro_rules! impl_mapping_processor {
+    ($name:ident, $item:ident, $apply:expr) => {
+        impl<CP, F> $name<CP, F> {
+            /// Maps the item of the inner processor (keeping it for dirs)
+            fn apply<E, T>(&self, item: CP::Item) -> Option<Processed<T, CP::Item>>
+            where
+                E: fs::FsDirEntry,
+                CP: ContentProcessor<E>,
+                F: FnMut(CP::Item) -> $item<T>,
+            {
+                let is_dir = CP::is_dir(&item);
+                let inner = if is_dir { Some(item.clone()) } else { None };
+                let value = $apply((self.f.lock().unwrap())(item))?;
+                Processed { inner, ..Processed::new(value, is_dir) }.into_some()
+            }
+        }
+
+        impl<E, CP, F, T> ContentProcessor<E> for $name<CP, F>
+        where
+            E: fs::FsDirEntry,
+            CP: ContentProcessor<E>,
+            F: FnMut(CP::Item) -> $item<T>,
+            T: Clone,
+        {
+            type Item = Processed<T, CP::Item>;
+            type Collection = Vec<Processed<T, CP::Item>>;
+            type Scratch = CP::Scratch;
+
+            fn allow_push(&self, fsdent: &E, parent: ParentContext<'_, Self::Item, Self::Scratch>) -> bool {
+                let (item, scratch) = parent.into_parts();
+                let inner = item.and_then(|item| item.inner.as_ref());
+                self.inner.allow_push(fsdent, ParentContext::new(inner, scratch))
+            }
+
+            fn process_root_direntry(
+                &self,
+                fsdent: &mut E::RootDirEntry,
+                follow_link: bool,
+                is_dir: bool,
+                depth: Depth,
+                ctx: &mut E::Context,
+            ) -> Option<Self::Item> {
+                let item = self.inner.process_root_direntry(fsdent, follow_link, is_dir, depth, ctx)?;
+                self.apply(item)
+            }
+
+            fn process_direntry(
+                &self,
+                fsdent: &mut E,
+                parent: ParentContext<'_, Self::Item, Self::Scratch>,
+                follow_link: bool,
+                is_dir: bool,
+                depth: Depth,
+                ctx: &mut E::Context,
+            ) -> Option<Self::Item> {
+                let (item, scratch) = parent.into_parts();
+                let parent = ParentContext::new(item.and_then(|item| item.inner.as_ref()), scratch);
+                let item = self.inner.process_direntry(fsdent, parent, follow_link, is_dir, depth, ctx)?;
+                self.apply(item)
+            }
+
+            fn take_error(&self) -> Option<E::Error> {
+                self.inner.take_error()
+            }
+
+            fn process_ahead(&self) -> bool {
+                self.inner.process_ahead()
+            }
+
+            fn set_sibling_info(&self, item: &mut Self::Item, info: SiblingInfo) {
+                item.sibling_info = Some(info);
+            }
+
+            fn is_dir(item: &Self::Item) -> bool {
+                item.is_dir
+            }
+
+            fn collect(&self, iter: impl Iterator<Item = Self::Item>) -> Self::Collection {
+                iter.collect()
+            }
+
+            fn empty_collection() -> Self::Collection {
+                vec![]
+            }
+        }
+    };
+}
+
+/// `T` itself (so `Map` and `FilterMap` share one implementation)
+type Same<T> = T;
+
+impl_mapping_processor!(Map, Same, Some);
+impl_mapping_processor!(FilterMap, Option, std::convert::identity);
+
+/////////////////////////////////////////////////////////////////////////
+//// Pair
+
+/// A pair of content processors yields pairs of their items.
+///
+/// An entry is yielded only when both processors produce an item for it.
+/// Both processors see the same fs entry, the first one -- before the second
+/// one.
+impl<E, A, B> ContentProcessor<E> for (A, B)
+where
+    E: fs::FsDirEntry,
+    A: ContentProcessor<E>,
+    B: ContentProcessor<E>,
+{
+    type Item = (A::Item, B::Item);
+    type Collection = Vec<(A::Item, B::Item)>;
+    type Scratch = (A::Scratch, B::Scratch);
+
+    fn allow_push(&self, fsdent: &E, parent: ParentContext<'_, Self::Item, Self::Scratch>) -> bool {
+        let (a, b) = split_parent(parent);
+        self.0.allow_push(fsdent, a) && self.1.allow_push(fsdent, b)
+    }
+
+    fn process_root_direntry(
+        &self,
+        fsdent: &mut E::RootDirEntry,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let a = self.0.process_root_direntry(fsdent, follow_link, is_dir, depth, ctx)?;
+        let b = self.1.process_root_direntry(fsdent, follow_link, is_dir, depth, ctx)?;
+        (a, b).into_some()
+    }
+
+    fn process_direntry(
+        &self,
+        fsdent: &mut E,
+        parent: ParentContext<'_, Self::Item, Self::Scratch>,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let (pa, pb) = split_parent(parent);
+        let a = self.0.process_direntry(fsdent, pa, follow_link, is_dir, depth, ctx)?;
+        let b = self.1.process_direntry(fsdent, pb, follow_link, is_dir, depth, ctx)?;
+        (a, b).into_some()
+    }
+
+    fn take_error(&self) -> Option<E::Error> {
+        self.0.take_error().or_else(|| self.1.take_error())
+    }
+
+    fn process_ahead(&self) -> bool {
+        self.0.process_ahead() || self.1.process_ahead()
+    }
+
+    fn set_sibling_info(&self, item: &mut Self::Item, info: SiblingInfo) {
+        self.0.set_sibling_info(&mut item.0, info.clone());
+        self.1.set_sibling_info(&mut item.1, info);
+    }
+
+    fn is_dir(item: &Self::Item) -> bool {
+        A::is_dir(&item.0)
+    }
+
+    fn collect(&self, iter: impl Iterator<Item = Self::Item>) -> Self::Collection {
+        iter.collect()
+    }
+
+    fn empty_collection() -> Self::Collection {
+        vec![]
+    }
+}
+
+/// Splits parent context of a pair into contexts of its parts
+fn split_parent<'a, TA, TB, SA, SB>(
+    parent: ParentContext<'a, (TA, TB), (SA, SB)>,
+) -> (ParentContext<'a, TA, SA>, ParentContext<'a, TB, SB>) {
+    let (item, scratch) = parent.into_parts();
+    let (sa, sb) = (&mut scratch.0, &mut scratch.1);
+    (ParentContext::new(item.map(|item| &item.0), sa), ParentContext::new(item.map(|item| &item.1), sb))
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// FnContentProcessor
+
+#[derive(Debug)]
+enum FsEntryInner<'a, E: fs::FsDirEntry> {
+    Root(&'a mut E::RootDirEntry),
+    Entry(&'a mut E),
+}
+
+/// A fs entry passed to the closure of [`from_fn`] (either the root or an entry read from a dir)
+///
+/// [`from_fn`]: fn.from_fn.html
+#[derive(Debug)]
+pub struct FsEntryMut<'a, E: fs::FsDirEntry> {
+    inner: FsEntryInner<'a, E>,
+    follow_link: bool,
+    is_dir: bool,
+    depth: Depth,
+}
+
+impl<'a, E: fs::FsDirEntry> FsEntryMut<'a, E> {
+    /// Get path of this entry
+    pub fn path(&self) -> &E::Path {
+        match self.inner {
+            FsEntryInner::Root(ref root) => root.path(),
+            FsEntryInner::Entry(ref fsdent) => fsdent.path(),
+        }
+    }
+
+    /// Get bare name of this entry
+    pub fn file_name(&self) -> E::FileName {
+        match self.inner {
+            FsEntryInner::Root(ref root) => root.file_name(),
+            FsEntryInner::Entry(ref fsdent) => fsdent.file_name(),
+        }
+    }
+
+    /// Get metadata (of the symlink target if links are followed)
+    pub fn metadata(&self, ctx: &mut E::Context) -> Result<E::Metadata, E::Error> {
+        match self.inner {
+            FsEntryInner::Root(ref root) => root.metadata(self.follow_link, ctx),
+            FsEntryInner::Entry(ref fsdent) => fsdent.metadata(self.follow_link, ctx),
+        }
+    }
+
+    /// Get path, metadata and file name (see `FsDirEntry::to_parts`)
+    pub fn to_parts(
+        &mut self,
+        force_metadata: bool,
+        force_file_name: bool,
+        ctx: &mut E::Context,
+    ) -> (E::PathBuf, Option<E::Metadata>, Option<E::FileName>) {
+        match self.inner {
+            FsEntryInner::Root(ref mut root) => root.to_parts(self.follow_link, force_metadata, force_file_name, ctx),
+            FsEntryInner::Entry(ref mut fsdent) => fsdent.to_parts(self.follow_link, force_metadata, force_file_name, ctx),
+        }
+    }
+
+    /// The entry read from a dir (`None` for the root)
+    pub fn fsdent(&mut self) -> Option<&mut E> {
+        match self.inner {
+            FsEntryInner::Root(_) => None,
+            FsEntryInner::Entry(ref mut fsdent) => Some(fsdent),
+        }
+    }
+
+    /// Are symlinks followed?
+    pub fn follow_link(&self) -> bool {
+        self.follow_link
+    }
+
+    /// Is it a dir (which will be descended into)?
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Depth of this entry relative to the root
+    pub fn depth(&self) -> Depth {
+        self.depth
+    }
+}
+
+/// Content processor made from a closure (see [`from_fn`])
+///
+/// [`from_fn`]: fn.from_fn.html
+pub struct FnContentProcessor<F> {
+    f: Mutex<F>,
+}
+
+/// Makes a content processor from a closure, which gets each fs entry and
+/// returns the item to yield (or `None` to skip the entry and, for dirs, its content).
+///
+/// ```no_run
+/// use walkdir2::WalkDirBuilder;
+/// use walkdir2::cp::{self, FsEntryMut};
+/// use walkdir2::fs::DefaultDirEntry;
+///
+/// let names = cp::from_fn(|fsdent: &mut FsEntryMut<DefaultDirEntry>, _ctx: &mut _| Some(fsdent.file_name()));
+/// for name in WalkDirBuilder::with_context("foo", Default::default(), names).into_classic() {
+///     println!("{:?}", *name.unwrap());
+/// }
+/// ```
+pub fn from_fn<F>(f: F) -> FnContentProcessor<F> {
+    FnContentProcessor { f: Mutex::new(f) }
+}
+
+impl<F> fmt::Debug for FnContentProcessor<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnContentProcessor").finish()
+    }
+}
+
+impl<F> FnContentProcessor<F> {
+    fn call<E, T>(&self, mut fsdent: FsEntryMut<'_, E>, ctx: &mut E::Context) -> Option<Processed<T>>
+    where
+        E: fs::FsDirEntry,
+        F: FnMut(&mut FsEntryMut<'_, E>, &mut E::Context) -> Option<T>,
+    {
+        let value = (self.f.lock().unwrap())(&mut fsdent, ctx)?;
+        Processed::new(value, fsdent.is_dir).into_some()
+    }
+}
+
+impl<E, F, T> ContentProcessor<E> for FnContentProcessor<F>
+where
+    E: fs::FsDirEntry,
+    F: FnMut(&mut FsEntryMut<'_, E>, &mut E::Context) -> Option<T>,
+    T: Clone,
+{
+    type Item = Processed<T>;
+    type Collection = Vec<Processed<T>>;
+    type Scratch = ();
+
+    fn allow_push(&self, _fsdent: &E, _parent: ParentContext<'_, Self::Item, Self::Scratch>) -> bool {
+        true
+    }
+
+    fn process_root_direntry(
+        &self,
+        fsdent: &mut E::RootDirEntry,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let fsdent = FsEntryMut { inner: FsEntryInner::Root(fsdent), follow_link, is_dir, depth };
+        self.call(fsdent, ctx)
+    }
+
+    fn process_direntry(
+        &self,
+        fsdent: &mut E,
+        _parent: ParentContext<'_, Self::Item, Self::Scratch>,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let fsdent = FsEntryMut { inner: FsEntryInner::Entry(fsdent), follow_link, is_dir, depth };
+        self.call(fsdent, ctx)
+    }
+
+    fn set_sibling_info(&self, item: &mut Self::Item, info: SiblingInfo) {
+        item.sibling_info = Some(info);
+    }
+
+    fn is_dir(item: &Self::Item) -> bool {
+        item.is_dir
+    }
+
+    fn collect(&self, iter: impl Iterator<Item = Self::Item>) -> Self::Collection {
+        iter.collect()
+    }
+
+    fn empty_collection() -> Self::Collection {
+        vec![]
+    }
+}
@@ -1,7 +1,7 @@
 //use crate::error::{into_io_err, Error};
-use crate::fs::{self, FsFileType, FsRootDirEntry, FsMetadata};
-use crate::wd::{self, Depth, IntoSome, IntoOk};
-use crate::cp::ContentProcessor;
+use crate::fs::{self, FsFileType, FsRootDirEntry, FsMetadata, FsPath};
+use crate::wd::{self, Depth, IntoSome, IntoOk, SiblingInfo};
+use crate::cp::{ContentProcessor, ParentContext};
 
 use std::vec::Vec;
 
@@ -47,6 +47,8 @@ pub struct DirEntry<E: fs::FsDirEntry = fs::DefaultDirEntry> {
     file_name: E::FileName,
     /// The depth at which this entry was generated relative to the root.
     depth: Depth,
+    /// Position among siblings
+    sibling_info: Option<SiblingInfo>,
 }
 
 impl<E: fs::FsDirEntry> Clone for DirEntry<E> {
@@ -58,6 +60,7 @@ impl<E: fs::FsDirEntry> Clone for DirEntry<E> {
             metadata:       self.metadata.clone(),
             file_name:      self.file_name.clone(),
             depth:          self.depth,
+            sibling_info:   self.sibling_info.clone(),
         }    
     }
 }
@@ -82,6 +85,38 @@ impl<E: fs::FsDirEntry> DirEntry<E> {
         &self.path
     }
 
+    /// The path of this entry relative to the root initially given to
+    /// [`WalkDir::new`].
+    ///
+    /// This is a borrowed tail of [`path`] (the last [`depth`] components),
+    /// so it doesn't depend on how the root was spelled (e.g. `./foo` vs
+    /// `foo`). For the root itself, an empty path is returned.
+    ///
+    /// [`WalkDir::new`]: struct.WalkDir.html#method.new
+    /// [`path`]: struct.DirEntry.html#method.path
+    /// [`depth`]: struct.DirEntry.html#method.depth
+    pub fn relative_path(&self) -> &E::Path {
+        match self.path().split_tail(self.depth) {
+            Some((_, tail)) => tail,
+            None => self.path(),
+        }
+    }
+
+    /// The root initially given to [`WalkDir::new`].
+    ///
+    /// This is a borrowed head of [`path`] (without the last [`depth`]
+    /// components).
+    ///
+    /// [`WalkDir::new`]: struct.WalkDir.html#method.new
+    /// [`path`]: struct.DirEntry.html#method.path
+    /// [`depth`]: struct.DirEntry.html#method.depth
+    pub fn root(&self) -> &E::Path {
+        match self.path().split_tail(self.depth) {
+            Some((head, _)) => head,
+            None => self.path(),
+        }
+    }
+
     /// The full path that this entry represents.
     ///
     /// Analogous to [`path`], but moves ownership of the path.
@@ -160,12 +195,35 @@ impl<E: fs::FsDirEntry> DirEntry<E> {
         self.depth
     }
 
+    /// Returns the position of this entry among its siblings.
+    ///
+    /// This is only available when [`track_siblings`] is enabled.
+    ///
+    /// [`track_siblings`]: struct.WalkDir.html#method.track_siblings
+    pub fn sibling_info(&self) -> Option<&SiblingInfo> {
+        self.sibling_info.as_ref()
+    }
+
     /////////////////////////////////////////////////////////////////////////////////
     
     /// Returns true if and only if this entry points to a directory.
     pub(crate) fn is_dir(&self) -> bool {
         self.is_dir
     }
+
+    /// Cached metadata without cloning
+    pub(crate) fn metadata_ref(&self) -> &E::Metadata {
+        &self.metadata
+    }
+}
+
+impl<E: fs::FsDirEntry<Path = std::path::Path>> DirEntry<E> {
+    /// Iterates over the components of [`relative_path`].
+    ///
+    /// [`relative_path`]: struct.DirEntry.html#method.relative_path
+    pub fn components(&self) -> std::path::Components<'_> {
+        self.relative_path().components()
+    }
 }
 
 // /////////////////////////////////////////////////////////////////////////////////
@@ -197,10 +255,12 @@ pub struct DirEntryContentProcessor {}
 impl<E: fs::FsDirEntry> ContentProcessor<E> for DirEntryContentProcessor {
     type Item = DirEntry<E>;
     type Collection = Vec<DirEntry<E>>;
+    type Scratch = ();
 
     fn allow_push(
         &self,
         _fsdent: &E,
+        _parent: ParentContext<'_, Self::Item, Self::Scratch>,
     ) -> bool {
         true
     }
@@ -223,6 +283,7 @@ impl<E: fs::FsDirEntry> ContentProcessor<E> for DirEntryContentProcessor {
             metadata: metadata.unwrap(),
             file_name: file_name.unwrap(),
             depth,
+            sibling_info: None,
         }.into_some()
     }
 
@@ -230,6 +291,7 @@ impl<E: fs::FsDirEntry> ContentProcessor<E> for DirEntryContentProcessor {
     fn process_direntry(
         &self,
         fsdent: &mut E,
+        _parent: ParentContext<'_, Self::Item, Self::Scratch>,
         follow_link: bool,
         is_dir: bool,
         depth: Depth,
@@ -244,9 +306,15 @@ impl<E: fs::FsDirEntry> ContentProcessor<E> for DirEntryContentProcessor {
             metadata: metadata.unwrap(),
             file_name: file_name.unwrap(),
             depth,
+            sibling_info: None,
         }.into_some()
     }
 
+    /// Attach position among siblings to final entry
+    fn set_sibling_info(&self, item: &mut Self::Item, info: SiblingInfo) {
+        item.sibling_info = Some(info);
+    }
+
     /// Check if final entry is dir
     fn is_dir(item: &Self::Item) -> bool {
         item.is_dir()
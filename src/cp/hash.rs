@@ -0,0 +1,411 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, Once};
+use std::thread;
+
+use crate::cp::{ContentProcessor, DirEntry, DirEntryContentProcessor, KeyedItem, ParentContext};
+use crate::fs;
+use crate::wd::{Depth, SiblingInfo};
+
+/////////////////////////////////////////////////////////////////////////
+//// HashAlgorithm
+
+/// A hash algorithm for [`HashingContentProcessor`]
+///
+/// [`HashingContentProcessor`]: struct.HashingContentProcessor.html
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HashAlgorithm {
+    /// SHA-256 (requires `sha2` feature)
+    #[cfg(feature = "sha2")]
+    Sha256,
+    /// BLAKE3 (requires `blake3` feature)
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl Default for HashAlgorithm {
+    #[cfg(feature = "sha2")]
+    fn default() -> Self {
+        HashAlgorithm::Sha256
+    }
+
+    #[cfg(not(feature = "sha2"))]
+    fn default() -> Self {
+        HashAlgorithm::Blake3
+    }
+}
+
+impl HashAlgorithm {
+    /// Hash content of the file
+    pub fn hash_file<P: AsRef<Path>>(&self, path: P) -> io::Result<Digest> {
+        let mut file = std::fs::File::open(path)?;
+        let mut buf = vec![0; 64 * 1024];
+
+        match self {
+            #[cfg(feature = "sha2")]
+            HashAlgorithm::Sha256 => {
+                use sha2::Digest as _;
+                let mut hasher = sha2::Sha256::new();
+                loop {
+                    match file.read(&mut buf)? {
+                        0 => break,
+                        n => hasher.update(&buf[..n]),
+                    }
+                }
+                Ok(Digest(hasher.finalize().to_vec()))
+            }
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                loop {
+                    match file.read(&mut buf)? {
+                        0 => break,
+                        n => hasher.update(&buf[..n]),
+                    };
+                }
+                Ok(Digest(hasher.finalize().as_bytes().to_vec()))
+            }
+        }
+    }
+}
+
+/// A digest of file content (displayed as lowercase hex)
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Digest(pub Vec<u8>);
+
+impl Digest {
+    /// Raw bytes of the digest
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// HashedDirEntry
+
+/// A digest which may still be computed by a hashing thread.
+///
+/// It is a once-cell (`std::sync::OnceLock` needs a newer Rust than the MSRV):
+/// `value` is written only inside `init.call_once` and read only after it.
+struct DigestCell {
+    init: Once,
+    value: UnsafeCell<Option<io::Result<Digest>>>,
+    pending: Mutex<Option<mpsc::Receiver<io::Result<Digest>>>>,
+}
+
+// SAFETY: `value` is never accessed concurrently (see above)
+unsafe impl Sync for DigestCell {}
+
+impl DigestCell {
+    fn ready(value: Option<io::Result<Digest>>) -> Self {
+        let cell = Self { init: Once::new(), value: UnsafeCell::new(value), pending: Mutex::new(None) };
+        cell.init.call_once(|| {});
+        cell
+    }
+
+    fn pending(rx: mpsc::Receiver<io::Result<Digest>>) -> Self {
+        Self { init: Once::new(), value: UnsafeCell::new(None), pending: Mutex::new(Some(rx)) }
+    }
+
+    /// Waits for the digest (if it is still computed)
+    fn get(&self) -> Option<&io::Result<Digest>> {
+        self.init.call_once(|| {
+            if let Some(rx) = self.pending.lock().unwrap().take() {
+                let digest = rx.recv().unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "hash thread stopped")));
+                // SAFETY: no reference to `value` exists before `init` is completed
+                unsafe { *self.value.get() = Some(digest) };
+            }
+        });
+        // SAFETY: `init` is completed, so `value` is not written anymore
+        unsafe { (*self.value.get()).as_ref() }
+    }
+
+    /// The digest if it is computed already
+    fn peek(&self) -> Option<Option<&io::Result<Digest>>> {
+        if self.init.is_completed() {
+            Some(self.get())
+        } else {
+            None
+        }
+    }
+}
+
+/// A [`DirEntry`] with digest of its content.
+///
+/// Derefs to `DirEntry`. Digest is `None` for dirs, symlinks (when not
+/// followed) and files over the size limit.
+///
+/// [`DirEntry`]: struct.DirEntry.html
+pub struct HashedDirEntry<E: fs::FsDirEntry = fs::DefaultDirEntry> {
+    entry: DirEntry<E>,
+    digest: Arc<DigestCell>,
+}
+
+impl<E: fs::FsDirEntry> HashedDirEntry<E> {
+    /// Digest of file content or an error occured while reading the file.
+    ///
+    /// When files are hashed in threads, this waits for the result.
+    pub fn digest(&self) -> Option<&io::Result<Digest>> {
+        self.digest.get()
+    }
+
+    /// The entry itself
+    pub fn entry(&self) -> &DirEntry<E> {
+        &self.entry
+    }
+
+    /// Unwraps the entry (dropping the digest)
+    pub fn into_entry(self) -> DirEntry<E> {
+        self.entry
+    }
+}
+
+impl<E: fs::FsDirEntry> Deref for HashedDirEntry<E> {
+    type Target = DirEntry<E>;
+
+    fn deref(&self) -> &DirEntry<E> {
+        &self.entry
+    }
+}
+
+/// Clones share the digest
+impl<E: fs::FsDirEntry> Clone for HashedDirEntry<E> {
+    fn clone(&self) -> Self {
+        Self { entry: self.entry.clone(), digest: Arc::clone(&self.digest) }
+    }
+}
+
+impl<E: fs::FsDirEntry> fmt::Debug for HashedDirEntry<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashedDirEntry")
+            .field("entry", &self.entry)
+            .field("digest", &self.digest.peek())
+            .finish()
+    }
+}
+
+/// Keyed by file name
+impl<E: fs::FsDirEntry> KeyedItem for HashedDirEntry<E> {
+    type Key = E::FileName;
+
+    fn key(&self) -> Self::Key {
+        self.entry.file_name().clone()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// HashingContentProcessor
+
+type Job = (PathBuf, HashAlgorithm, mpsc::Sender<io::Result<Digest>>);
+
+/// Hashing threads (joined when the processor is dropped)
+struct HashPool {
+    jobs: Option<Mutex<mpsc::Sender<Job>>>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl HashPool {
+    fn new(n: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let threads = (0..n)
+            .map(|_| {
+                let rx = Arc::clone(&rx);
+                thread::spawn(move || loop {
+                    let job = rx.lock().unwrap().recv();
+                    match job {
+                        Ok((path, algorithm, result)) => {
+                            let _ = result.send(algorithm.hash_file(path));
+                        }
+                        // The processor is dropped
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        Self { jobs: Some(Mutex::new(tx)), threads }
+    }
+
+    fn submit(&self, job: Job) -> bool {
+        match self.jobs {
+            Some(ref jobs) => jobs.lock().unwrap().send(job).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Already submitted files are still hashed
+impl Drop for HashPool {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Content processor which yields [`HashedDirEntry`].
+///
+/// Each file is hashed once: by default in the walking thread right before
+/// it is yielded. With [`threads`] files are hashed by a pool of threads:
+/// all files of a dir are sent to it when the dir is opened, so hashing
+/// overlaps with the walk, and [`HashedDirEntry::digest`] waits for the
+/// result of its file (entries are still yielded in walk order).
+///
+/// ```no_run
+/// use walkdir2::WalkDirBuilder;
+/// use walkdir2::cp::HashingContentProcessor;
+/// use walkdir2::fs::DefaultDirEntry;
+///
+/// let cp = HashingContentProcessor::default().max_size(Some(1 << 20)).threads(4);
+/// let walk = WalkDirBuilder::<DefaultDirEntry, HashingContentProcessor>::new("foo")
+///     .content_processor(cp)
+///     .into_classic();
+/// for entry in walk {
+///     let entry = entry.unwrap();
+///     if let Some(Ok(digest)) = entry.digest() {
+///         println!("{}  {}", digest, entry.path().display());
+///     }
+/// }
+/// ```
+///
+/// [`HashedDirEntry`]: struct.HashedDirEntry.html
+/// [`HashedDirEntry::digest`]: struct.HashedDirEntry.html#method.digest
+/// [`threads`]: #method.threads
+#[derive(Default)]
+pub struct HashingContentProcessor {
+    inner: DirEntryContentProcessor,
+    algorithm: HashAlgorithm,
+    max_size: Option<u64>,
+    pool: Option<HashPool>,
+}
+
+impl fmt::Debug for HashingContentProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashingContentProcessor")
+            .field("algorithm", &self.algorithm)
+            .field("max_size", &self.max_size)
+            .field("threads", &self.pool.as_ref().map_or(0, |pool| pool.threads.len()))
+            .finish()
+    }
+}
+
+impl HashingContentProcessor {
+    /// Create with the given algorithm
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self { algorithm, ..Self::default() }
+    }
+
+    /// Hash only files which are not bigger than `limit` bytes (default: no limit)
+    pub fn max_size(mut self, limit: Option<u64>) -> Self {
+        self.max_size = limit;
+        self
+    }
+
+    /// Hash files in a pool of `n` threads (default: 0, in the walking thread)
+    pub fn threads(mut self, n: usize) -> Self {
+        self.pool = if n > 0 { Some(HashPool::new(n)) } else { None };
+        self
+    }
+
+    fn hash<E>(&self, entry: DirEntry<E>) -> HashedDirEntry<E>
+    where
+        E: fs::FsDirEntry<Path = Path, Metadata = std::fs::Metadata>,
+    {
+        let md = entry.metadata_ref();
+        let hashable = md.is_file()
+            && match self.max_size {
+                Some(limit) => md.len() <= limit,
+                None => true,
+            };
+        let digest = if !hashable {
+            DigestCell::ready(None)
+        } else if let Some(ref pool) = self.pool {
+            let (tx, rx) = mpsc::channel();
+            if pool.submit((entry.path().to_path_buf(), self.algorithm, tx)) {
+                DigestCell::pending(rx)
+            } else {
+                DigestCell::ready(Some(Err(io::Error::new(io::ErrorKind::Other, "hash thread stopped"))))
+            }
+        } else {
+            DigestCell::ready(Some(self.algorithm.hash_file(entry.path())))
+        };
+
+        HashedDirEntry { entry, digest: Arc::new(digest) }
+    }
+}
+
+impl<E> ContentProcessor<E> for HashingContentProcessor
+where
+    E: fs::FsDirEntry<Path = Path, Metadata = std::fs::Metadata>,
+{
+    type Item = HashedDirEntry<E>;
+    type Collection = Vec<HashedDirEntry<E>>;
+    type Scratch = ();
+
+    fn allow_push(&self, fsdent: &E, parent: ParentContext<'_, Self::Item, Self::Scratch>) -> bool {
+        let (item, scratch) = parent.into_parts();
+        let parent = ParentContext::new(item.map(|item| &item.entry), scratch);
+        ContentProcessor::<E>::allow_push(&self.inner, fsdent, parent)
+    }
+
+    fn process_root_direntry(
+        &self,
+        fsdent: &mut E::RootDirEntry,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let entry = self.inner.process_root_direntry(fsdent, follow_link, is_dir, depth, ctx)?;
+        Some(self.hash(entry))
+    }
+
+    fn process_direntry(
+        &self,
+        fsdent: &mut E,
+        parent: ParentContext<'_, Self::Item, Self::Scratch>,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let (item, scratch) = parent.into_parts();
+        let parent = ParentContext::new(item.map(|item| &item.entry), scratch);
+        let entry = self.inner.process_direntry(fsdent, parent, follow_link, is_dir, depth, ctx)?;
+        Some(self.hash(entry))
+    }
+
+    /// With threads all files of a dir are sent to them when it is opened
+    fn process_ahead(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    fn set_sibling_info(&self, item: &mut Self::Item, info: SiblingInfo) {
+        ContentProcessor::<E>::set_sibling_info(&self.inner, &mut item.entry, info);
+    }
+
+    fn is_dir(item: &Self::Item) -> bool {
+        item.entry.is_dir()
+    }
+
+    fn collect(&self, iter: impl Iterator<Item = Self::Item>) -> Self::Collection {
+        iter.collect()
+    }
+
+    fn empty_collection() -> Self::Collection {
+        vec![]
+    }
+}
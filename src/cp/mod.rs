@@ -1,24 +1,40 @@
 mod dent;
+mod collection;
+mod combinators;
+mod path;
+#[cfg(feature = "serde")]
+mod snapshot;
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+mod hash;
 
 use crate::fs;
-use crate::wd::Depth;
+use crate::wd::{Depth, SiblingInfo};
 
 pub use dent::{DirEntry, DirEntryContentProcessor};
+pub use collection::{ContentCollection, KeyedItem, WithCollection};
+pub use combinators::{filter_map, from_fn, map, FilterMap, FnContentProcessor, FsEntryMut, Map, Processed};
+pub use path::{PathContentProcessor, PathEntry};
+#[cfg(feature = "serde")]
+pub use snapshot::DirEntrySnapshot;
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+pub use hash::{Digest, HashAlgorithm, HashedDirEntry, HashingContentProcessor};
 
 //use std::iter::FromIterator;
 
 /// Convertor from RawDirEntry into final entry type (e.g. DirEntry)
 pub trait ContentProcessor<E: fs::FsDirEntry>: std::fmt::Debug {
-    /// Final entry type
-    type Item;
+    /// Final entry type (items of dirs are cloned for `OpenDir` and `CloseDir`)
+    type Item: Clone;
     /// Collection of items
-//    type Collection: FromIterator<Self::Item>;
-    type Collection;
+    type Collection: ContentCollection<Self::Item>;
+    /// State kept for each open dir and shared by processing of its entries
+    type Scratch: Default;
 
     /// Allow fsdent to be walked
     fn allow_push (
         &self,
         fsdent: &E,
+        parent: ParentContext<'_, Self::Item, Self::Scratch>,
     ) -> bool;
 
     /// Convert RawDirEntry into final entry type (e.g. DirEntry)
@@ -35,12 +51,31 @@ pub trait ContentProcessor<E: fs::FsDirEntry>: std::fmt::Debug {
     fn process_direntry(
         &self,
         fsdent: &mut E,
+        parent: ParentContext<'_, Self::Item, Self::Scratch>,
         follow_link: bool,
         is_dir: bool,
         depth: Depth,
         ctx: &mut E::Context,
     ) -> Option<Self::Item>;
 
+    /// Takes the error which made the last `process_*` call return `None`.
+    ///
+    /// The walker yields it in place of the entry. `None` (the default) means
+    /// that the entry was skipped on purpose.
+    fn take_error(&self) -> Option<E::Error> {
+        None
+    }
+
+    /// Make items of all entries of a dir as soon as it is opened (e.g. to start
+    /// background work for them); they are kept until they are yielded.
+    /// Default: `false` (an item is made right before it is yielded).
+    fn process_ahead(&self) -> bool {
+        false
+    }
+
+    /// Attach position among siblings to final entry (only when WalkDirBuilder::track_siblings is set)
+    fn set_sibling_info(&self, _item: &mut Self::Item, _info: SiblingInfo) {}
+
     /// Check if final entry is dir
     fn is_dir(item: &Self::Item) -> bool;
 
@@ -50,3 +85,46 @@ pub trait ContentProcessor<E: fs::FsDirEntry>: std::fmt::Debug {
     fn empty_collection() -> Self::Collection;
 }
 
+
+/////////////////////////////////////////////////////////////////////////
+//// ParentContext
+
+/// The dir containing an entry, as seen by `ContentProcessor::process_direntry`
+/// and `ContentProcessor::allow_push`: its already processed item and the
+/// processor's scratch state for this dir.
+///
+/// Scratch starts as `Default::default()` when the dir is opened and is
+/// dropped when it is closed. Each entry is processed once, so scratch is
+/// updated once per entry.
+#[derive(Debug)]
+pub struct ParentContext<'a, T, S> {
+    item: Option<&'a T>,
+    scratch: &'a mut S,
+}
+
+impl<'a, T, S> ParentContext<'a, T, S> {
+    /// Make context (e.g. to pass a part of it to a wrapped processor)
+    pub fn new(item: Option<&'a T>, scratch: &'a mut S) -> Self {
+        Self { item, scratch }
+    }
+
+    /// Processed item of the parent dir (`None` if the processor skipped it)
+    pub fn item(&self) -> Option<&'a T> {
+        self.item
+    }
+
+    /// Scratch state of the parent dir
+    pub fn scratch(&self) -> &S {
+        self.scratch
+    }
+
+    /// Scratch state of the parent dir (mutable)
+    pub fn scratch_mut(&mut self) -> &mut S {
+        self.scratch
+    }
+
+    /// Splits into the item and the scratch state
+    pub fn into_parts(self) -> (Option<&'a T>, &'a mut S) {
+        (self.item, self.scratch)
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::cp::{ContentProcessor, ParentContext};
+use crate::fs::{self, FsRootDirEntry};
+use crate::wd::{Depth, IntoSome, SiblingInfo};
+
+/////////////////////////////////////////////////////////////////////////
+//// PathEntry
+
+/// A lightweight directory entry: only the path, file type and depth.
+///
+/// Neither metadata nor file name is read or copied: with
+/// [`PathContentProcessor`] the only call per entry is reading its file type.
+///
+/// [`PathContentProcessor`]: struct.PathContentProcessor.html
+#[derive(Debug)]
+pub struct PathEntry<E: fs::FsDirEntry = fs::DefaultDirEntry> {
+    /// Path
+    path: E::PathBuf,
+    /// File type (of the symlink target if links are followed)
+    file_type: E::FileType,
+    /// Is normal dir
+    is_dir: bool,
+    /// The depth at which this entry was generated relative to the root.
+    depth: Depth,
+    /// Position among siblings
+    sibling_info: Option<SiblingInfo>,
+}
+
+impl<E: fs::FsDirEntry> Clone for PathEntry<E> {
+    fn clone(&self) -> Self {
+        Self {
+            path:           self.path.clone(),
+            file_type:      self.file_type,
+            is_dir:         self.is_dir,
+            depth:          self.depth,
+            sibling_info:   self.sibling_info.clone(),
+        }
+    }
+}
+
+impl<E: fs::FsDirEntry> PathEntry<E> {
+    /// The full path that this entry represents (see `DirEntry::path`).
+    pub fn path(&self) -> &E::Path {
+        &self.path
+    }
+
+    /// The full path that this entry represents.
+    ///
+    /// Analogous to [`path`], but moves ownership of the path.
+    ///
+    /// [`path`]: struct.PathEntry.html#method.path
+    pub fn into_path(self) -> E::PathBuf {
+        self.path
+    }
+
+    /// Return the file type for the file that this entry points to.
+    ///
+    /// If this is a symbolic link and [`follow_links`] is `true`, then this
+    /// returns the type of the target.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    pub fn file_type(&self) -> E::FileType {
+        self.file_type
+    }
+
+    /// Returns the depth at which this entry was created relative to the root.
+    pub fn depth(&self) -> Depth {
+        self.depth
+    }
+
+    /// Returns the position of this entry among its siblings.
+    ///
+    /// This is only available when [`track_siblings`] is enabled.
+    ///
+    /// [`track_siblings`]: struct.WalkDir.html#method.track_siblings
+    pub fn sibling_info(&self) -> Option<&SiblingInfo> {
+        self.sibling_info.as_ref()
+    }
+
+    /// Returns true if and only if this entry is a dir which is descended into.
+    pub(crate) fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// PathContentProcessor
+
+/// Convertor from RawDirEntry into PathEntry (the fastest configuration).
+///
+/// When the file type of an entry can't be read (e.g. it was removed during
+/// the walk), the error is yielded in place of the entry.
+pub struct PathContentProcessor<E: fs::FsDirEntry = fs::DefaultDirEntry> {
+    /// Error of the last entry (see `ContentProcessor::take_error`)
+    error: Mutex<Option<E::Error>>,
+}
+
+impl<E: fs::FsDirEntry> PathContentProcessor<E> {
+    /// Keeps the error for `take_error`
+    fn keep_error<T>(&self, result: Result<T, E::Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                *self.error.lock().unwrap() = Some(err);
+                None
+            }
+        }
+    }
+}
+
+impl<E: fs::FsDirEntry> Default for PathContentProcessor<E> {
+    fn default() -> Self {
+        Self { error: Mutex::new(None) }
+    }
+}
+
+impl<E: fs::FsDirEntry> fmt::Debug for PathContentProcessor<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PathContentProcessor").finish()
+    }
+}
+
+impl<E: fs::FsDirEntry> ContentProcessor<E> for PathContentProcessor<E> {
+    type Item = PathEntry<E>;
+    type Collection = Vec<PathEntry<E>>;
+    type Scratch = ();
+
+    fn allow_push(
+        &self,
+        _fsdent: &E,
+        _parent: ParentContext<'_, Self::Item, Self::Scratch>,
+    ) -> bool {
+        true
+    }
+
+    fn process_root_direntry(
+        &self,
+        fsdent: &mut E::RootDirEntry,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let file_type = self.keep_error(fsdent.file_type( follow_link, ctx ))?;
+        let (path, _, _) = fsdent.to_parts( follow_link, false, false, ctx );
+
+        Self::Item { path, file_type, is_dir, depth, sibling_info: None }.into_some()
+    }
+
+    fn process_direntry(
+        &self,
+        fsdent: &mut E,
+        _parent: ParentContext<'_, Self::Item, Self::Scratch>,
+        follow_link: bool,
+        is_dir: bool,
+        depth: Depth,
+        ctx: &mut E::Context,
+    ) -> Option<Self::Item> {
+        let file_type = self.keep_error(fsdent.file_type( follow_link, ctx ))?;
+        let (path, _, _) = fsdent.to_parts( follow_link, false, false, ctx );
+
+        Self::Item { path, file_type, is_dir, depth, sibling_info: None }.into_some()
+    }
+
+    fn take_error(&self) -> Option<E::Error> {
+        self.error.lock().unwrap().take()
+    }
+
+    fn set_sibling_info(&self, item: &mut Self::Item, info: SiblingInfo) {
+        item.sibling_info = Some(info);
+    }
+
+    fn is_dir(item: &Self::Item) -> bool {
+        item.is_dir()
+    }
+
+    fn collect(&self, iter: impl Iterator<Item = Self::Item>) -> Self::Collection {
+        iter.collect()
+    }
+
+    fn empty_collection() -> Self::Collection {
+        vec![]
+    }
+}
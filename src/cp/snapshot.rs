@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::cp::DirEntry;
+use crate::fs::{self, FsPath};
+use crate::wd::{Depth, SiblingInfo};
+
+/////////////////////////////////////////////////////////////////////////////////
+
+/// An owned copy of [`DirEntry`] which can be serialized and deserialized.
+///
+/// `DirEntry` itself is serialized in exactly this shape, so the output of
+/// serializing a `DirEntry` can be deserialized into a `DirEntrySnapshot`
+/// (a `DirEntry` can't be deserialized, since its metadata can't be created
+/// other than by the fs).
+///
+/// [`DirEntry`]: struct.DirEntry.html
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct DirEntrySnapshot {
+    /// Full path of the entry
+    pub path: PathBuf,
+    /// Depth relative to the root
+    pub depth: Depth,
+    /// The entry is a dir
+    pub is_dir: bool,
+    /// The entry is a file
+    pub is_file: bool,
+    /// The entry is a symlink (not followed)
+    pub is_symlink: bool,
+    /// The entry was created from a symlink (see `DirEntry::path_is_symlink`)
+    pub path_is_symlink: bool,
+    /// Size in bytes
+    pub len: u64,
+    /// Last modification time, if available on this platform
+    pub modified: Option<SystemTime>,
+    /// Read-only flag
+    pub readonly: bool,
+    /// Position among siblings (when `track_siblings` is set)
+    pub sibling_info: Option<SiblingInfo>,
+}
+
+impl<E> From<&DirEntry<E>> for DirEntrySnapshot
+where
+    E: fs::FsDirEntry<PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    fn from(dent: &DirEntry<E>) -> Self {
+        let md = dent.metadata_ref();
+        let ty = md.file_type();
+        Self {
+            path:               dent.path().to_path_buf(),
+            depth:              dent.depth(),
+            is_dir:             ty.is_dir(),
+            is_file:            ty.is_file(),
+            is_symlink:         ty.is_symlink(),
+            path_is_symlink:    dent.path_is_symlink(),
+            len:                md.len(),
+            modified:           md.modified().ok(),
+            readonly:           md.permissions().readonly(),
+            sibling_info:       dent.sibling_info().cloned(),
+        }
+    }
+}
+
+impl<E> Serialize for DirEntry<E>
+where
+    E: fs::FsDirEntry<PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DirEntrySnapshot::from(self).serialize(serializer)
+    }
+}
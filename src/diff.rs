@@ -0,0 +1,260 @@
+//! Streaming diff of two dir trees.
+//!
+//! Both trees are walked in lockstep with entries sorted by name, so the
+//! entries of the two walks can be merged like two sorted lists. Only the
+//! content of currently opened dirs is kept in memory.
+//!
+//! ```no_run
+//! use walkdir2::diff::{self, DiffEvent};
+//!
+//! for event in diff::diff("old", "new") {
+//!     match event {
+//!         Ok(DiffEvent::Added(dent)) => println!("+ {}", dent.relative_path().display()),
+//!         Ok(DiffEvent::Removed(dent)) => println!("- {}", dent.relative_path().display()),
+//!         Ok(event) => println!("~ {}", event.relative_path().display()),
+//!         Err(err) => eprintln!("ERROR: {}", err),
+//!     }
+//! }
+//! ```
+
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::cp::{DirEntry, DirEntryContentProcessor};
+use crate::error::Error;
+use crate::fs::{self, FsFileType};
+use crate::util::mode;
+use crate::walk::{WalkDirBuilder, WalkDirIterator};
+use crate::wd::{self, Position};
+
+/////////////////////////////////////////////////////////////////////////
+//// DiffEvent
+
+/// What was changed in a modified entry
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Modification {
+    /// Size differs (files only)
+    pub size: bool,
+    /// Modification time differs (files only)
+    pub mtime: bool,
+    /// Permissions differ
+    pub mode: bool,
+}
+
+impl Modification {
+    /// Is there any change?
+    pub fn any(&self) -> bool {
+        self.size || self.mtime || self.mode
+    }
+}
+
+/// A difference between two trees.
+///
+/// Entries of the left tree are "removed", entries of the right tree are
+/// "added". A dir which exists in one tree only is reported once (its content
+/// is not walked).
+#[derive(Debug)]
+pub enum DiffEvent<E: fs::FsDirEntry = fs::DefaultDirEntry> {
+    /// Entry exists in the right tree only
+    Added(DirEntry<E>),
+    /// Entry exists in the left tree only
+    Removed(DirEntry<E>),
+    /// Entry exists in both trees, but its type (file, dir or symlink) differs
+    TypeChanged(DirEntry<E>, DirEntry<E>),
+    /// Entry exists in both trees with the same type, but differs
+    Modified(DirEntry<E>, DirEntry<E>, Modification),
+}
+
+impl<E: fs::FsDirEntry<Path = Path>> DiffEvent<E> {
+    /// Path of the changed entry relative to the roots
+    pub fn relative_path(&self) -> &Path {
+        match self {
+            DiffEvent::Added(dent) | DiffEvent::Removed(dent) => dent.relative_path(),
+            DiffEvent::TypeChanged(_, dent) | DiffEvent::Modified(_, dent, _) => dent.relative_path(),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// TreeDiff
+
+/// Diffs two trees with default options (see [`TreeDiff`]).
+///
+/// [`TreeDiff`]: struct.TreeDiff.html
+pub fn diff<P: AsRef<Path>, Q: AsRef<Path>>(left: P, right: Q) -> TreeDiff {
+    TreeDiff::new(WalkDirBuilder::new(left), WalkDirBuilder::new(right))
+}
+
+/// An iterator over differences between two trees.
+///
+/// Values of this type are created by [`diff`] or [`TreeDiff::new`]. Errors
+/// of both walks are passed through.
+///
+/// Subtrees are pruned when both sides are known to be the same dir (same
+/// device and inode on unix, e.g. hard links to dirs or bind mounts of the
+/// same dir); otherwise both subtrees are walked.
+///
+/// [`diff`]: fn.diff.html
+/// [`TreeDiff::new`]: struct.TreeDiff.html#method.new
+#[derive(Debug)]
+pub struct TreeDiff<E = fs::DefaultDirEntry>
+where
+    E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    left: WalkDirIterator<E, DirEntryContentProcessor>,
+    right: WalkDirIterator<E, DirEntryContentProcessor>,
+    left_next: Option<DirEntry<E>>,
+    right_next: Option<DirEntry<E>>,
+    compare_mtime: bool,
+    compare_mode: bool,
+}
+
+impl<E> TreeDiff<E>
+where
+    E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    /// Create a diff of the trees walked by the given builders.
+    ///
+    /// Builders may have any options, except that their sort order is
+    /// replaced by name and `contents_first` is turned off.
+    pub fn new(
+        left: WalkDirBuilder<E, DirEntryContentProcessor>,
+        right: WalkDirBuilder<E, DirEntryContentProcessor>,
+    ) -> Self {
+        Self {
+            left: Self::prepare(left),
+            right: Self::prepare(right),
+            left_next: None,
+            right_next: None,
+            compare_mtime: true,
+            compare_mode: true,
+        }
+    }
+
+    fn prepare(builder: WalkDirBuilder<E, DirEntryContentProcessor>) -> WalkDirIterator<E, DirEntryContentProcessor> {
+        builder
+            .contents_first(false)
+            .sort_by(|a, b, _| a.0.path().cmp(b.0.path()))
+            .build()
+    }
+
+    /// Report files with different modification time as modified (default `true`)
+    pub fn compare_mtime(mut self, yes: bool) -> Self {
+        self.compare_mtime = yes;
+        self
+    }
+
+    /// Report entries with different permission bits as modified (default `true`)
+    pub fn compare_mode(mut self, yes: bool) -> Self {
+        self.compare_mode = yes;
+        self
+    }
+
+    /// Fetch next entry of the walk into `slot` (if it is empty)
+    fn fill(
+        iter: &mut WalkDirIterator<E, DirEntryContentProcessor>,
+        slot: &mut Option<DirEntry<E>>,
+    ) -> Option<Error<E>> {
+        while slot.is_none() {
+            match iter.next()? {
+                Position::Entry(dent) => *slot = Some(dent),
+                Position::Error(err) => return Some(err),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn same_type(left: &DirEntry<E>, right: &DirEntry<E>) -> bool {
+        let (l, r) = (left.file_type(), right.file_type());
+        l.is_dir() == r.is_dir() && l.is_symlink() == r.is_symlink()
+    }
+
+    fn modification(&self, left: &std::fs::Metadata, right: &std::fs::Metadata) -> Modification {
+        let is_dir = left.is_dir();
+        Modification {
+            size: !is_dir && left.len() != right.len(),
+            mtime: self.compare_mtime && !is_dir && left.modified().ok() != right.modified().ok(),
+            mode: self.compare_mode && mode(left) != mode(right),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_dir(left: &std::fs::Metadata, right: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    left.dev() == right.dev() && left.ino() == right.ino()
+}
+
+#[cfg(not(unix))]
+fn same_dir(_left: &std::fs::Metadata, _right: &std::fs::Metadata) -> bool {
+    false
+}
+
+impl<E> Iterator for TreeDiff<E>
+where
+    E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    type Item = wd::Result<DiffEvent<E>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(err) = Self::fill(&mut self.left, &mut self.left_next) {
+                return Some(Err(err));
+            }
+            if let Some(err) = Self::fill(&mut self.right, &mut self.right_next) {
+                return Some(Err(err));
+            }
+
+            let ord = match (&self.left_next, &self.right_next) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(l), Some(r)) => l.relative_path().cmp(r.relative_path()),
+            };
+
+            match ord {
+                Ordering::Less => {
+                    let l = self.left_next.take().unwrap();
+                    if l.is_dir() {
+                        self.left.skip_current_dir();
+                    }
+                    return Some(Ok(DiffEvent::Removed(l)));
+                }
+                Ordering::Greater => {
+                    let r = self.right_next.take().unwrap();
+                    if r.is_dir() {
+                        self.right.skip_current_dir();
+                    }
+                    return Some(Ok(DiffEvent::Added(r)));
+                }
+                Ordering::Equal => {}
+            }
+
+            let l = self.left_next.take().unwrap();
+            let r = self.right_next.take().unwrap();
+
+            if !Self::same_type(&l, &r) {
+                if l.is_dir() {
+                    self.left.skip_current_dir();
+                }
+                if r.is_dir() {
+                    self.right.skip_current_dir();
+                }
+                return Some(Ok(DiffEvent::TypeChanged(l, r)));
+            }
+
+            let (lmd, rmd) = (l.metadata_ref(), r.metadata_ref());
+            if l.is_dir() && r.is_dir() && same_dir(lmd, rmd) {
+                self.left.skip_current_dir();
+                self.right.skip_current_dir();
+                continue;
+            }
+
+            let modification = self.modification(lmd, rmd);
+            if modification.any() {
+                return Some(Ok(DiffEvent::Modified(l, r, modification)));
+            }
+        }
+    }
+}
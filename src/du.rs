@@ -0,0 +1,211 @@
+//! Disk usage of dir trees.
+//!
+//! Sizes are aggregated bottom-up with [`WalkDirIter::fold_tree`], so the
+//! usage of each dir is yielded when the dir is closed (the root is yielded
+//! last). Each hard-linked file (same device and inode on unix) is counted
+//! only once. Options of the builder are honored, e.g. with
+//! `same_file_system` dirs on other file systems are not counted.
+//!
+//! ```no_run
+//! use walkdir2::WalkDir;
+//! use walkdir2::du::DiskUsageIter;
+//!
+//! let top = DiskUsageIter::new(WalkDir::new("/home").same_file_system(true)).top(10);
+//! for (path, usage) in &top.dirs {
+//!     println!("{:>12} {}", usage.allocated, path.display());
+//! }
+//! ```
+//!
+//! [`WalkDirIter::fold_tree`]: ../trait.WalkDirIter.html#method.fold_tree
+
+use std::collections::HashSet;
+use std::ops::{Add, AddAssign};
+use std::path::{Path, PathBuf};
+
+use crate::cp::{DirEntry, DirEntryContentProcessor};
+use crate::fs;
+use crate::walk::{ErrorReport, FoldTree, WalkDirBuilder, WalkDirIter, WalkDirIterator};
+use crate::wd;
+
+/////////////////////////////////////////////////////////////////////////
+//// DiskUsage
+
+/// Disk usage of a subtree
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct DiskUsage {
+    /// Sum of file sizes in bytes
+    pub apparent: u64,
+    /// Bytes allocated on disk (the same as `apparent` on non-unix platforms)
+    pub allocated: u64,
+    /// Count of files (hard links to a counted file are not counted again)
+    pub files: u64,
+    /// Count of dirs (including the dir itself)
+    pub dirs: u64,
+}
+
+impl DiskUsage {
+    fn of(md: &std::fs::Metadata) -> Self {
+        Self {
+            apparent: md.len(),
+            allocated: allocated(md),
+            files: (!md.is_dir()) as u64,
+            dirs: md.is_dir() as u64,
+        }
+    }
+}
+
+impl Add for DiskUsage {
+    type Output = DiskUsage;
+
+    fn add(mut self, other: DiskUsage) -> DiskUsage {
+        self += other;
+        self
+    }
+}
+
+impl AddAssign for DiskUsage {
+    fn add_assign(&mut self, other: DiskUsage) {
+        self.apparent += other.apparent;
+        self.allocated += other.allocated;
+        self.files += other.files;
+        self.dirs += other.dirs;
+    }
+}
+
+#[cfg(unix)]
+fn allocated(md: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    md.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated(md: &std::fs::Metadata) -> u64 {
+    md.len()
+}
+
+/// Returns the identity of a file with several hard links
+#[cfg(unix)]
+fn hard_link_id(md: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    if md.nlink() > 1 {
+        Some((md.dev(), md.ino()))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_md: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// DiskUsageIter
+
+type FnLeaf<E> = Box<dyn FnMut(&DirEntry<E>) -> DiskUsage + Send + Sync + 'static>;
+type FnCombine<E> = Box<dyn FnMut(&DirEntry<E>, Vec<DiskUsage>) -> DiskUsage + Send + Sync + 'static>;
+
+/// An iterator over dirs with their disk usage (including subdirs).
+///
+/// Errors are passed through, unreadable entries are not counted.
+pub struct DiskUsageIter<E = fs::DefaultDirEntry>
+where
+    E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    inner: FoldTree<
+        E,
+        DirEntryContentProcessor,
+        WalkDirIterator<E, DirEntryContentProcessor>,
+        FnLeaf<E>,
+        FnCombine<E>,
+        DiskUsage,
+    >,
+}
+
+impl<E> std::fmt::Debug for DiskUsageIter<E>
+where
+    E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskUsageIter").finish()
+    }
+}
+
+/// Walks the given root with default options (see [`DiskUsageIter`]).
+///
+/// [`DiskUsageIter`]: struct.DiskUsageIter.html
+pub fn du<P: AsRef<Path>>(root: P) -> DiskUsageIter {
+    DiskUsageIter::new(WalkDirBuilder::new(root))
+}
+
+impl<E> DiskUsageIter<E>
+where
+    E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    /// Create from the builder (`contents_first` is turned off).
+    pub fn new(builder: WalkDirBuilder<E, DirEntryContentProcessor>) -> Self {
+        let mut seen: HashSet<(u64, u64)> = HashSet::new();
+        let leaf: FnLeaf<E> = Box::new(move |dent| {
+            let md = dent.metadata_ref();
+            match hard_link_id(md) {
+                Some(id) if !seen.insert(id) => DiskUsage::default(),
+                _ => DiskUsage::of(md),
+            }
+        });
+        let combine: FnCombine<E> = Box::new(|dir, children| {
+            children.into_iter().fold(DiskUsage::of(dir.metadata_ref()), Add::add)
+        });
+
+        Self { inner: builder.contents_first(false).build().fold_tree(leaf, combine) }
+    }
+
+    /// Consumes the walk and returns `n` largest subtrees (by allocated size)
+    pub fn top(self, n: usize) -> TopUsage<E> {
+        let mut top = TopUsage { dirs: vec![], total: DiskUsage::default(), errors: ErrorReport::default() };
+
+        for result in self {
+            let (dir, usage) = match result {
+                Ok(r) => r,
+                Err(err) => {
+                    top.errors.push(err);
+                    continue;
+                }
+            };
+            if dir.depth() == 0 {
+                top.total = usage;
+            }
+
+            let at = top.dirs.iter().position(|(_, u)| u.allocated < usage.allocated).unwrap_or(top.dirs.len());
+            if at < n {
+                top.dirs.insert(at, (dir.into_path(), usage));
+                top.dirs.truncate(n);
+            }
+        }
+
+        top
+    }
+}
+
+impl<E> Iterator for DiskUsageIter<E>
+where
+    E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, Metadata = std::fs::Metadata>,
+{
+    type Item = wd::Result<(DirEntry<E>, DiskUsage), E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// Result of [`DiskUsageIter::top`]
+///
+/// [`DiskUsageIter::top`]: struct.DiskUsageIter.html#method.top
+#[derive(Debug)]
+pub struct TopUsage<E: fs::FsDirEntry = fs::DefaultDirEntry> {
+    /// Largest subtrees, the largest first
+    pub dirs: Vec<(PathBuf, DiskUsage)>,
+    /// Usage of the whole tree
+    pub total: DiskUsage,
+    /// Errors occured while walking
+    pub errors: ErrorReport<E>,
+}
@@ -0,0 +1,297 @@
+//! Duplicate files finder.
+//!
+//! Files are grouped by size, then by hash of their first bytes, then by hash
+//! of full content, so most files are never read. The remaining candidates
+//! are compared byte by byte. Hard links to the same file (same device and
+//! inode on unix) are treated as one file.
+//!
+//! ```no_run
+//! use walkdir2::dupes::DuplicateFinder;
+//!
+//! let dupes = DuplicateFinder::new().root("photos").root("backup").find();
+//! for group in &dupes.groups {
+//!     println!("{} bytes reclaimable:", group.reclaimable());
+//!     for file in &group.files {
+//!         println!("  {}", file[0].display());
+//!     }
+//! }
+//! println!("total: {} bytes", dupes.reclaimable());
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::walk::{ErrorReport, WalkDirIter};
+use crate::wd::Position;
+use crate::WalkDir;
+
+/////////////////////////////////////////////////////////////////////////
+//// Duplicates
+
+/// A group of files with the same content
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DuplicateGroup {
+    /// Size of each file
+    pub size: u64,
+    /// Distinct files (at least two), each one is the list of its paths (hard links)
+    pub files: Vec<Vec<PathBuf>>,
+}
+
+impl DuplicateGroup {
+    /// Bytes which can be freed by keeping only one file of the group
+    pub fn reclaimable(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+/// Result of [`DuplicateFinder::find`]
+///
+/// [`DuplicateFinder::find`]: struct.DuplicateFinder.html#method.find
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    /// Groups of duplicates, the most reclaimable first
+    pub groups: Vec<DuplicateGroup>,
+    /// Errors occured while walking
+    pub walk_errors: ErrorReport,
+    /// Errors occured while reading files (such files are not reported as duplicates)
+    pub read_errors: Vec<(PathBuf, io::Error)>,
+}
+
+impl Duplicates {
+    /// Total bytes which can be freed
+    pub fn reclaimable(&self) -> u64 {
+        self.groups.iter().map(DuplicateGroup::reclaimable).sum()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// DuplicateFinder
+
+/// A distinct file with all its paths
+#[derive(Debug)]
+struct Candidate {
+    paths: Vec<PathBuf>,
+}
+
+/// Finds duplicate files in one or more trees.
+///
+/// Symlinks are not followed. Files with the same size and hashes are
+/// finally compared byte by byte, so all files of a group have the same
+/// content.
+#[derive(Debug, Clone)]
+pub struct DuplicateFinder {
+    roots: Vec<PathBuf>,
+    min_size: u64,
+    partial_size: u64,
+}
+
+impl Default for DuplicateFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DuplicateFinder {
+    /// Create without roots
+    pub fn new() -> Self {
+        Self { roots: vec![], min_size: 1, partial_size: 4096 }
+    }
+
+    /// Add a tree to search in
+    pub fn root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.roots.push(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Skip files smaller than `size` bytes (default 1, i.e. skip empty files)
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    /// Count of first bytes hashed before hashing full content (default 4096)
+    pub fn partial_size(mut self, size: u64) -> Self {
+        self.partial_size = size;
+        self
+    }
+
+    /// Walk all roots and find duplicates
+    pub fn find(&self) -> Duplicates {
+        let mut result = Duplicates::default();
+
+        let by_size = self.collect(&mut result);
+
+        let mut groups = vec![];
+        for (size, candidates) in by_size {
+            if candidates.len() < 2 {
+                continue;
+            }
+            let partial = self.split_by_hash(candidates, Some(self.partial_size), &mut result.read_errors);
+            for candidates in partial {
+                let full = if size <= self.partial_size {
+                    vec![candidates]
+                } else {
+                    self.split_by_hash(candidates, None, &mut result.read_errors)
+                };
+                for candidates in full.into_iter().flat_map(|c| split_by_content(c, &mut result.read_errors)) {
+                    let mut files: Vec<Vec<PathBuf>> = candidates.into_iter().map(|c| c.paths).collect();
+                    for paths in &mut files {
+                        paths.sort();
+                    }
+                    files.sort();
+                    groups.push(DuplicateGroup { size, files });
+                }
+            }
+        }
+
+        groups.sort_by(|a, b| b.reclaimable().cmp(&a.reclaimable()).then_with(|| a.files.cmp(&b.files)));
+        result.groups = groups;
+        result
+    }
+
+    /// Walk all roots and group distinct files by size
+    fn collect(&self, result: &mut Duplicates) -> HashMap<u64, Vec<Candidate>> {
+        let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+        let mut by_id: HashMap<(u64, u64), (u64, usize)> = HashMap::new();
+
+        for root in &self.roots {
+            let mut iter = WalkDir::new(root).into_iter().collect_errors();
+            for pos in &mut iter {
+                let dent = match pos {
+                    Position::Entry(dent) => dent,
+                    _ => continue,
+                };
+                let md = dent.metadata_ref();
+                if !md.is_file() || md.len() < self.min_size {
+                    continue;
+                }
+
+                let size = md.len();
+                let id = file_id(md);
+                let path = dent.into_path();
+                match id.and_then(|id| by_id.get(&id)) {
+                    Some(&(size, idx)) => {
+                        let paths = &mut by_size.get_mut(&size).unwrap()[idx].paths;
+                        if !paths.contains(&path) {
+                            paths.push(path);
+                        }
+                    }
+                    None => {
+                        let candidates = by_size.entry(size).or_default();
+                        if let Some(id) = id {
+                            by_id.insert(id, (size, candidates.len()));
+                        }
+                        candidates.push(Candidate { paths: vec![path] });
+                    }
+                }
+            }
+            for err in iter.into_report().into_errors() {
+                result.walk_errors.push(err);
+            }
+        }
+
+        by_size
+    }
+
+    /// Split candidates into groups (of two or more) with the same hash
+    fn split_by_hash(
+        &self,
+        candidates: Vec<Candidate>,
+        limit: Option<u64>,
+        errors: &mut Vec<(PathBuf, io::Error)>,
+    ) -> Vec<Vec<Candidate>> {
+        let mut by_hash: HashMap<u64, Vec<Candidate>> = HashMap::new();
+        for candidate in candidates {
+            match hash_file(&candidate.paths[0], limit) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(candidate),
+                Err(err) => errors.push((candidate.paths[0].clone(), err)),
+            }
+        }
+        by_hash.into_iter().map(|(_, group)| group).filter(|group| group.len() > 1).collect()
+    }
+}
+
+/// Split candidates into groups (of two or more) with the same content
+fn split_by_content(candidates: Vec<Candidate>, errors: &mut Vec<(PathBuf, io::Error)>) -> Vec<Vec<Candidate>> {
+    let mut groups: Vec<Vec<Candidate>> = vec![];
+    'candidates: for candidate in candidates {
+        for group in &mut groups {
+            match same_content(&group[0].paths[0], &candidate.paths[0]) {
+                Ok(true) => {
+                    group.push(candidate);
+                    continue 'candidates;
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    errors.push((candidate.paths[0].clone(), err));
+                    continue 'candidates;
+                }
+            }
+        }
+        groups.push(vec![candidate]);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Compare content of two files
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (std::fs::File::open(a)?, std::fs::File::open(b)?);
+    let (mut buf_a, mut buf_b) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let n = read_full(&mut a, &mut buf_a)?;
+        if n != read_full(&mut b, &mut buf_b)? || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Read until `buf` is full or the end of file is reached
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(unix)]
+fn file_id(md: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((md.dev(), md.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_md: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Hash first `limit` bytes of the file (or the whole file)
+fn hash_file(path: &Path, limit: Option<u64>) -> io::Result<u64> {
+    let file = std::fs::File::open(path)?;
+    let mut reader: Box<dyn Read> = match limit {
+        Some(limit) => Box::new(file.take(limit)),
+        None => Box::new(file),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => hasher.write(&buf[..n]),
+        }
+    }
+    Ok(hasher.finish())
+}
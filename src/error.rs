@@ -1,7 +1,7 @@
 use std::fmt;
 
 use crate::fs;
-use crate::fs::{FsPath, FsPathBuf};
+use crate::fs::{FsError, FsPath, FsPathBuf};
 use crate::wd::Depth;
 
 /// An error produced by recursively walking a directory.
@@ -27,35 +27,101 @@ use crate::wd::Depth;
 pub struct Error<E: fs::FsDirEntry = fs::DefaultDirEntry> {
     inner: ErrorInner<E>,
     depth: Depth,
+    /// Kind restored by deserialization (the recreated inner error may not carry it)
+    kind: Option<ErrorKind>,
+}
+
+/// A kind of error
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    /// Missing permission to access an entry
+    PermissionDenied,
+    /// An entry was not found (e.g. removed while walking or broken symlink)
+    NotFound,
+    /// A file system loop found
+    Loop,
+    /// An entry expected to be a dir is not a dir
+    NotADirectory,
+    /// Limit of opened files is reached
+    TooManyOpenFiles,
+    /// Any other error
+    Other,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorKind::PermissionDenied => "permission denied",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::Loop => "file system loop",
+            ErrorKind::NotADirectory => "not a directory",
+            ErrorKind::TooManyOpenFiles => "too many open files",
+            ErrorKind::Other => "other error",
+        };
+        f.write_str(s)
+    }
+}
+
+/// An operation which failed
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    /// Opening a dir for reading
+    ReadDir,
+    /// Reading next entry of a dir
+    NextEntry,
+    /// Getting metadata (or file type) of an entry
+    Metadata,
+    /// Following a symlink (including loop detection)
+    FollowLink,
+    /// Getting fingerprint of a dir (for loop detection)
+    Fingerprint,
+    /// Getting device num (for same_file_system)
+    DeviceNum,
+    /// Reading the content of a file (e.g. hashing it for a manifest)
+    ReadFile,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Operation::ReadDir => "read_dir",
+            Operation::NextEntry => "next entry",
+            Operation::Metadata => "metadata",
+            Operation::FollowLink => "follow symlink",
+            Operation::Fingerprint => "fingerprint",
+            Operation::DeviceNum => "device_num",
+            Operation::ReadFile => "read file",
+        };
+        f.write_str(s)
+    }
 }
 
 #[derive(Debug)]
 pub enum ErrorInner<E: fs::FsDirEntry> {
-    Io { path: Option<E::PathBuf>, err: Option<E::Error> },
+    Io { path: Option<E::PathBuf>, op: Operation, err: Option<E::Error> },
     Loop { ancestor: E::PathBuf, child: E::PathBuf },
 }
 
 impl<E: fs::FsDirEntry> ErrorInner<E> {
-    pub(crate) fn from_path(pb: E::PathBuf, err: E::Error) -> Self {
-        Self::Io { path: Some(pb), err: Some(err) }
+    pub(crate) fn from_path(pb: E::PathBuf, op: Operation, err: E::Error) -> Self {
+        Self::Io { path: Some(pb), op, err: Some(err) }
     }
 
     // pub(crate) fn from_entry(fsdent: &E, err: E::Error) -> Self {
     //     Self::Io { path: Some(fsdent.path().to_path_buf()), err: Some(err) }
     // }
 
-    pub(crate) fn from_io(err: E::Error) -> Self {
-        Self::Io { path: None, err: Some(err) }
-    }
-
     pub(crate) fn from_loop(ancestor: &E::Path, child: &E::Path) -> Self {
         Self::Loop { ancestor: ancestor.to_path_buf(), child: child.to_path_buf() }
     }
 
     pub fn take(&mut self) -> Self {
         match self {
-            Self::Io { path, err } => Self::Io { 
+            Self::Io { path, op, err } => Self::Io { 
                 path: path.clone(), 
+                op: *op,
                 err: err.take() 
             },
             Self::Loop { ancestor, child } => Self::Loop { 
@@ -92,13 +158,13 @@ impl<E: fs::FsDirEntry> std::error::Error for Error<E> {
 impl<E: fs::FsDirEntry> fmt::Display for Error<E> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.inner {
-            ErrorInner::Io { path: None, err: Some(ref err) } => err.fmt(f),
-            ErrorInner::Io { path: None, err: None } => write!(f, "IO error for operation"),
-            ErrorInner::Io { path: Some(ref path), err: Some(ref err) } => {
-                write!(f, "IO error for operation on {}: {}", path.display(), err)
+            ErrorInner::Io { path: None, err: Some(ref err), .. } => err.fmt(f),
+            ErrorInner::Io { path: None, op, err: None } => write!(f, "IO error for {}", op),
+            ErrorInner::Io { path: Some(ref path), op, err: Some(ref err) } => {
+                write!(f, "IO error for {} on {}: {}", op, path.display(), err)
             }
-            ErrorInner::Io { path: Some(ref path), err: None } => {
-                write!(f, "IO error for operation on {}", path.display())
+            ErrorInner::Io { path: Some(ref path), op, err: None } => {
+                write!(f, "IO error for {} on {}", op, path.display())
             }
             ErrorInner::Loop { ref ancestor, ref child } => write!(
                 f,
@@ -187,6 +253,33 @@ impl<E: fs::FsDirEntry> Error<E> {
         self.depth
     }
 
+    /// Returns the kind of this error.
+    ///
+    /// For IO errors the kind is determined by the underlying error (see
+    /// [`FsError::kind`]), a detected cycle is always `ErrorKind::Loop`.
+    ///
+    /// [`FsError::kind`]: fs/trait.FsError.html#method.kind
+    pub fn kind(&self) -> ErrorKind {
+        if let Some(kind) = self.kind {
+            return kind;
+        }
+        match self.inner {
+            ErrorInner::Io { err: Some(ref err), .. } => err.kind(),
+            ErrorInner::Io { err: None, .. } => ErrorKind::Other,
+            ErrorInner::Loop { .. } => ErrorKind::Loop,
+        }
+    }
+
+    /// Returns the operation which failed.
+    ///
+    /// A detected cycle is reported as `Operation::FollowLink`.
+    pub fn operation(&self) -> Operation {
+        match self.inner {
+            ErrorInner::Io { op, .. } => op,
+            ErrorInner::Loop { .. } => Operation::FollowLink,
+        }
+    }
+
     /// Inspect the original [`io::Error`] if there is one.
     ///
     /// [`None`] is returned if the [`Error`] doesn't correspond to an
@@ -265,17 +358,88 @@ impl<E: fs::FsDirEntry> Error<E> {
     }
 
     pub(crate) fn from_inner(inner: ErrorInner<E>, depth: Depth) -> Self {
-        Self { inner, depth }
+        Self { inner, depth, kind: None }
     }
 }
 
-pub fn into_io_err<E: fs::FsDirEntry>(err: E::Error) -> ErrorInner<E> {
-    ErrorInner::<E>::from_io(err)
-}
-
 pub fn into_path_err<E: fs::FsDirEntry, P: AsRef<E::Path>>(
     path: P,
+    op: Operation,
     err: E::Error,
 ) -> ErrorInner<E> {
-    ErrorInner::<E>::from_path(path.as_ref().to_path_buf(), err)
+    ErrorInner::<E>::from_path(path.as_ref().to_path_buf(), op, err)
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// serde
+
+/// Serialized form of Error
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ErrorRepr<P> {
+    depth: Depth,
+    kind: ErrorKind,
+    operation: Operation,
+    path: Option<P>,
+    loop_ancestor: Option<P>,
+    message: Option<String>,
+    raw_os_error: Option<i32>,
+}
+
+/// Serializes the depth, kind, operation, paths and the message of the
+/// underlying error.
+#[cfg(feature = "serde")]
+impl<E> serde::Serialize for Error<E>
+where
+    E: fs::FsDirEntry<Error = std::io::Error>,
+    E::PathBuf: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let err = self.io_error();
+        ErrorRepr {
+            depth: self.depth,
+            kind: self.kind(),
+            operation: self.operation(),
+            path: self.path().map(FsPath::to_path_buf),
+            loop_ancestor: self.loop_ancestor().map(FsPath::to_path_buf),
+            message: err.map(|err| err.to_string()),
+            raw_os_error: err.and_then(std::io::Error::raw_os_error),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Restores the error. The underlying IO error is recreated from the OS error
+/// code when it is present, otherwise from the kind and the message. `kind()`
+/// returns the serialized kind in any case.
+#[cfg(feature = "serde")]
+impl<'de, E> serde::Deserialize<'de> for Error<E>
+where
+    E: fs::FsDirEntry<Error = std::io::Error>,
+    E::PathBuf: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = ErrorRepr::<E::PathBuf>::deserialize(deserializer)?;
+
+        let inner = match (repr.path, repr.loop_ancestor) {
+            (Some(child), Some(ancestor)) => ErrorInner::Loop { ancestor, child },
+            (path, _) => {
+                let err = match (repr.raw_os_error, repr.message) {
+                    (Some(code), _) => Some(std::io::Error::from_raw_os_error(code)),
+                    (None, Some(message)) => {
+                        let kind = match repr.kind {
+                            ErrorKind::PermissionDenied => std::io::ErrorKind::PermissionDenied,
+                            ErrorKind::NotFound => std::io::ErrorKind::NotFound,
+                            _ => std::io::ErrorKind::Other,
+                        };
+                        Some(std::io::Error::new(kind, message))
+                    }
+                    (None, None) => None,
+                };
+                ErrorInner::Io { path, op: repr.operation, err }
+            }
+        };
+
+        Ok(Self { inner, depth: repr.depth, kind: Some(repr.kind) })
+    }
 }
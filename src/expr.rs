@@ -0,0 +1,450 @@
+//! Find-style filter expressions.
+//!
+//! An expression is parsed from a string once and then tested against
+//! entries, e.g. `type f and size > 10M and mtime < 7d and not name "*.tmp"`.
+//!
+//! Tests:
+//!
+//! * `type f`, `type d`, `type l` -- file, dir or symlink;
+//! * `name GLOB` -- file name matches the glob;
+//! * `path GLOB` -- path relative to the root (with `/` as separator) matches the glob;
+//! * `size OP N` -- size in bytes, `N` may have a `k`, `M`, `G` or `T` suffix (powers of 1024);
+//! * `mtime OP AGE` -- time since the last modification, `AGE` may have an
+//!   `s`, `m`, `h`, `d` or `w` suffix (seconds by default), so `mtime < 7d`
+//!   means "modified within the last 7 days";
+//! * `depth OP N` -- depth of the entry (the root has depth 0).
+//!
+//! `OP` is one of `<`, `<=`, `>`, `>=`, `=` (or `==`) and `!=`. Globs are
+//! the same as in [`WalkProfile`] patterns and may be quoted with `"` or `'`.
+//! Tests are combined with `not` (or `!`), `and` (may be omitted) and `or`
+//! (in order of precedence) and grouped with parentheses (nested at most 64
+//! levels deep, counting `not`s).
+//!
+//! ```no_run
+//! use walkdir2::{WalkDir, WalkDirIter};
+//! use walkdir2::expr::Expr;
+//!
+//! let big: Expr = "type f and size > 10M and not name '*.tmp'".parse().unwrap();
+//! let skip: Expr = "name .git or name target".parse().unwrap();
+//!
+//! let walk = WalkDir::new("foo").into_iter().filter_entry(skip.prune_filter()).into_classic();
+//! for entry in walk.filter_map(|e| e.ok()).filter(|e| big.is_match(e)) {
+//!     println!("{}", entry.path().display());
+//! }
+//! ```
+//!
+//! [`WalkProfile`]: ../struct.WalkProfile.html
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use crate::cp::DirEntry;
+use crate::fs::{self, FsFileType};
+use crate::profile::{glob_match, relative_path_str, FnEntryFilter};
+use crate::wd::Depth;
+
+/////////////////////////////////////////////////////////////////////////
+//// ParseError
+
+/// An error of parsing an expression
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+    pos: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(pos: usize, message: impl Into<String>) -> Self {
+        Self { pos, message: message.into() }
+    }
+
+    /// Byte offset in the source string
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.pos)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/////////////////////////////////////////////////////////////////////////
+//// Expr
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Cmp {
+    fn test<T: Ord>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Ge => lhs >= rhs,
+            Cmp::Eq => lhs == rhs,
+            Cmp::Ne => lhs != rhs,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum FileKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Node {
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+    Type(FileKind),
+    Name(String),
+    Path(String),
+    Size(Cmp, u64),
+    Mtime(Cmp, Duration),
+    Depth(Cmp, Depth),
+}
+
+/// A compiled filter expression (see [module docs](index.html) for the syntax).
+///
+/// Ages (`mtime` tests) are counted from the moment the expression was parsed.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    node: Node,
+    now: SystemTime,
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        Self::parse(s)
+    }
+}
+
+impl Expr {
+    /// Parse the expression
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { tokens: tokenize(s)?, at: 0, end: s.len(), depth: 0 };
+        let node = parser.parse_or()?;
+        if let Some((pos, _)) = parser.peek() {
+            return Err(ParseError::new(pos, "unexpected token"));
+        }
+        Ok(Self { node, now: SystemTime::now() })
+    }
+
+    /// Does the entry match this expression?
+    pub fn is_match<E>(&self, dent: &DirEntry<E>) -> bool
+    where
+        E: fs::FsDirEntry<Path = Path, Metadata = std::fs::Metadata>,
+    {
+        self.eval(&self.node, dent)
+    }
+
+    /// Returns a predicate for `filter_entry`: entries which don't match are
+    /// skipped (dirs are not descended into). The root is never filtered out.
+    pub fn entry_filter<E>(&self) -> FnEntryFilter<E>
+    where
+        E: fs::FsDirEntry<Path = Path, Metadata = std::fs::Metadata>,
+    {
+        let expr = self.clone();
+        Box::new(move |dent: &DirEntry<E>| dent.depth() == 0 || expr.is_match(dent))
+    }
+
+    /// Returns a predicate for `filter_entry` which prunes matching dirs:
+    /// they are neither yielded nor descended into, other entries are kept.
+    pub fn prune_filter<E>(&self) -> FnEntryFilter<E>
+    where
+        E: fs::FsDirEntry<Path = Path, Metadata = std::fs::Metadata>,
+    {
+        let expr = self.clone();
+        Box::new(move |dent: &DirEntry<E>| dent.depth() == 0 || !dent.is_dir() || !expr.is_match(dent))
+    }
+
+    fn eval<E>(&self, node: &Node, dent: &DirEntry<E>) -> bool
+    where
+        E: fs::FsDirEntry<Path = Path, Metadata = std::fs::Metadata>,
+    {
+        match node {
+            Node::And(nodes) => nodes.iter().all(|node| self.eval(node, dent)),
+            Node::Or(nodes) => nodes.iter().any(|node| self.eval(node, dent)),
+            Node::Not(node) => !self.eval(node, dent),
+            Node::Type(kind) => {
+                let ft = dent.file_type();
+                match kind {
+                    FileKind::File => ft.is_file(),
+                    FileKind::Dir => ft.is_dir(),
+                    FileKind::Symlink => ft.is_symlink(),
+                }
+            }
+            Node::Name(glob) => {
+                let path = dent.path();
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                glob_match(glob, &name)
+            }
+            Node::Path(glob) => {
+                let path = relative_path_str(dent.relative_path());
+                glob_match(glob, &path)
+            }
+            Node::Size(cmp, size) => cmp.test(dent.metadata_ref().len(), *size),
+            Node::Mtime(cmp, age) => match dent.metadata_ref().modified() {
+                Ok(mtime) => cmp.test(self.now.duration_since(mtime).unwrap_or_default(), *age),
+                Err(_) => false,
+            },
+            Node::Depth(cmp, depth) => cmp.test(dent.depth(), *depth),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Parser
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    /// A bare word
+    Word(String),
+    /// A quoted string
+    Str(String),
+    Op(Cmp),
+    LParen,
+    RParen,
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+
+    while let Some(&(pos, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push((pos, if c == '(' { Token::LParen } else { Token::RParen }));
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(ParseError::new(pos, "unterminated string")),
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, e)) => value.push(e),
+                            None => return Err(ParseError::new(pos, "unterminated string")),
+                        },
+                        Some((_, ch)) => value.push(ch),
+                    }
+                }
+                tokens.push((pos, Token::Str(value)));
+            }
+            '<' | '>' | '=' | '!' => {
+                chars.next();
+                let eq = matches!(chars.peek(), Some(&(_, '=')));
+                if eq {
+                    chars.next();
+                }
+                let token = match (c, eq) {
+                    ('<', false) => Token::Op(Cmp::Lt),
+                    ('<', true) => Token::Op(Cmp::Le),
+                    ('>', false) => Token::Op(Cmp::Gt),
+                    ('>', true) => Token::Op(Cmp::Ge),
+                    ('=', _) => Token::Op(Cmp::Eq),
+                    ('!', true) => Token::Op(Cmp::Ne),
+                    _ => Token::Word("not".to_string()),
+                };
+                tokens.push((pos, token));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || "()\"'<>=!".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push((pos, Token::Word(word)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Maximum nesting of parentheses and `not`s
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    at: usize,
+    /// Length of the source (position of "end of input" errors)
+    end: usize,
+    /// Current nesting of parentheses and `not`s
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens.get(self.at).map(|(pos, t)| (*pos, t))
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), ParseError> {
+        let token = self.tokens.get(self.at).cloned().ok_or_else(|| ParseError::new(self.end, "unexpected end of expression"))?;
+        self.at += 1;
+        Ok(token)
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some((_, Token::Word(w))) if w == word)
+    }
+
+    /// Parses a nested part with `parse` (errors out on too deep nesting)
+    fn nested(&mut self, pos: usize, parse: fn(&mut Self) -> Result<Node, ParseError>) -> Result<Node, ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParseError::new(pos, "expression is nested too deeply"));
+        }
+        self.depth += 1;
+        let node = parse(self);
+        self.depth -= 1;
+        node
+    }
+
+    fn parse_or(&mut self) -> Result<Node, ParseError> {
+        let mut nodes = vec![self.parse_and()?];
+        while self.peek_word("or") {
+            self.at += 1;
+            nodes.push(self.parse_and()?);
+        }
+        Ok(if nodes.len() == 1 { nodes.pop().unwrap() } else { Node::Or(nodes) })
+    }
+
+    fn parse_and(&mut self) -> Result<Node, ParseError> {
+        let mut nodes = vec![self.parse_not()?];
+        loop {
+            match self.peek() {
+                Some((_, Token::Word(w))) if w == "and" => self.at += 1,
+                Some((_, Token::Word(w))) if w != "or" => {}
+                Some((_, Token::LParen)) => {}
+                _ => break,
+            }
+            nodes.push(self.parse_not()?);
+        }
+        Ok(if nodes.len() == 1 { nodes.pop().unwrap() } else { Node::And(nodes) })
+    }
+
+    fn parse_not(&mut self) -> Result<Node, ParseError> {
+        if let Some((pos, Token::Word(w))) = self.peek() {
+            if w == "not" {
+                self.at += 1;
+                return Ok(Node::Not(Box::new(self.nested(pos, Self::parse_not)?)));
+            }
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, ParseError> {
+        let (pos, token) = self.next()?;
+        let test = match token {
+            Token::LParen => {
+                let node = self.nested(pos, Self::parse_or)?;
+                return match self.next()? {
+                    (_, Token::RParen) => Ok(node),
+                    (pos, _) => Err(ParseError::new(pos, "expected `)`")),
+                };
+            }
+            Token::Word(test) => test,
+            _ => return Err(ParseError::new(pos, "expected a test")),
+        };
+
+        let node = match test.as_str() {
+            "type" => match self.value()? {
+                (_, v) if v == "f" => Node::Type(FileKind::File),
+                (_, v) if v == "d" => Node::Type(FileKind::Dir),
+                (_, v) if v == "l" => Node::Type(FileKind::Symlink),
+                (pos, _) => return Err(ParseError::new(pos, "expected `f`, `d` or `l`")),
+            },
+            "name" => Node::Name(self.value()?.1),
+            "path" => Node::Path(self.value()?.1),
+            "size" => {
+                let cmp = self.cmp()?;
+                let (pos, v) = self.value()?;
+                Node::Size(cmp, parse_size(&v).ok_or_else(|| ParseError::new(pos, "invalid size"))?)
+            }
+            "mtime" => {
+                let cmp = self.cmp()?;
+                let (pos, v) = self.value()?;
+                Node::Mtime(cmp, parse_age(&v).ok_or_else(|| ParseError::new(pos, "invalid age"))?)
+            }
+            "depth" => {
+                let cmp = self.cmp()?;
+                let (pos, v) = self.value()?;
+                Node::Depth(cmp, v.parse().map_err(|_| ParseError::new(pos, "invalid depth"))?)
+            }
+            _ => return Err(ParseError::new(pos, format!("unknown test `{}`", test))),
+        };
+        Ok(node)
+    }
+
+    fn cmp(&mut self) -> Result<Cmp, ParseError> {
+        match self.next()? {
+            (_, Token::Op(cmp)) => Ok(cmp),
+            (pos, _) => Err(ParseError::new(pos, "expected a comparison operator")),
+        }
+    }
+
+    fn value(&mut self) -> Result<(usize, String), ParseError> {
+        match self.next()? {
+            (pos, Token::Word(v)) | (pos, Token::Str(v)) => Ok((pos, v)),
+            (pos, _) => Err(ParseError::new(pos, "expected a value")),
+        }
+    }
+}
+
+/// Split a number and its unit suffix
+fn split_unit(s: &str) -> Option<(u64, &str)> {
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..digits].parse().ok()?, &s[digits..]))
+}
+
+fn parse_size(s: &str) -> Option<u64> {
+    let (n, unit) = split_unit(s)?;
+    let scale: u64 = match unit {
+        "" => 1,
+        "k" | "K" => 1 << 10,
+        "m" | "M" => 1 << 20,
+        "g" | "G" => 1 << 30,
+        "t" | "T" => 1 << 40,
+        _ => return None,
+    };
+    n.checked_mul(scale)
+}
+
+fn parse_age(s: &str) -> Option<Duration> {
+    let (n, unit) = split_unit(s)?;
+    let scale: u64 = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(n.checked_mul(scale)?))
+}
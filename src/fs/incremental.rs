@@ -0,0 +1,565 @@
+use super::{FsDirEntry, FsReadDirIterator, FsRootDirEntry};
+use crate::fs::standard::{StandardDirEntry, StandardDirFingerprint};
+use crate::wd::{IntoOk, IntoSome};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+///////////////////////////////////////////////////////////////////////////////////////////////
+
+/// What identifies a version of a dir listing
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct DirStamp {
+    dev:    u64,
+    ino:    u64,
+    /// Modification time (nanoseconds since the epoch)
+    mtime:  i64,
+    /// Status change time (nanoseconds since the epoch)
+    ctime:  i64,
+}
+
+impl DirStamp {
+    fn of(path: &Path) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let md = std::fs::metadata(path).ok()?;
+        Self {
+            dev:    md.dev(),
+            ino:    md.ino(),
+            mtime:  md.mtime().saturating_mul(1_000_000_000).saturating_add(md.mtime_nsec()),
+            ctime:  md.ctime().saturating_mul(1_000_000_000).saturating_add(md.ctime_nsec()),
+        }.into_some()
+    }
+}
+
+fn now_nanos() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos().min(i64::MAX as u128) as i64)
+}
+
+/// A type of cached entry
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum CachedKind {
+    File,
+    Dir,
+    Symlink,
+    /// Other types (or unknown) are read from the fs when requested
+    Other,
+}
+
+impl CachedKind {
+    fn of(ft: std::fs::FileType) -> Self {
+        if ft.is_symlink() {
+            CachedKind::Symlink
+        } else if ft.is_dir() {
+            CachedKind::Dir
+        } else if ft.is_file() {
+            CachedKind::File
+        } else {
+            CachedKind::Other
+        }
+    }
+}
+
+/// Sample file types of each cached kind.
+///
+/// `std::fs::FileType` can't be constructed, so file types of replayed
+/// entries are copied from entries of the same kind which were read from the
+/// fs in this run.
+#[derive(Debug, Default, Clone)]
+struct FileTypes {
+    file:       Option<std::fs::FileType>,
+    dir:        Option<std::fs::FileType>,
+    symlink:    Option<std::fs::FileType>,
+}
+
+impl FileTypes {
+    fn slot(&mut self, kind: CachedKind) -> Option<&mut Option<std::fs::FileType>> {
+        match kind {
+            CachedKind::File => Some(&mut self.file),
+            CachedKind::Dir => Some(&mut self.dir),
+            CachedKind::Symlink => Some(&mut self.symlink),
+            CachedKind::Other => None,
+        }
+    }
+
+    fn get(&mut self, kind: CachedKind) -> Option<std::fs::FileType> {
+        *self.slot(kind)?
+    }
+
+    fn remember(&mut self, ft: std::fs::FileType) {
+        if let Some(slot) = self.slot(CachedKind::of(ft)) {
+            if slot.is_none() {
+                *slot = Some(ft);
+            }
+        }
+    }
+}
+
+/// A dir listing recorded by a previous walk
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct CachedDir {
+    stamp:      DirStamp,
+    /// When the listing was read (nanoseconds since the epoch)
+    scanned_at: i64,
+    /// Names and types of entries in the order they were read
+    entries:    Vec<(OsString, CachedKind)>,
+    /// Was the dir opened by the current walk?
+    #[cfg_attr(feature = "serde", serde(skip))]
+    visited:    bool,
+}
+
+/// Counters of a walk with [`DirCache`]
+///
+/// [`DirCache`]: struct.DirCache.html
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct CacheStats {
+    /// Dirs replayed from the cache
+    pub reused: u64,
+    /// Dirs read from the fs
+    pub read:   u64,
+}
+
+fn default_racy_window() -> Duration {
+    Duration::from_secs(2)
+}
+
+/// The fs context of [`IncrementalDirEntry`]: listings of dirs keyed by their paths.
+///
+/// A dir is not read again when its device, inode, mtime and ctime are the
+/// same as when it was read by a previous walk. Its cached listing is
+/// replayed instead, so on a quiescent tree the walk yields the same entries
+/// in the same order as a full walk. File types of entries are cached along
+/// with their names, metadata is not.
+///
+/// A listing is trusted only when the dir was not changed within the racy
+/// window (see [`racy_window`]) before it was read, because a change made in
+/// the same timestamp tick could not be detected later.
+///
+/// With `serde` feature the cache may be saved between runs.
+///
+/// [`IncrementalDirEntry`]: struct.IncrementalDirEntry.html
+/// [`racy_window`]: #method.racy_window
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirCache {
+    dirs:           HashMap<PathBuf, CachedDir>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_racy_window"))]
+    racy_window:    Duration,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stats:          CacheStats,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    file_types:     FileTypes,
+}
+
+impl Default for DirCache {
+    fn default() -> Self {
+        Self {
+            dirs:           HashMap::new(),
+            racy_window:    default_racy_window(),
+            stats:          CacheStats::default(),
+            file_types:     FileTypes::default(),
+        }
+    }
+}
+
+impl DirCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Do not trust listings of dirs changed less than `window` before they were read (default 2s)
+    pub fn racy_window(mut self, window: Duration) -> Self {
+        self.racy_window = window;
+        self
+    }
+
+    /// Count of cached dirs
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    /// Is the cache empty?
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    /// Counters since the cache was created or [`retain_visited`] was called
+    ///
+    /// [`retain_visited`]: #method.retain_visited
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Drop dirs which were not opened since the last call (e.g. removed ones) and reset counters
+    pub fn retain_visited(&mut self) {
+        self.dirs.retain(|_, dir| dir.visited);
+        for dir in self.dirs.values_mut() {
+            dir.visited = false;
+        }
+        self.stats = CacheStats::default();
+    }
+
+    /// Get the cached listing of the dir (if it is still valid)
+    fn replay(&mut self, path: &Path, stamp: Option<DirStamp>) -> Option<Vec<(OsString, CachedKind)>> {
+        let window = self.racy_window.as_nanos().min(i64::MAX as u128) as i64;
+        let dir = self.dirs.get_mut(path)?;
+        let stamp = stamp?;
+        let settled = stamp.mtime.max(stamp.ctime).saturating_add(window) < dir.scanned_at;
+        if dir.stamp != stamp || !settled {
+            return None;
+        }
+        dir.visited = true;
+        dir.entries.clone().into_some()
+    }
+
+    fn record(&mut self, path: PathBuf, dir: CachedDir) {
+        self.dirs.insert(path, dir);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A FsReadDirIterator implementation which replays cached listings
+#[derive(Debug)]
+pub struct IncrementalReadDir {
+    state:  ReadDirState,
+}
+
+#[derive(Debug)]
+enum ReadDirState {
+    /// Reading the dir from the fs (`record` is `None` when the listing can't be cached)
+    Read {
+        inner:  std::fs::ReadDir,
+        path:   PathBuf,
+        record: Option<CachedDir>,
+    },
+    /// Replaying the cached listing
+    Replay {
+        path:       PathBuf,
+        entries:    std::vec::IntoIter<(OsString, CachedKind)>,
+    },
+}
+
+impl IncrementalReadDir {
+    fn open(path: &Path, ctx: &mut DirCache) -> Result<Self, std::io::Error> {
+        let scanned_at = now_nanos();
+        let stamp = DirStamp::of(path);
+
+        if let Some(entries) = ctx.replay(path, stamp) {
+            ctx.stats.reused += 1;
+            let state = ReadDirState::Replay {
+                path:       path.to_path_buf(),
+                entries:    entries.into_iter(),
+            };
+            return Self { state }.into_ok();
+        }
+
+        let inner = std::fs::read_dir(path)?;
+        ctx.stats.read += 1;
+        let state = ReadDirState::Read {
+            inner,
+            path:   path.to_path_buf(),
+            record: stamp.map(|stamp| CachedDir { stamp, scanned_at, entries: vec![], visited: true }),
+        };
+        Self { state }.into_ok()
+    }
+}
+
+impl FsReadDirIterator for IncrementalReadDir {
+    type Context    = DirCache;
+    type Error      = std::io::Error;
+    type DirEntry   = IncrementalDirEntry;
+
+    fn next_entry(
+        &mut self,
+        ctx: &mut Self::Context,
+    ) -> Option<Result<Self::DirEntry, Self::Error>> {
+        match &mut self.state {
+            ReadDirState::Read { inner, path, record } => match inner.next() {
+                Some(Ok(inner)) => {
+                    let kind = match inner.file_type() {
+                        Ok(ft) => {
+                            ctx.file_types.remember(ft);
+                            CachedKind::of(ft)
+                        },
+                        Err(_) => CachedKind::Other,
+                    };
+                    if let Some(dir) = record {
+                        dir.entries.push((inner.file_name(), kind));
+                    }
+                    IncrementalDirEntry::from_inner(inner).into_ok().into_some()
+                },
+                Some(Err(err)) => {
+                    *record = None;
+                    Err(err).into_some()
+                },
+                None => {
+                    if let Some(dir) = record.take() {
+                        ctx.record(std::mem::take(path), dir);
+                    }
+                    None
+                },
+            },
+            ReadDirState::Replay { path, entries } => {
+                let (name, kind) = entries.next()?;
+                IncrementalDirEntry::from_cache(path.join(&name), name, kind).into_ok().into_some()
+            },
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A FsDirEntry implementation for incremental walks (see [`DirCache`])
+///
+/// [`DirCache`]: struct.DirCache.html
+#[derive(Debug)]
+pub struct IncrementalDirEntry {
+    pathbuf:    PathBuf,
+    file_name:  OsString,
+    /// Inner fs object (`None` when replayed from the cache)
+    inner:      Option<std::fs::DirEntry>,
+    /// Cached type of this entry (when replayed from the cache)
+    kind:       CachedKind,
+    /// Metadata of this entry (don't follow symlink)
+    metadata:   RefCell<Option<std::fs::Metadata>>,
+}
+
+impl IncrementalDirEntry {
+    /// Get inner fs object (`None` when replayed from the cache)
+    pub fn inner(&self) -> Option<&std::fs::DirEntry> {
+        self.inner.as_ref()
+    }
+
+    /// Is this entry replayed from the cache?
+    pub fn is_cached(&self) -> bool {
+        self.inner.is_none()
+    }
+
+    fn from_inner(inner: std::fs::DirEntry) -> Self {
+        Self {
+            pathbuf:    inner.path(),
+            file_name:  inner.file_name(),
+            inner:      Some(inner),
+            kind:       CachedKind::Other,
+            metadata:   RefCell::new(None),
+        }
+    }
+
+    fn from_cache(pathbuf: PathBuf, file_name: OsString, kind: CachedKind) -> Self {
+        Self {
+            pathbuf,
+            file_name,
+            inner:      None,
+            kind,
+            metadata:   RefCell::new(None),
+        }
+    }
+
+    fn device_num_from_path(path: &Path) -> Result<u64, std::io::Error> {
+        use std::os::unix::fs::MetadataExt;
+
+        path.metadata().map(|md| md.dev())
+    }
+}
+
+impl FsDirEntry for IncrementalDirEntry {
+    type Context        = DirCache;
+
+    type Path           = Path;
+    type PathBuf        = PathBuf;
+    type FileName       = OsString;
+
+    type Error          = std::io::Error;
+    type FileType       = std::fs::FileType;
+    type Metadata       = std::fs::Metadata;
+    type ReadDir        = IncrementalReadDir;
+    type DirFingerprint = StandardDirFingerprint;
+    type DeviceNum      = u64;
+    type RootDirEntry   = IncrementalRootDirEntry;
+
+    fn path(&self) -> &Self::Path {
+        &self.pathbuf
+    }
+    fn pathbuf(&self) -> Self::PathBuf {
+        self.pathbuf.clone()
+    }
+    fn canonicalize(&self) -> Result<Self::PathBuf, Self::Error> {
+        StandardDirEntry::canonicalize_from_path(self.path())
+    }
+    fn file_name(&self) -> Self::FileName {
+        self.file_name.clone()
+    }
+
+    fn file_type(
+        &self,
+        follow_link: bool,
+        ctx: &mut Self::Context,
+    ) -> Result<Self::FileType, Self::Error> {
+        if !follow_link {
+            if let Some(ref inner) = self.inner {
+                return inner.file_type();
+            }
+            if let Some(ft) = ctx.file_types.get(self.kind) {
+                return ft.into_ok();
+            }
+        }
+        let ft = self.metadata(follow_link, ctx)?.file_type();
+        if !follow_link {
+            ctx.file_types.remember(ft);
+        }
+        ft.into_ok()
+    }
+
+    fn metadata(
+        &self,
+        follow_link: bool,
+        _ctx: &mut Self::Context,
+    ) -> Result<Self::Metadata, Self::Error> {
+        if follow_link {
+            return std::fs::metadata(&self.pathbuf);
+        }
+        if let Some(ref md) = *self.metadata.borrow() {
+            return md.clone().into_ok();
+        }
+        let md = std::fs::symlink_metadata(&self.pathbuf)?;
+        *self.metadata.borrow_mut() = Some(md.clone());
+        md.into_ok()
+    }
+
+    fn read_dir(
+        &self,
+        ctx: &mut Self::Context,
+    ) -> Result<Self::ReadDir, Self::Error> {
+        IncrementalReadDir::open(self.path(), ctx)
+    }
+
+    fn fingerprint(
+        &self,
+        _ctx: &mut Self::Context,
+    ) -> Result<Self::DirFingerprint, Self::Error> {
+        StandardDirEntry::fingerprint_from_path(self.path())
+    }
+
+    fn is_same(
+        lhs: (&Self::Path, &Self::DirFingerprint),
+        rhs: (&Self::Path, &Self::DirFingerprint),
+    ) -> bool {
+        StandardDirEntry::is_same( lhs, rhs )
+    }
+
+    fn device_num(
+        &self,
+        _ctx: &mut Self::Context,
+    ) -> Result<Self::DeviceNum, Self::Error> {
+        Self::device_num_from_path( self.path() )
+    }
+
+    fn to_parts(
+        &mut self,
+        follow_link: bool,
+        force_metadata: bool,
+        force_file_name: bool,
+        ctx: &mut Self::Context,
+    ) -> (Self::PathBuf, Option<Self::Metadata>, Option<Self::FileName>) {
+        let md = if force_metadata {self.metadata(follow_link, ctx).ok()} else {None};
+        let n = if force_file_name {self.file_name().into_some()} else {None};
+        (self.pathbuf.clone(), md, n)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A FsRootDirEntry implementation for incremental walks
+#[derive(Debug)]
+pub struct IncrementalRootDirEntry {
+    pathbuf:    PathBuf,
+}
+
+impl FsRootDirEntry for IncrementalRootDirEntry {
+    type Context    = DirCache;
+    type DirEntry   = IncrementalDirEntry;
+
+    fn from_path(
+        path: &<Self::DirEntry as FsDirEntry>::Path,
+        _ctx: &mut Self::Context,
+    ) -> Result<Self, <Self::DirEntry as FsDirEntry>::Error> {
+        Self {
+            pathbuf: path.to_path_buf(),
+        }.into_ok()
+    }
+
+    fn path(&self) -> &<Self::DirEntry as FsDirEntry>::Path {
+        &self.pathbuf
+    }
+    fn pathbuf(&self) -> <Self::DirEntry as FsDirEntry>::PathBuf {
+        self.pathbuf.clone()
+    }
+    fn canonicalize(&self) -> Result<<Self::DirEntry as FsDirEntry>::PathBuf, <Self::DirEntry as FsDirEntry>::Error> {
+        StandardDirEntry::canonicalize_from_path( self.path() )
+    }
+
+    fn file_name(
+        &self
+    ) -> <Self::DirEntry as FsDirEntry>::FileName {
+        StandardDirEntry::file_name_from_path( self.path() )
+    }
+
+    fn file_type(
+        &self,
+        follow_link: bool,
+        ctx: &mut Self::Context,
+    ) -> Result<<Self::DirEntry as FsDirEntry>::FileType, <Self::DirEntry as FsDirEntry>::Error> {
+        self.metadata(follow_link, ctx)?.file_type().into_ok()
+    }
+
+    fn metadata(
+        &self,
+        follow_link: bool,
+        _ctx: &mut Self::Context,
+    ) -> Result<<Self::DirEntry as FsDirEntry>::Metadata, <Self::DirEntry as FsDirEntry>::Error> {
+        StandardDirEntry::metadata_from_path( self.path(), follow_link )
+    }
+
+    fn read_dir(
+        &self,
+        ctx: &mut Self::Context,
+    ) -> Result<<Self::DirEntry as FsDirEntry>::ReadDir, <Self::DirEntry as FsDirEntry>::Error> {
+        IncrementalReadDir::open(self.path(), ctx)
+    }
+
+    fn fingerprint(
+        &self,
+        _ctx: &mut Self::Context,
+    ) -> Result<<Self::DirEntry as FsDirEntry>::DirFingerprint, <Self::DirEntry as FsDirEntry>::Error> {
+        StandardDirEntry::fingerprint_from_path( self.path() )
+    }
+
+    fn device_num(
+        &self,
+        _ctx: &mut Self::Context,
+    ) -> Result<<Self::DirEntry as FsDirEntry>::DeviceNum, <Self::DirEntry as FsDirEntry>::Error> {
+        IncrementalDirEntry::device_num_from_path( self.path() )
+    }
+
+    fn to_parts(
+        &mut self,
+        follow_link: bool,
+        force_metadata: bool,
+        force_file_name: bool,
+        ctx: &mut Self::Context,
+    ) -> (<Self::DirEntry as FsDirEntry>::PathBuf, Option<<Self::DirEntry as FsDirEntry>::Metadata>, Option<<Self::DirEntry as FsDirEntry>::FileName>) {
+        let md = if force_metadata {self.metadata(follow_link, ctx).ok()} else {None};
+        let n = if force_file_name {self.file_name().into_some()} else {None};
+        (self.pathbuf.clone(), md, n)
+    }
+}
@@ -2,18 +2,26 @@ use std::ops::Deref;
 use std::fmt::Debug;
 
 mod path;
+mod names;
 mod standard;
 #[cfg(unix)]
 mod unix;
+#[cfg(unix)]
+mod incremental;
 #[cfg(windows)]
 mod windows;
 
+use crate::error::ErrorKind;
 use crate::wd::{IntoSome, IntoErr};
 pub use self::path::{FsPath, FsPathBuf};
+pub use self::names::FileKind;
+pub(crate) use self::names::ReadDirNames;
 pub use self::standard::{StandardDirEntry, StandardDirFingerprint, StandardReadDir, StandardRootDirEntry};
 
 #[cfg(unix)]
 pub use self::unix::{UnixDirEntry, UnixReadDir, UnixRootDirEntry};
+#[cfg(unix)]
+pub use self::incremental::{CacheStats, DirCache, IncrementalDirEntry, IncrementalReadDir, IncrementalRootDirEntry};
 #[cfg(windows)]
 pub use self::windows::{WindowsDirEntry, WindowsReadDir, WindowsRootDirEntry};
 
@@ -37,6 +45,11 @@ pub trait FsError: 'static + std::error::Error + Debug {
 
     /// Creates a new I/O error from a known kind of error as well as an arbitrary error payload.
     fn from_inner(inner: Self::Inner) -> Self;
+
+    /// Get kind of this error
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////
@@ -208,6 +221,9 @@ pub trait FsDirEntry: Debug + Sized {
     ) -> (Self::PathBuf, Option<Self::Metadata>, Option<Self::FileName>);
 }
 
+/// Path, metadata and file name of an entry (as returned by `to_parts`)
+pub type EntryParts<E> = (<E as FsDirEntry>::PathBuf, Option<<E as FsDirEntry>::Metadata>, Option<<E as FsDirEntry>::FileName>);
+
 ///////////////////////////////////////////////////////////////////////////////////////////////
 
 /// Functions for FsRootDirEntry
@@ -271,5 +287,5 @@ pub trait FsRootDirEntry: Debug + Sized {
         force_metadata: bool,
         force_file_name: bool,
         ctx: &mut Self::Context,
-    ) -> (<Self::DirEntry as FsDirEntry>::PathBuf, Option<<Self::DirEntry as FsDirEntry>::Metadata>, Option<<Self::DirEntry as FsDirEntry>::FileName>);
+    ) -> EntryParts<Self::DirEntry>;
 }
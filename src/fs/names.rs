@@ -0,0 +1,201 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::FsFileType;
+
+///////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Kind of a dir entry, as reported along with its name (see `LendingEntry::file_type`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    /// Regular file
+    File,
+    /// Dir
+    Dir,
+    /// Symbolic link
+    Symlink,
+    /// Anything else (device, socket, pipe etc.)
+    Other,
+}
+
+impl From<std::fs::FileType> for FileKind {
+    fn from(ft: std::fs::FileType) -> Self {
+        if ft.is_dir() {
+            FileKind::Dir
+        } else if ft.is_file() {
+            FileKind::File
+        } else if ft.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::Other
+        }
+    }
+}
+
+/// Functions for FsFileType
+impl FsFileType for FileKind {
+    /// Is it dir?
+    fn is_dir(&self) -> bool {
+        *self == FileKind::Dir
+    }
+    /// Is it file
+    fn is_file(&self) -> bool {
+        *self == FileKind::File
+    }
+    /// Is it symlink
+    fn is_symlink(&self) -> bool {
+        *self == FileKind::Symlink
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Reads names of dir entries, appending each one to a shared path buffer.
+///
+/// On Linux, Android, macOS, iOS and BSDs it uses `readdir` directly, so no
+/// name is allocated. Elsewhere it uses `std::fs::read_dir`, which allocates
+/// each name.
+#[derive(Debug)]
+pub(crate) struct ReadDirNames {
+    inner: imp::ReadDirNames,
+}
+
+impl ReadDirNames {
+    /// Opens the dir
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self { inner: imp::ReadDirNames::open(path)? })
+    }
+
+    /// Appends the name of the next entry to `path` (`.` and `..` are skipped).
+    /// Returns the kind of the entry if the OS reported it along with the name.
+    pub(crate) fn next_into(&mut self, path: &mut PathBuf) -> Option<io::Result<Option<FileKind>>> {
+        self.inner.next_into(path)
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+mod imp {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::fmt;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    use super::FileKind;
+
+    // Without LFS, readdir fails with EOVERFLOW on large inode numbers on 32-bit targets
+    #[cfg(target_os = "linux")]
+    use libc::{__errno_location as errno_location, readdir64 as readdir};
+    #[cfg(not(target_os = "linux"))]
+    use libc::readdir;
+    #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+    use libc::__errno as errno_location;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    use libc::__error as errno_location;
+
+    pub(crate) struct ReadDirNames {
+        /// Never null
+        dir: *mut libc::DIR,
+    }
+
+    // The stream is used only through `&mut self` (as `std::fs::ReadDir` does)
+    unsafe impl Send for ReadDirNames {}
+    unsafe impl Sync for ReadDirNames {}
+
+    impl fmt::Debug for ReadDirNames {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ReadDirNames").finish()
+        }
+    }
+
+    impl ReadDirNames {
+        pub(crate) fn open(path: &Path) -> io::Result<Self> {
+            let path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            let dir = unsafe { libc::opendir(path.as_ptr()) };
+            if dir.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { dir })
+        }
+
+        pub(crate) fn next_into(&mut self, path: &mut PathBuf) -> Option<io::Result<Option<FileKind>>> {
+            loop {
+                // readdir returns null both at the end and on errors: only errno tells them apart
+                unsafe { *errno_location() = 0 };
+                let ent = unsafe { readdir(self.dir) };
+                if ent.is_null() {
+                    let err = io::Error::last_os_error();
+                    return if err.raw_os_error() == Some(0) { None } else { Some(Err(err)) };
+                }
+
+                // The entry is valid until the next readdir call
+                let (name, d_type) = unsafe { (CStr::from_ptr((*ent).d_name.as_ptr()).to_bytes(), (*ent).d_type) };
+                if name == b"." || name == b".." {
+                    continue;
+                }
+
+                let kind = match d_type {
+                    libc::DT_UNKNOWN => None,
+                    libc::DT_DIR => Some(FileKind::Dir),
+                    libc::DT_REG => Some(FileKind::File),
+                    libc::DT_LNK => Some(FileKind::Symlink),
+                    _ => Some(FileKind::Other),
+                };
+                path.push(OsStr::from_bytes(name));
+                return Some(Ok(kind));
+            }
+        }
+    }
+
+    impl Drop for ReadDirNames {
+        fn drop(&mut self) {
+            unsafe { libc::closedir(self.dir) };
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+mod imp {
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use super::FileKind;
+
+    #[derive(Debug)]
+    pub(crate) struct ReadDirNames {
+        inner: std::fs::ReadDir,
+    }
+
+    impl ReadDirNames {
+        pub(crate) fn open(path: &Path) -> io::Result<Self> {
+            Ok(Self { inner: std::fs::read_dir(path)? })
+        }
+
+        pub(crate) fn next_into(&mut self, path: &mut PathBuf) -> Option<io::Result<Option<FileKind>>> {
+            let dent = match self.inner.next()? {
+                Ok(dent) => dent,
+                Err(err) => return Some(Err(err)),
+            };
+            let kind = dent.file_type().ok().map(FileKind::from);
+            path.push(dent.file_name());
+            Some(Ok(kind))
+        }
+    }
+}
@@ -19,6 +19,9 @@ pub trait FsPath: Ord
 
     /// Try to get file name from path
     fn file_name(&self) -> Option<Self::FileName>;
+
+    /// Split path into head and tail, where tail consists of the last `n` components
+    fn split_tail(&self, n: usize) -> Option<(&Self, &Self)>;
 }
 
 /// Functions for StorageExt::PathBuf
@@ -56,6 +59,12 @@ impl FsPath for std::path::Path {
     fn file_name(&self) -> Option<Self::FileName> {
         self.file_name()?.to_os_string().into_some()
     }
+
+    fn split_tail(&self, n: usize) -> Option<(&Self, &Self)> {
+        let head = self.ancestors().nth(n)?;
+        let tail = self.strip_prefix(head).ok()?;
+        (head, tail).into_some()
+    }
 }
 
 // impl FsFileName for std::path::Path {
@@ -90,6 +99,14 @@ impl FsPath for str {
     fn file_name(&self) -> Option<Self::FileName> {
         None
     }
+
+    fn split_tail(&self, n: usize) -> Option<(&Self, &Self)> {
+        if n == 0 {
+            return (self, &self[self.len()..]).into_some();
+        }
+        let (idx, _) = self.rmatch_indices('/').nth(n - 1)?;
+        (&self[..idx], &self[idx + 1..]).into_some()
+    }
 }
 
 pub struct StringDisplay<'s> {
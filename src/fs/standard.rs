@@ -1,4 +1,5 @@
 use super::{FsError, FsFileType, FsMetadata, FsReadDir, FsDirEntry, FsRootDirEntry, FsReadDirIterator};
+use crate::error::ErrorKind;
 use crate::wd::{IntoOk, IntoSome};
 
 use same_file;
@@ -12,6 +13,37 @@ impl FsError for std::io::Error {
     fn from_inner(inner: Self::Inner) -> Self {
         inner
     }
+
+    fn kind(&self) -> ErrorKind {
+        // Kinds which have no std::io::ErrorKind variant on the supported
+        // toolchains are recognized by the OS error code
+        #[cfg(unix)]
+        const RAW_KINDS: &[(i32, ErrorKind)] = &[
+            (libc::ENFILE, ErrorKind::TooManyOpenFiles),
+            (libc::EMFILE, ErrorKind::TooManyOpenFiles),
+            (libc::ELOOP, ErrorKind::Loop),
+            (libc::ENOTDIR, ErrorKind::NotADirectory),
+        ];
+        #[cfg(windows)]
+        const RAW_KINDS: &[(i32, ErrorKind)] = &[
+            (winapi::shared::winerror::ERROR_TOO_MANY_OPEN_FILES as i32, ErrorKind::TooManyOpenFiles),
+            (winapi::shared::winerror::ERROR_DIRECTORY as i32, ErrorKind::NotADirectory),
+        ];
+        #[cfg(not(any(unix, windows)))]
+        const RAW_KINDS: &[(i32, ErrorKind)] = &[];
+
+        if let Some(code) = self.raw_os_error() {
+            if let Some(&(_, kind)) = RAW_KINDS.iter().find(|&&(c, _)| c == code) {
+                return kind;
+            }
+        }
+
+        match std::io::Error::kind(self) {
+            std::io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+            _ => ErrorKind::Other,
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////
@@ -114,7 +146,7 @@ impl StandardDirEntry {
 
     /// Makes new StandardDirEntry from inner fs object
     pub fn from_inner(inner: std::fs::DirEntry) -> Result<Self, std::io::Error> {
-        let pathbuf = inner.path().to_path_buf();
+        let pathbuf = inner.path();
         Self {
             pathbuf,
             inner,
@@ -114,8 +114,17 @@ mod error;
 /// Filesystem-specific parts
 pub mod fs;
 mod walk;
+mod util;
+pub mod profile;
 /// Content processor
 pub mod cp;
+pub mod diff;
+pub mod dupes;
+pub mod du;
+pub mod expr;
+pub mod manifest;
+pub mod render;
+pub mod tree;
 
 #[cfg(test)]
 mod tests;
@@ -123,9 +132,15 @@ mod tests;
 /// Default (classic) WalkDir
 pub type WalkDir = WalkDirBuilder<fs::DefaultDirEntry, cp::DirEntryContentProcessor>;
 
-pub use wd::{ContentFilter, ContentOrder, Depth, FnCmp, Position, Result, ResultInner};
-pub use walk::{ClassicFilterEntry, ClassicIter, ClassicWalkDirIter, FilterEntry, RawDirEntry, ReadDir, WalkDirBuilder, WalkDirIter, WalkDirIterator, WalkDirIteratorItem, WalkDirOptions};
-pub use error::Error;
+/// WalkDir yielding only paths, file types and depths (see `cp::PathContentProcessor`)
+pub type PathWalkDir = WalkDirBuilder<fs::DefaultDirEntry, cp::PathContentProcessor>;
+
+pub use wd::{ContentFilter, ContentOrder, Depth, DirSummary, ErrorAction, FnCmp, FnErrorPolicy, LastFlags, Position, Result, ResultInner, SiblingInfo};
+pub use walk::{ClassicFilterEntry, ClassicIter, ClassicWalkDirIter, CollectErrors, ErrorReport, ErrorReportDetails, FilterEntry, FoldTree, LendingEntry, LendingWalk, RawDirEntry, ReadDir, WalkDirBuilder, WalkDirConfig, WalkDirIter, WalkControl, WalkDirIterator, WalkDirIteratorItem, WalkDirOptions, WalkDirOptionsImmut, WalkVisitor};
+pub use profile::{FnEntryFilter, ProfileIter, SortBy, WalkProfile};
+pub use error::{Error, ErrorKind, Operation};
 pub use cp::DirEntry;
+#[cfg(feature = "serde")]
+pub use cp::DirEntrySnapshot;
 
 
@@ -0,0 +1,530 @@
+//! Manifests of dir trees.
+//!
+//! A manifest records relative path, type, size, mode, mtime, symlink target
+//! and (optionally) content hash of every entry below the root, one entry per
+//! line, in the order of a walk sorted by name (tabs are shown as spaces here):
+//!
+//! ```text
+//! walkdir2-manifest 1
+//! d  0755  0  -  -  src
+//! f  0644  1024  1600000000.000000000  -  src/lib.rs
+//! l  0777  6  1600000000.000000000  -  src/link  lib.rs
+//! ```
+//!
+//! Fields are separated by tabs: type (`f`, `d` or `l`), octal mode, size,
+//! mtime (seconds and nanoseconds since the unix epoch), hash, path and (for
+//! symlinks) target. Missing values are written as `-`. Backslash, tab and
+//! line breaks in paths are escaped with a backslash, bytes which are not
+//! valid UTF-8 are written as `\xHH`. Sizes and mtimes of dirs are not
+//! recorded, since they depend on the file system rather than on content.
+//!
+//! ```no_run
+//! use walkdir2::manifest::ManifestWriter;
+//!
+//! # fn try_main() -> std::io::Result<()> {
+//! let mut writer = ManifestWriter::new();
+//! let file = std::fs::File::create("release.manifest")?;
+//! let errors = writer.write("release", file)?;
+//! eprintln!("{}", errors);
+//!
+//! let file = std::io::BufReader::new(std::fs::File::open("release.manifest")?);
+//! let (differences, _errors) = writer.verify("release", file)?;
+//! for difference in differences {
+//!     println!("{}", difference);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cp::DirEntry;
+use crate::error::{into_path_err, Error, Operation};
+use crate::fs::FsDirEntry;
+use crate::util::{mode, os_bytes};
+use crate::walk::ErrorReport;
+use crate::wd::Position;
+use crate::WalkDir;
+
+const HEADER: &str = "walkdir2-manifest 1";
+
+/////////////////////////////////////////////////////////////////////////
+//// ManifestEntry
+
+/// A type of entry in manifest
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EntryType {
+    /// Regular file
+    File,
+    /// Dir
+    Dir,
+    /// Symlink (never followed)
+    Symlink,
+}
+
+/// A single line of manifest
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ManifestEntry {
+    /// Path relative to the root
+    pub path: PathBuf,
+    /// Type of entry
+    pub ty: EntryType,
+    /// Permission bits (on non-unix platforms only readonly flag is recorded as `0444`/`0644`)
+    pub mode: u32,
+    /// Size in bytes (0 for dirs)
+    pub size: u64,
+    /// Last modification time (None for dirs)
+    pub mtime: Option<SystemTime>,
+    /// Content hash (files only, when a hash function is set)
+    pub hash: Option<String>,
+    /// Target of symlink
+    pub target: Option<PathBuf>,
+}
+
+/// Fields of [`ManifestEntry`] which differ
+///
+/// [`ManifestEntry`]: struct.ManifestEntry.html
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ChangedFields {
+    /// Type differs
+    pub ty: bool,
+    /// Permission bits differ
+    pub mode: bool,
+    /// Size differs
+    pub size: bool,
+    /// Modification time differs
+    pub mtime: bool,
+    /// Content hash differs (only when both entries have it)
+    pub hash: bool,
+    /// Symlink target differs
+    pub target: bool,
+}
+
+impl ChangedFields {
+    /// Is there any change?
+    pub fn any(&self) -> bool {
+        self.ty || self.mode || self.size || self.mtime || self.hash || self.target
+    }
+}
+
+impl fmt::Display for ChangedFields {
+    /// Formats as a list of names of changed fields, e.g. `size, hash`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = [
+            (self.ty, "type"),
+            (self.mode, "mode"),
+            (self.size, "size"),
+            (self.mtime, "mtime"),
+            (self.hash, "hash"),
+            (self.target, "target"),
+        ];
+        let mut sep = "";
+        for &(changed, name) in fields.iter() {
+            if changed {
+                write!(f, "{}{}", sep, name)?;
+                sep = ", ";
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ManifestEntry {
+    /// Fields which differ from `other`
+    pub fn changed_fields(&self, other: &ManifestEntry, compare_mtime: bool) -> ChangedFields {
+        ChangedFields {
+            ty: self.ty != other.ty,
+            mode: self.mode != other.mode,
+            size: self.size != other.size,
+            mtime: compare_mtime && self.mtime != other.mtime,
+            hash: self.hash.is_some() && other.hash.is_some() && self.hash != other.hash,
+            target: self.target != other.target,
+        }
+    }
+
+    /// Errors of reading the file or the link target are reported as errors of the walk
+    fn from_dent(dent: &DirEntry, hasher: &mut Option<FnHash>) -> Result<Self, Error> {
+        let md = dent.metadata_ref();
+        let ft = dent.file_type();
+        let ty = if ft.is_symlink() {
+            EntryType::Symlink
+        } else if ft.is_dir() {
+            EntryType::Dir
+        } else {
+            EntryType::File
+        };
+
+        let (size, mtime) = match ty {
+            EntryType::Dir => (0, None),
+            _ => (md.len(), md.modified().ok()),
+        };
+        let path_err = |op, err| Error::from_inner(into_path_err(dent.path(), op, err), dent.depth());
+        let hash = match (ty, hasher) {
+            (EntryType::File, Some(hasher)) => Some(hasher(dent.path()).map_err(|err| path_err(Operation::ReadFile, err))?),
+            _ => None,
+        };
+        let target = match ty {
+            EntryType::Symlink => {
+                Some(std::fs::read_link(dent.path()).map_err(|err| path_err(Operation::FollowLink, err))?)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            path: dent.relative_path().to_path_buf(),
+            ty,
+            mode: mode(md),
+            size,
+            mtime,
+            hash,
+            target,
+        })
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 6 && fields.len() != 7 {
+            return None;
+        }
+
+        let ty = match fields[0] {
+            "f" => EntryType::File,
+            "d" => EntryType::Dir,
+            "l" => EntryType::Symlink,
+            _ => return None,
+        };
+        let mode = u32::from_str_radix(fields[1], 8).ok()?;
+        let size = fields[2].parse().ok()?;
+        let mtime = match fields[3] {
+            "-" => None,
+            s => {
+                let mut parts = s.splitn(2, '.');
+                let (secs, nanos) = (parts.next()?, parts.next()?);
+                Some(UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?))
+            }
+        };
+        let hash = match fields[4] {
+            "-" => None,
+            s => Some(s.to_string()),
+        };
+        let path = PathBuf::from(unescape(fields[5])?);
+        let target = match fields.get(6) {
+            Some(s) => Some(PathBuf::from(unescape(s)?)),
+            None => None,
+        };
+
+        Some(Self { path, ty, mode, size, mtime, hash, target })
+    }
+}
+
+impl fmt::Display for ManifestEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ty = match self.ty {
+            EntryType::File => "f",
+            EntryType::Dir => "d",
+            EntryType::Symlink => "l",
+        };
+        write!(f, "{}\t{:04o}\t{}\t", ty, self.mode, self.size)?;
+        match self.mtime.and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+            Some(d) => write!(f, "{}.{:09}\t", d.as_secs(), d.subsec_nanos())?,
+            None => f.write_str("-\t")?,
+        }
+        write!(f, "{}\t{}", self.hash.as_deref().unwrap_or("-"), escape(self.path.as_os_str()))?;
+        if let Some(ref target) = self.target {
+            write!(f, "\t{}", escape(target.as_os_str()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn os_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    String::from_utf8(bytes).ok().map(OsString::from)
+}
+
+fn escape(s: &std::ffi::OsStr) -> String {
+    let bytes = os_bytes(s);
+    let mut out = String::with_capacity(bytes.len());
+    let mut rest = &bytes[..];
+    loop {
+        let (valid, invalid) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, &[][..]),
+            Err(err) => {
+                let (valid, after) = rest.split_at(err.valid_up_to());
+                let (invalid, after) = after.split_at(err.error_len().unwrap_or(after.len()));
+                rest = after;
+                (std::str::from_utf8(valid).unwrap(), invalid)
+            }
+        };
+        for c in valid.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                c => out.push(c),
+            }
+        }
+        for b in invalid {
+            out.push_str(&format!("\\x{:02x}", b));
+        }
+        if invalid.is_empty() {
+            break;
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> Option<OsString> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        match bytes.next()? {
+            b'\\' => out.push(b'\\'),
+            b't' => out.push(b'\t'),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b'x' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            _ => return None,
+        }
+    }
+    os_from_bytes(out)
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Manifest
+
+/// A parsed manifest
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Manifest {
+    /// Entries in the order of a walk sorted by name
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Parse manifest from its text form
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a manifest"));
+        }
+
+        let mut entries = vec![];
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry = ManifestEntry::parse(&line).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("invalid manifest line {}", n + 2))
+            })?;
+            entries.push(entry);
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { entries })
+    }
+
+    /// Write manifest in its text form
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        for entry in &self.entries {
+            writeln!(writer, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Difference
+
+/// A difference between a manifest and a tree
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Difference {
+    /// Entry is in manifest, but not in the tree
+    Missing(ManifestEntry),
+    /// Entry is in the tree, but not in manifest
+    Extra(ManifestEntry),
+    /// Entry differs (expected, actual and changed fields)
+    Changed(ManifestEntry, ManifestEntry, ChangedFields),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Missing(e) => write!(f, "missing: {}", escape(e.path.as_os_str())),
+            Difference::Extra(e) => write!(f, "extra: {}", escape(e.path.as_os_str())),
+            Difference::Changed(e, _, fields) => {
+                write!(f, "changed: {} ({})", escape(e.path.as_os_str()), fields)
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// ManifestWriter
+
+/// A content hash function (gets the path of a file, returns its hash as text)
+pub type FnHash = Box<dyn FnMut(&Path) -> io::Result<String> + Send + Sync + 'static>;
+
+/// Creates and verifies manifests.
+///
+/// Symlinks are never followed.
+pub struct ManifestWriter {
+    hasher: Option<FnHash>,
+    compare_mtime: bool,
+}
+
+impl fmt::Debug for ManifestWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManifestWriter")
+            .field("hasher", &if self.hasher.is_some() { "Some(...)" } else { "None" })
+            .field("compare_mtime", &self.compare_mtime)
+            .finish()
+    }
+}
+
+impl Default for ManifestWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManifestWriter {
+    /// Create without hashing
+    pub fn new() -> Self {
+        Self { hasher: None, compare_mtime: true }
+    }
+
+    /// Set a function which hashes content of files
+    pub fn hash_with<F>(mut self, hasher: F) -> Self
+    where
+        F: FnMut(&Path) -> io::Result<String> + Send + Sync + 'static,
+    {
+        self.hasher = Some(Box::new(hasher));
+        self
+    }
+
+    /// Report different mtimes on verify (default `true`)
+    pub fn compare_mtime(mut self, yes: bool) -> Self {
+        self.compare_mtime = yes;
+        self
+    }
+
+    /// Walk the tree and call `f` for each entry in manifest order.
+    ///
+    /// Errors of the walk don't stop it, they are returned (entries which
+    /// could not be read, hashed or whose link target could not be read are
+    /// not passed to `f`).
+    fn walk<P, F>(&mut self, root: P, mut f: F) -> io::Result<ErrorReport>
+    where
+        P: AsRef<Path>,
+        F: FnMut(ManifestEntry) -> io::Result<()>,
+    {
+        let iter = WalkDir::new(root)
+            .min_depth(1)
+            .sort_by(|a, b, _| a.0.path().cmp(b.0.path()));
+
+        let mut errors = ErrorReport::default();
+        for pos in iter {
+            match pos {
+                Position::Entry(dent) => match ManifestEntry::from_dent(&dent, &mut self.hasher) {
+                    Ok(entry) => f(entry)?,
+                    Err(err) => errors.push(err),
+                },
+                Position::Error(err) => errors.push(err),
+                _ => {}
+            }
+        }
+        Ok(errors)
+    }
+
+    /// Collect manifest of the tree (along with errors of the walk)
+    pub fn manifest<P: AsRef<Path>>(&mut self, root: P) -> io::Result<(Manifest, ErrorReport)> {
+        let mut entries = vec![];
+        let errors = self.walk(root, |e| {
+            entries.push(e);
+            Ok(())
+        })?;
+        Ok((Manifest { entries }, errors))
+    }
+
+    /// Write manifest of the tree (streaming) and return errors of the walk
+    pub fn write<P: AsRef<Path>, W: Write>(&mut self, root: P, mut writer: W) -> io::Result<ErrorReport> {
+        writeln!(writer, "{}", HEADER)?;
+        let errors = self.walk(root, |e| writeln!(writer, "{}", e))?;
+        writer.flush()?;
+        Ok(errors)
+    }
+
+    /// Walk the tree and compare it with manifest.
+    ///
+    /// Entries which could not be read because of errors of the walk are
+    /// reported as missing, the errors are returned along with differences.
+    pub fn verify<P: AsRef<Path>, R: BufRead>(
+        &mut self,
+        root: P,
+        manifest: R,
+    ) -> io::Result<(Vec<Difference>, ErrorReport)> {
+        let manifest = Manifest::read(manifest)?;
+        self.verify_manifest(root, &manifest)
+    }
+
+    /// Walk the tree and compare it with already parsed manifest (see [`verify`])
+    ///
+    /// [`verify`]: #method.verify
+    pub fn verify_manifest<P: AsRef<Path>>(
+        &mut self,
+        root: P,
+        manifest: &Manifest,
+    ) -> io::Result<(Vec<Difference>, ErrorReport)> {
+        let compare_mtime = self.compare_mtime;
+        let mut expected = manifest.entries.iter().peekable();
+        let mut differences = vec![];
+
+        let errors = self.walk(root, |actual| {
+            loop {
+                let ord = match expected.peek() {
+                    Some(e) => e.path.cmp(&actual.path),
+                    None => Ordering::Greater,
+                };
+                match ord {
+                    Ordering::Less => differences.push(Difference::Missing(expected.next().unwrap().clone())),
+                    Ordering::Greater => {
+                        differences.push(Difference::Extra(actual));
+                        return Ok(());
+                    }
+                    Ordering::Equal => {
+                        let e = expected.next().unwrap();
+                        let fields = e.changed_fields(&actual, compare_mtime);
+                        if fields.any() {
+                            differences.push(Difference::Changed(e.clone(), actual, fields));
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+        })?;
+
+        differences.extend(expected.cloned().map(Difference::Missing));
+        Ok((differences, errors))
+    }
+}
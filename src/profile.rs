@@ -0,0 +1,247 @@
+//! Reusable walk configurations.
+//!
+//! A [`WalkProfile`] keeps all builder settings which are not closures, plus
+//! a sort order and include/exclude globs, so it can be loaded from a config
+//! file and applied to many roots.
+//!
+//! [`WalkProfile`]: struct.WalkProfile.html
+
+use std::path::Path;
+
+use crate::cp::{ContentProcessor, DirEntry, DirEntryContentProcessor};
+use crate::fs;
+use crate::walk::{FilterEntry, WalkDirBuilder, WalkDirConfig, WalkDirIter, WalkDirIterator};
+
+/////////////////////////////////////////////////////////////////////////
+//// SortBy
+
+/// A named built-in sort order (see [`WalkProfile`])
+///
+/// [`WalkProfile`]: struct.WalkProfile.html
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SortBy {
+    /// By file name, ascending
+    Name,
+    /// By file name, descending
+    NameDesc,
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// WalkProfile
+
+/// A predicate for `filter_entry` produced by [`WalkProfile::entry_filter`].
+///
+/// [`WalkProfile::entry_filter`]: struct.WalkProfile.html#method.entry_filter
+pub type FnEntryFilter<E> = Box<dyn FnMut(&DirEntry<E>) -> bool + Send + Sync + 'static>;
+
+/// An iterator produced by [`WalkProfile::walk`].
+///
+/// [`WalkProfile::walk`]: struct.WalkProfile.html#method.walk
+pub type ProfileIter<E = fs::DefaultDirEntry> = FilterEntry<
+    E,
+    DirEntryContentProcessor,
+    WalkDirIterator<E, DirEntryContentProcessor>,
+    FnEntryFilter<E>,
+>;
+
+/// All `WalkDirBuilder` settings which are not closures.
+///
+/// Unlike the builder, a profile is `Clone` and (with the `serde` feature)
+/// can be loaded from any format supported by serde, so one configuration can
+/// be applied to many roots. All fields are optional when deserializing:
+///
+/// ```toml
+/// sort = "name"
+/// include = ["*.rs"]
+/// exclude = ["target", ".*"]
+///
+/// [options]
+/// max_depth = 3
+/// follow_links = true
+/// content_order = "dirs_first"
+/// ```
+///
+/// Patterns are globs matched against the path relative to the root (with
+/// `/` as separator): `?` matches any char but `/`, `*` matches any run of
+/// chars but `/` and `**` matches anything. A pattern without `/` is matched
+/// against the file name only, a leading `/` anchors the pattern at the root.
+///
+/// Excluded entries are not yielded and excluded dirs are not descended into.
+/// If `include` is not empty, only files matching one of its patterns are
+/// yielded (dirs are not affected). The root is never filtered out.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct WalkProfile {
+    /// Builder options (see [`WalkDirConfig`])
+    ///
+    /// [`WalkDirConfig`]: ../struct.WalkDirConfig.html
+    pub options: WalkDirConfig,
+    /// Sort order of entries within each dir
+    pub sort: Option<SortBy>,
+    /// Patterns of files to yield (all files if empty)
+    pub include: Vec<String>,
+    /// Patterns of entries to skip
+    pub exclude: Vec<String>,
+}
+
+impl WalkProfile {
+    /// Applies options and sort order of this profile to the builder.
+    ///
+    /// Patterns are not applied, since the builder has no entry filter --
+    /// use [`entry_filter`] or [`walk`] for them.
+    ///
+    /// [`entry_filter`]: #method.entry_filter
+    /// [`walk`]: #method.walk
+    pub fn apply<FS, CP>(&self, builder: WalkDirBuilder<FS, CP>) -> WalkDirBuilder<FS, CP>
+    where
+        FS: fs::FsDirEntry,
+        CP: ContentProcessor<FS>,
+    {
+        let builder = builder.with_config(&self.options);
+        match self.sort {
+            None => builder,
+            Some(SortBy::Name) => builder.sort_by(|a, b, _| a.0.path().cmp(b.0.path())),
+            Some(SortBy::NameDesc) => builder.sort_by(|a, b, _| b.0.path().cmp(a.0.path())),
+        }
+    }
+
+    /// Returns a predicate which checks include/exclude patterns, to be
+    /// passed to `filter_entry`.
+    pub fn entry_filter<E>(&self) -> FnEntryFilter<E>
+    where
+        E: fs::FsDirEntry<Path = Path>,
+    {
+        let include: Vec<Glob> = self.include.iter().map(|p| Glob::new(p)).collect();
+        let exclude: Vec<Glob> = self.exclude.iter().map(|p| Glob::new(p)).collect();
+
+        Box::new(move |dent: &DirEntry<E>| {
+            if dent.depth() == 0 {
+                return true;
+            }
+
+            let path = relative_path_str(dent.relative_path());
+            if exclude.iter().any(|g| g.is_match(&path)) {
+                return false;
+            }
+
+            include.is_empty()
+                || dent.is_dir()
+                || include.iter().any(|g| g.is_match(&path))
+        })
+    }
+
+    /// Walks the given root with this profile (including patterns).
+    pub fn walk<P: AsRef<Path>>(&self, root: P) -> ProfileIter {
+        self.apply(WalkDirBuilder::new(root))
+            .build()
+            .filter_entry(self.entry_filter())
+    }
+}
+
+pub(crate) fn relative_path_str(path: &Path) -> String {
+    let parts: Vec<_> = path.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+    parts.join("/")
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Glob
+
+/// A compiled glob pattern
+#[derive(Debug, Clone)]
+struct Glob {
+    pattern: String,
+    /// Match against the full relative path (otherwise against file name only)
+    full_path: bool,
+}
+
+impl Glob {
+    fn new(pattern: &str) -> Self {
+        let full_path = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        Self { pattern: pattern.to_string(), full_path }
+    }
+
+    fn is_match(&self, path: &str) -> bool {
+        let s = if self.full_path {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        glob_match(&self.pattern, s)
+    }
+}
+
+/// Length of the char starting with byte `b`
+fn char_len(b: u8) -> usize {
+    match b {
+        0xF0..=0xFF => 4,
+        0xE0..=0xEF => 3,
+        0xC0..=0xDF => 2,
+        _ => 1,
+    }
+}
+
+/// Matches `s` against the glob `p` (see [`WalkProfile`] for the syntax).
+///
+/// Only the last `*` and the last `**` are retried on mismatch, so it runs in
+/// `O(p.len() * s.len())` time without allocations.
+///
+/// [`WalkProfile`]: struct.WalkProfile.html
+pub(crate) fn glob_match(p: &str, s: &str) -> bool {
+    let (p, s) = (p.as_bytes(), s.as_bytes());
+    let (mut px, mut sx) = (0, 0);
+    // Where to retry the last `*` (it can't extend past `/`)
+    let mut star: Option<(usize, usize)> = None;
+    // Where to retry the last `**`
+    let mut double_star: Option<(usize, usize)> = None;
+
+    while px < p.len() || sx < s.len() {
+        if px < p.len() {
+            match p[px] {
+                b'*' if p.get(px + 1) == Some(&b'*') => {
+                    star = None;
+                    if p.get(px + 2) == Some(&b'/') {
+                        // `**/` matches no dirs first, then everything up to each next `/`
+                        double_star = s[sx..].iter().position(|&b| b == b'/').map(|i| (px, sx + i + 1));
+                        px += 3;
+                    } else {
+                        double_star = if sx < s.len() { Some((px, sx + char_len(s[sx]))) } else { None };
+                        px += 2;
+                    }
+                    continue;
+                }
+                b'*' => {
+                    star = if sx < s.len() && s[sx] != b'/' { Some((px, sx + char_len(s[sx]))) } else { None };
+                    px += 1;
+                    continue;
+                }
+                b'?' if sx < s.len() && s[sx] != b'/' => {
+                    px += 1;
+                    sx += char_len(s[sx]);
+                    continue;
+                }
+                c if c != b'?' && sx < s.len() && s[sx] == c => {
+                    px += 1;
+                    sx += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        // Mismatch: let the last `*` or `**` consume one more char
+        if let Some((retry_px, retry_sx)) = star.take() {
+            px = retry_px;
+            sx = retry_sx;
+        } else if let Some((retry_px, retry_sx)) = double_star.take() {
+            px = retry_px;
+            sx = retry_sx;
+        } else {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,331 @@
+//! `tree(1)`-style rendering of a walk.
+//!
+//! [`TreeRenderer`] consumes the `Position` stream and writes each entry on
+//! its own line, prefixed with connectors. The connectors are computed from
+//! the entries which are actually printed, so the stream may be filtered:
+//!
+//! ```text
+//! foo (1 file)
+//! ├── bar (2 files)
+//! │   ├── a
+//! │   └── b
+//! └── c
+//!
+//! 1 directory, 3 files
+//! ```
+//!
+//! ```no_run
+//! use walkdir2::WalkDir;
+//! use walkdir2::render::TreeRenderer;
+//!
+//! let renderer = TreeRenderer::new().sizes(true).max_depth(2);
+//! let (summary, errors) = renderer.render(WalkDir::new("foo"), std::io::stdout()).unwrap();
+//! eprintln!("{} ({} errors)", summary, errors.len());
+//! ```
+//!
+//! [`TreeRenderer`]: struct.TreeRenderer.html
+
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cp::{DirEntry, DirEntryContentProcessor};
+use crate::error::Error;
+use crate::fs::{self, FsFileType};
+use crate::util::os_bytes;
+use crate::walk::{ErrorReport, WalkDirBuilder};
+use crate::wd::{ContentFilter, Depth, Position};
+
+/////////////////////////////////////////////////////////////////////////
+//// Charset
+
+/// Characters used to draw connectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Charset {
+    /// `├── `, `└── `, `│   `
+    Unicode,
+    /// `|-- `, `` `-- ``, `|   `
+    Ascii,
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        Charset::Unicode
+    }
+}
+
+impl Charset {
+    /// Returns the connector of an entry (`last`: it is the last one in its dir)
+    fn entry(self, last: bool) -> &'static str {
+        match (self, last) {
+            (Charset::Unicode, false) => "├── ",
+            (Charset::Unicode, true) => "└── ",
+            (Charset::Ascii, false) => "|-- ",
+            (Charset::Ascii, true) => "`-- ",
+        }
+    }
+
+    /// Returns the indent under an ancestor (`last`: the ancestor is the last one in its dir)
+    fn indent(self, last: bool) -> &'static str {
+        match (self, last) {
+            (Charset::Unicode, false) => "│   ",
+            (Charset::Ascii, false) => "|   ",
+            (_, true) => "    ",
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// RenderSummary
+
+/// Counts of rendered entries (the root is not counted)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenderSummary {
+    /// Count of dirs
+    pub dirs: usize,
+    /// Count of other entries
+    pub files: usize,
+}
+
+impl fmt::Display for RenderSummary {
+    /// Formats as `N directories, M files`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, {} {}",
+            self.dirs,
+            if self.dirs == 1 { "directory" } else { "directories" },
+            self.files,
+            if self.files == 1 { "file" } else { "files" },
+        )
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// TreeRenderer
+
+/// Renders a walk as a tree (see the [module docs]).
+///
+/// [module docs]: index.html
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeRenderer {
+    charset: Charset,
+    sizes: bool,
+    file_counts: bool,
+    max_depth: Option<Depth>,
+    summary: bool,
+}
+
+impl Default for TreeRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TreeRenderer {
+    /// Create a renderer with Unicode connectors and the final summary line.
+    pub fn new() -> Self {
+        Self { charset: Charset::Unicode, sizes: false, file_counts: false, max_depth: None, summary: true }
+    }
+
+    /// Characters used to draw connectors (Unicode by default).
+    pub fn charset(mut self, charset: Charset) -> Self {
+        self.charset = charset;
+        self
+    }
+
+    /// Use ASCII connectors (the same as `charset(Charset::Ascii)`).
+    pub fn ascii(self, yes: bool) -> Self {
+        self.charset(if yes { Charset::Ascii } else { Charset::Unicode })
+    }
+
+    /// Print the size in bytes before each name (`[      4096]  name`).
+    pub fn sizes(mut self, yes: bool) -> Self {
+        self.sizes = yes;
+        self
+    }
+
+    /// Print the count of files after the name of each dir descended into.
+    pub fn file_counts(mut self, yes: bool) -> Self {
+        self.file_counts = yes;
+        self
+    }
+
+    /// Don't render entries deeper than `depth`.
+    pub fn max_depth(mut self, depth: Depth) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Print `N directories, M files` after the tree (true by default).
+    pub fn summary(mut self, yes: bool) -> Self {
+        self.summary = yes;
+        self
+    }
+
+    /// Sets the options of the builder which the rendering depends on:
+    /// `contents_first(false)`, `min_depth(0)`, the depth limit and (with
+    /// `file_counts`) `yield_open_dir_with_content`.
+    pub fn configure<E>(&self, builder: WalkDirBuilder<E, DirEntryContentProcessor>) -> WalkDirBuilder<E, DirEntryContentProcessor>
+    where
+        E: fs::FsDirEntry,
+    {
+        let mut builder = builder.min_depth(0).contents_first(false);
+        if let Some(depth) = self.max_depth {
+            builder = builder.max_depth(depth);
+        }
+        if self.file_counts {
+            builder = builder.yield_open_dir_with_content(true).open_dir_with_content_filter(ContentFilter::FilesOnly);
+        }
+        builder
+    }
+
+    /// Walks with the builder (see [`configure`]) and writes the tree into `out`.
+    ///
+    /// Errors of the walk don't stop it, they are returned along with the summary.
+    ///
+    /// [`configure`]: #method.configure
+    pub fn render<E, W>(
+        &self,
+        builder: WalkDirBuilder<E, DirEntryContentProcessor>,
+        out: W,
+    ) -> io::Result<(RenderSummary, ErrorReport<E>)>
+    where
+        E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, FileName = OsString, Metadata = std::fs::Metadata>,
+        W: io::Write,
+    {
+        self.render_positions(self.configure(builder).build(), out)
+    }
+
+    /// Writes the tree of already produced positions into `out`.
+    ///
+    /// The positions may be filtered: an entry whose parent dir isn't among
+    /// them is drawn under its nearest ancestor which is, and the summary
+    /// counts only the printed entries. Each dir must go before its content
+    /// (no `contents_first`). File counts are printed only for dirs followed
+    /// by `Position::OpenDirWithContent`.
+    ///
+    /// Whether an entry is the last one in its dir is known only after its
+    /// subtree, so the lines are written when the positions are exhausted.
+    pub fn render_positions<E, W, I>(&self, positions: I, mut out: W) -> io::Result<(RenderSummary, ErrorReport<E>)>
+    where
+        E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, FileName = OsString, Metadata = std::fs::Metadata>,
+        W: io::Write,
+        I: IntoIterator<Item = Position<DirEntry<E>, Vec<DirEntry<E>>, Error<E>>>,
+    {
+        let mut summary = RenderSummary::default();
+        let mut errors = ErrorReport::default();
+        let mut lines: Vec<Line> = vec![];
+        // Depths of the printed dirs containing the last entry
+        let mut dirs: Vec<Depth> = vec![];
+        // Path of the dir of the last line, waiting for its file count
+        let mut pending: Option<PathBuf> = None;
+
+        for pos in positions {
+            let dent = match pos {
+                Position::Entry(dent) => dent,
+                Position::OpenDirWithContent(dir, content) => {
+                    if pending.take().map_or(false, |path| path == dir.path()) {
+                        let files = content.iter().filter(|dent| !dent.file_type().is_dir()).count();
+                        let line = lines.last_mut().unwrap();
+                        write!(line.text, " ({} {})", files, if files == 1 { "file" } else { "files" })?;
+                    }
+                    continue;
+                }
+                Position::Error(err) => {
+                    errors.push(err);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            pending = None;
+            if self.max_depth.map_or(false, |max| dent.depth() > max) {
+                continue;
+            }
+
+            let is_dir = dent.file_type().is_dir();
+            if dent.depth() > 0 {
+                if is_dir {
+                    summary.dirs += 1;
+                } else {
+                    summary.files += 1;
+                }
+            }
+
+            while dirs.last().map_or(false, |&depth| depth >= dent.depth()) {
+                dirs.pop();
+            }
+            let mut line = Line { level: dirs.len(), last: false, text: vec![] };
+            self.write_entry(&mut line.text, &dent)?;
+            lines.push(line);
+            if is_dir {
+                dirs.push(dent.depth());
+                if self.file_counts {
+                    pending = Some(dent.path().to_path_buf());
+                }
+            }
+        }
+
+        // A line is the last one in its dir if no later line has the same level before a lower one
+        let mut has_next: Vec<bool> = vec![];
+        for line in lines.iter_mut().rev() {
+            line.last = !has_next.get(line.level).copied().unwrap_or(false);
+            has_next.truncate(line.level);
+            has_next.resize(line.level, false);
+            has_next.push(true);
+        }
+
+        // Last flags of the ancestors of the current line (the lines at level 0 have no connector)
+        let mut ancestors: Vec<bool> = vec![];
+        for line in &lines {
+            ancestors.truncate(line.level.saturating_sub(1));
+            if line.level > 0 {
+                for &ancestor_last in &ancestors {
+                    out.write_all(self.charset.indent(ancestor_last).as_bytes())?;
+                }
+                out.write_all(self.charset.entry(line.last).as_bytes())?;
+                ancestors.push(line.last);
+            }
+            out.write_all(&line.text)?;
+            out.write_all(b"\n")?;
+        }
+
+        if self.summary {
+            writeln!(out)?;
+            writeln!(out, "{}", summary)?;
+        }
+
+        Ok((summary, errors))
+    }
+
+    /// Writes the size and the name of the entry
+    fn write_entry<E, W>(&self, mut out: W, dent: &DirEntry<E>) -> io::Result<()>
+    where
+        E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, FileName = OsString, Metadata = std::fs::Metadata>,
+        W: io::Write,
+    {
+        if self.sizes {
+            write!(out, "[{:>10}]  ", dent.metadata_ref().len())?;
+        }
+        if dent.depth() == 0 {
+            out.write_all(&os_bytes(dent.path().as_os_str()))
+        } else {
+            out.write_all(&os_bytes(dent.file_name()))
+        }
+    }
+}
+
+/// A printed entry (without the connectors)
+struct Line {
+    /// Count of the printed dirs containing the entry
+    level: usize,
+    /// It is the last printed entry in its dir
+    last: bool,
+    /// Size, name and file count
+    text: Vec<u8>,
+}
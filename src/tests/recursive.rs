@@ -2,7 +2,9 @@ use std::fs;
 use std::path::PathBuf;
 
 use crate::tests::util::Dir;
-use crate::{ClassicWalkDirIter, ContentFilter, ContentOrder, Position, WalkDir, WalkDirIter, fs::FsDirEntry};
+use crate::{ClassicWalkDirIter, ContentFilter, ContentOrder, DirEntry, DirSummary, Error, ErrorAction, ErrorKind, Operation, Position, SiblingInfo, SortBy, WalkControl, WalkDir, WalkProfile, WalkDirIter, WalkVisitor, fs::FsDirEntry};
+use crate::cp::DirEntryContentProcessor;
+use crate::fs::DefaultDirEntry;
 
 /// Check for defaulted type parameter bug
 /// See https://github.com/rust-lang/rust/issues/27336
@@ -648,6 +650,21 @@ fn sym_loop_detect() {
 
     assert_eq!(4, err.depth());
     assert!(err.io_error().is_none());
+    assert_eq!(ErrorKind::Loop, err.kind());
+    assert_eq!(Operation::FollowLink, err.operation());
+}
+
+#[test]
+fn root_under_file_not_a_directory() {
+    let dir = Dir::tmp();
+    dir.touch_all(&["a"]);
+
+    let wd = WalkDir::new(dir.join("a/b"));
+    let r = dir.run_recursive(wd.into_classic());
+
+    let errs = r.errs();
+    assert_eq!(1, errs.len());
+    assert_eq!(ErrorKind::NotADirectory, errs[0].kind());
 }
 
 #[test]
@@ -694,6 +711,8 @@ fn sym_file_self_loop_io_error() {
     assert_eq!(1, err.depth());
     assert!(err.loop_ancestor().is_none());
     assert!(err.io_error().is_some());
+    assert_eq!(ErrorKind::Loop, err.kind());
+    assert_eq!(Operation::FollowLink, err.operation());
 }
 
 #[test]
@@ -848,7 +867,7 @@ fn classic_contents_first_ordered() {
     dir.touch_all(&["zzz/a", "zzz/b", "zzz/c"]);
     dir.touch_all(&["baz/a", "baz/b", "baz/c"]);
 
-    let mut wd = WalkDir::new(dir.path())
+    let wd = WalkDir::new(dir.path())
         .contents_first(false)
         .content_filter(ContentFilter::SkipAll)
         .sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()))
@@ -856,7 +875,7 @@ fn classic_contents_first_ordered() {
         .open_dir_with_content_filter(ContentFilter::FilesOnly)
         .into_iter();
     let mut r: Vec<(PathBuf, Vec<String>)> = vec![];
-    while let Some(pos) = wd.next() {
+    for pos in wd {
         match pos {
             Position::OpenDirWithContent(dent, content) => {
                 let path = dent.path().to_path_buf();
@@ -867,7 +886,7 @@ fn classic_contents_first_ordered() {
 
                 r.push((path, content));
             }
-            Position::CloseDir => {}
+            Position::CloseDir(_) => {}
             _ => panic!(),
         }
     }
@@ -927,6 +946,646 @@ fn contents_first_ordered() {
     assert_eq!(expected, r.paths());
 }
 
+#[test]
+fn close_dir_entry() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.mkdirp("baz");
+    dir.touch_all(&["foo/a", "foo/bar/b", "baz/c"]);
+
+    for &contents_first in &[false, true] {
+        let mut stack = vec![];
+        let mut closed = vec![];
+        for pos in WalkDir::new(dir.path()).contents_first(contents_first) {
+            match pos {
+                Position::OpenDir(dent) => stack.push(dent.path().to_path_buf()),
+                Position::CloseDir(dent) => {
+                    assert_eq!(stack.pop().as_deref(), Some(dent.path()));
+                    assert!(dent.file_type().is_dir());
+                    closed.push(dent.into_path());
+                }
+                Position::Entry(_) => {}
+                _ => panic!(),
+            }
+        }
+        assert!(stack.is_empty());
+
+        closed.sort();
+        let expected =
+            vec![dir.path().to_path_buf(), dir.join("baz"), dir.join("foo"), dir.join("foo").join("bar")];
+        assert_eq!(expected, closed);
+    }
+}
+
+#[test]
+fn close_dir_with_summary() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.mkdirp("foo/baz");
+    dir.touch_all(&["foo/a", "foo/b", "foo/c"]);
+
+    let wd = WalkDir::new(dir.path()).yield_close_dir_with_summary(true);
+    let mut summaries = vec![];
+    for pos in wd {
+        match pos {
+            Position::CloseDirWithSummary(dent, summary) => {
+                summaries.push((dent.into_path(), summary))
+            }
+            Position::CloseDir(_) => panic!(),
+            _ => {}
+        }
+    }
+    summaries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let expected = vec![
+        (dir.path().to_path_buf(), DirSummary { files: 0, dirs: 1, errors: 0, skipped: false }),
+        (dir.join("foo"), DirSummary { files: 3, dirs: 2, errors: 0, skipped: false }),
+        (dir.join("foo").join("bar"), DirSummary::default()),
+        (dir.join("foo").join("baz"), DirSummary::default()),
+    ];
+    assert_eq!(expected, summaries);
+}
+
+#[test]
+fn fold_tree() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.mkdirp("foo/baz");
+    dir.touch_all(&["foo/a", "foo/b", "foo/bar/c", "d"]);
+
+    for &contents_first in &[false, true] {
+        let wd = WalkDir::new(dir.path()).contents_first(contents_first);
+        let mut counts = vec![];
+        let mut order = vec![];
+        for result in wd.into_iter().fold_tree(|_| 1usize, |_, children| children.into_iter().sum()) {
+            let (dent, count) = result.unwrap();
+            order.push(dent.path().to_path_buf());
+            counts.push((dent.into_path(), count));
+        }
+        assert_eq!(Some(&dir.path().to_path_buf()), order.last());
+        counts.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let expected = vec![
+            (dir.path().to_path_buf(), 4),
+            (dir.join("foo"), 3),
+            (dir.join("foo").join("bar"), 1),
+            (dir.join("foo").join("baz"), 0),
+        ];
+        assert_eq!(expected, counts);
+    }
+}
+
+struct TestVisitor<F> {
+    control: F,
+    paths: Vec<PathBuf>,
+    open: usize,
+    close: usize,
+}
+
+impl<F> TestVisitor<F>
+where
+    F: FnMut(&str, &DirEntry) -> WalkControl,
+{
+    fn new(control: F) -> Self {
+        Self { control, paths: vec![], open: 0, close: 0 }
+    }
+}
+
+impl<F> WalkVisitor<DefaultDirEntry, DirEntryContentProcessor> for TestVisitor<F>
+where
+    F: FnMut(&str, &DirEntry) -> WalkControl,
+{
+    fn open_dir(&mut self, dir: &DirEntry) -> WalkControl {
+        self.open += 1;
+        (self.control)("open", dir)
+    }
+
+    fn entry(&mut self, entry: &DirEntry) -> WalkControl {
+        self.paths.push(entry.path().to_path_buf());
+        (self.control)("entry", entry)
+    }
+
+    fn close_dir(&mut self, dir: &DirEntry) -> WalkControl {
+        self.close += 1;
+        (self.control)("close", dir)
+    }
+
+    fn error(&mut self, err: Error) -> WalkControl {
+        panic!("unexpected error: {}", err);
+    }
+}
+
+fn visit_tree() -> Dir {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/a/x");
+    dir.mkdirp("foo/b/y");
+    dir.touch_all(&["foo/c"]);
+    dir
+}
+
+#[test]
+fn visit_skip_subtree() {
+    let dir = visit_tree();
+
+    for &kind in &["entry", "open"] {
+        let mut visitor = TestVisitor::new(|k: &str, dent: &DirEntry| {
+            if k == kind && dent.file_name() == "a" {
+                WalkControl::SkipSubtree
+            } else {
+                WalkControl::Continue
+            }
+        });
+        let wd = WalkDir::new(dir.path()).sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()));
+        assert_eq!(WalkControl::Continue, wd.visit(&mut visitor));
+        assert_eq!(visitor.open, visitor.close);
+
+        let expected = vec![
+            dir.path().to_path_buf(),
+            dir.join("foo"),
+            dir.join("foo").join("a"),
+            dir.join("foo").join("b"),
+            dir.join("foo").join("b").join("y"),
+            dir.join("foo").join("c"),
+        ];
+        assert_eq!(expected, visitor.paths);
+    }
+}
+
+#[test]
+fn visit_skip_siblings() {
+    let dir = visit_tree();
+
+    for &kind in &["entry", "open", "close"] {
+        for &contents_first in &[false, true] {
+            let mut visitor = TestVisitor::new(|k: &str, dent: &DirEntry| {
+                if k == kind && dent.file_name() == "a" {
+                    WalkControl::SkipSiblings
+                } else {
+                    WalkControl::Continue
+                }
+            });
+            let wd = WalkDir::new(dir.path())
+                .contents_first(contents_first)
+                .sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()));
+            assert_eq!(WalkControl::Continue, wd.visit(&mut visitor));
+            assert_eq!(visitor.open, visitor.close);
+
+            let mut paths = visitor.paths;
+            paths.sort();
+            let expected = vec![
+                dir.path().to_path_buf(),
+                dir.join("foo"),
+                dir.join("foo").join("a"),
+                dir.join("foo").join("a").join("x"),
+            ];
+            assert_eq!(expected, paths, "{} {}", kind, contents_first);
+        }
+    }
+}
+
+#[test]
+fn visit_stop() {
+    let dir = visit_tree();
+
+    let mut visitor = TestVisitor::new(|k: &str, dent: &DirEntry| {
+        if k == "entry" && dent.file_name() == "b" {
+            WalkControl::Stop
+        } else {
+            WalkControl::Continue
+        }
+    });
+    let wd = WalkDir::new(dir.path()).sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()));
+    assert_eq!(WalkControl::Stop, wd.visit(&mut visitor));
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("foo"),
+        dir.join("foo").join("a"),
+        dir.join("foo").join("a").join("x"),
+        dir.join("foo").join("b"),
+    ];
+    assert_eq!(expected, visitor.paths);
+}
+
+#[test]
+fn relative_path() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch_all(&["foo/a"]);
+
+    for root in &[dir.path().to_path_buf(), dir.path().join(".")] {
+        let mut paths = vec![];
+        for dent in WalkDir::new(root).into_classic() {
+            let dent = dent.unwrap();
+            assert_eq!(root.as_path(), dent.root());
+            assert_eq!(dent.depth(), dent.components().count());
+            paths.push(dent.relative_path().to_path_buf());
+        }
+        paths.sort();
+
+        let expected = vec![
+            PathBuf::new(),
+            PathBuf::from("foo"),
+            PathBuf::from("foo").join("a"),
+            PathBuf::from("foo").join("bar"),
+        ];
+        assert_eq!(expected, paths);
+    }
+}
+
+#[test]
+fn sibling_info() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch_all(&["foo/a", "foo/bar/b", "z"]);
+
+    let info = |index, count, last: &[bool]| SiblingInfo { index, count, last: last.into() };
+
+    let wd = WalkDir::new(dir.path())
+        .track_siblings(true)
+        .sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()));
+    let mut got = vec![];
+    for pos in wd {
+        if let Position::Entry(dent) = pos {
+            got.push((dent.relative_path().to_path_buf(), dent.sibling_info().cloned().unwrap()));
+        }
+    }
+
+    let expected = vec![
+        (PathBuf::new(), info(0, 1, &[])),
+        (PathBuf::from("foo"), info(0, 2, &[false])),
+        (PathBuf::from("foo/a"), info(0, 2, &[false, false])),
+        (PathBuf::from("foo/bar"), info(1, 2, &[false, true])),
+        (PathBuf::from("foo/bar/b"), info(0, 1, &[false, true, true])),
+        (PathBuf::from("z"), info(1, 2, &[true])),
+    ];
+    assert_eq!(expected, got);
+
+    // Without sorting, counts are known as well
+    for dent in WalkDir::new(dir.path()).track_siblings(true).into_classic() {
+        let dent = dent.unwrap();
+        let info = dent.sibling_info().unwrap();
+        assert_eq!(dent.depth(), info.last.len());
+        assert_eq!(info.is_last(), info.last.last().cloned().unwrap_or(true));
+    }
+
+    // Hidden entries and entries dropped by the content processor are not counted
+    let not_z = crate::cp::filter_map(DirEntryContentProcessor::default(), |dent: DirEntry| {
+        if dent.file_name() == "z" {
+            None
+        } else {
+            Some(dent)
+        }
+    });
+    let wd = crate::WalkDirBuilder::with_context(dir.path(), (), not_z)
+        .track_siblings(true)
+        .content_filter(ContentFilter::FilesOnly)
+        .sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()));
+    let mut got = vec![];
+    for pos in wd {
+        if let Position::Entry(item) = pos {
+            got.push((item.relative_path().to_path_buf(), item.sibling_info().cloned().unwrap()));
+        }
+    }
+    let expected = vec![
+        (PathBuf::from("foo/a"), info(0, 1, &[true, true])),
+        (PathBuf::from("foo/bar/b"), info(0, 1, &[true, true, true])),
+    ];
+    assert_eq!(expected, got);
+
+    // And are not tracked by default
+    for dent in WalkDir::new(dir.path()).into_classic() {
+        assert!(dent.unwrap().sibling_info().is_none());
+    }
+}
+
+#[test]
+fn ancestors() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch_all(&["foo/bar/b"]);
+
+    for &contents_first in &[false, true] {
+        let mut it = WalkDir::new(dir.path()).contents_first(contents_first).into_iter();
+        let mut got = vec![];
+        while let Some(pos) = it.next() {
+            let dent = match pos {
+                Position::Entry(dent) => dent,
+                Position::OpenDir(_) | Position::CloseDir(_) => continue,
+                _ => panic!(),
+            };
+            let ancestors: Vec<PathBuf> =
+                it.ancestors().map(|a| a.relative_path().to_path_buf()).collect();
+            got.push((dent.relative_path().to_path_buf(), ancestors));
+        }
+        got.sort();
+
+        let root = PathBuf::new();
+        let foo = PathBuf::from("foo");
+        let bar = foo.join("bar");
+        let expected = vec![
+            (root.clone(), vec![]),
+            (foo.clone(), vec![root.clone()]),
+            (bar.clone(), vec![root.clone(), foo.clone()]),
+            (bar.join("b"), vec![root.clone(), foo.clone(), bar.clone()]),
+        ];
+        assert_eq!(expected, got);
+    }
+
+    // Ancestors are not processed again (and have sibling info)
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = calls.clone();
+    let names = crate::cp::from_fn(move |fsdent: &mut crate::cp::FsEntryMut<DefaultDirEntry>, _ctx: &mut _| {
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Some(fsdent.file_name())
+    });
+    let mut it = crate::WalkDirBuilder::with_context(dir.path(), (), names).track_siblings(true).build();
+    while let Some(pos) = it.next() {
+        if let Position::Entry(name) = pos {
+            if *name == "b" {
+                let before = calls.load(std::sync::atomic::Ordering::SeqCst);
+                let ancestors: Vec<_> = it.ancestors().map(|a| (a.to_os_string(), a.sibling_info().unwrap().last.len())).collect();
+                assert_eq!(before, calls.load(std::sync::atomic::Ordering::SeqCst));
+                assert_eq!(3, ancestors.len());
+                assert_eq!(("bar".into(), 2), ancestors[2]);
+            }
+        }
+    }
+}
+
+#[test]
+fn each_entry_processed_once() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.touch_all(&["a/x", "y"]);
+
+    // Root, a, a/b, a/x and y
+    for &(contents_first, open_with_content, track_siblings) in [(false, false, false), (true, false, false), (false, true, true), (true, true, true), (true, false, true)].iter() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let names = crate::cp::from_fn(move |fsdent: &mut crate::cp::FsEntryMut<DefaultDirEntry>, _ctx: &mut _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Some(fsdent.file_name())
+        });
+        let it = crate::WalkDirBuilder::with_context(dir.path(), (), names)
+            .contents_first(contents_first)
+            .yield_open_dir_with_content(open_with_content)
+            .yield_close_dir_with_summary(open_with_content)
+            .track_siblings(track_siblings)
+            .build();
+        let mut dirs = vec![];
+        for pos in it {
+            match pos {
+                Position::OpenDir(name) | Position::OpenDirWithContent(name, _) => dirs.push(name.into_value()),
+                Position::CloseDir(name) | Position::CloseDirWithSummary(name, _) => dirs.push(name.into_value()),
+                _ => {}
+            }
+        }
+        assert_eq!(6, dirs.len());
+        assert_eq!(5, calls.load(Ordering::SeqCst), "contents_first: {}, track_siblings: {}", contents_first, track_siblings);
+    }
+}
+
+#[test]
+fn error_policy_skip() {
+    let dir = Dir::tmp();
+    dir.touch_all(&["a", "c"]);
+    dir.symlink_file("nowhere", "b");
+
+    let wd = WalkDir::new(dir.path())
+        .follow_links(true)
+        .error_policy(|err, _| match err.io_error().map(|err| err.kind()) {
+            Some(std::io::ErrorKind::NotFound) => ErrorAction::Skip,
+            _ => ErrorAction::Yield,
+        });
+    let r = dir.run_recursive(wd.into_classic());
+    r.assert_no_errors();
+
+    let expected = vec![dir.path().to_path_buf(), dir.join("a"), dir.join("c")];
+    assert_eq!(expected, r.sorted_paths());
+}
+
+#[test]
+fn error_policy_abort() {
+    let dir = Dir::tmp();
+    dir.touch_all(&["a", "c"]);
+    dir.symlink_file("nowhere", "b");
+
+    let wd = WalkDir::new(dir.path())
+        .follow_links(true)
+        .sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()))
+        .error_policy(|_, _| ErrorAction::Abort);
+    let r = dir.run_recursive(wd.into_classic());
+
+    assert_eq!(1, r.errs().len());
+    assert_eq!(Some(&*dir.join("b")), r.errs()[0].path());
+    // Errors are sorted before entries, so nothing is yielded after the root
+    let expected = vec![dir.path().to_path_buf()];
+    assert_eq!(expected, r.paths());
+}
+
+#[test]
+fn error_policy_retry() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let dir = Dir::tmp();
+    let root = dir.join("later");
+    let attempts = Arc::new(AtomicUsize::new(0));
+
+    let wd = {
+        let root = root.clone();
+        let attempts = attempts.clone();
+        WalkDir::new(&root).error_policy(move |_, attempt| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt < 2 {
+                if attempt == 1 {
+                    fs::create_dir(&root).unwrap();
+                }
+                ErrorAction::Retry(std::time::Duration::from_millis(1))
+            } else {
+                ErrorAction::Yield
+            }
+        })
+    };
+    let r = dir.run_recursive(wd.into_classic());
+    r.assert_no_errors();
+
+    assert_eq!(2, attempts.load(Ordering::SeqCst));
+    assert_eq!(vec![root.clone()], r.paths());
+
+    // Errors found while reading the content of a dir are not retried
+    dir.symlink_file("nowhere", "later/b");
+    let calls = Arc::new(std::sync::Mutex::new(vec![]));
+    let wd = {
+        let calls = calls.clone();
+        WalkDir::new(&root).follow_links(true).error_policy(move |err, attempt| {
+            calls.lock().unwrap().push((err.operation(), attempt));
+            ErrorAction::Retry(std::time::Duration::from_secs(60))
+        })
+    };
+    let r = dir.run_recursive(wd.into_classic());
+    assert_eq!(1, r.errs().len());
+    assert_eq!(Some(&*dir.join("later/b")), r.errs()[0].path());
+    assert_eq!(vec![(r.errs()[0].operation(), 0)], *calls.lock().unwrap());
+    assert_ne!(Operation::ReadDir, r.errs()[0].operation());
+}
+
+#[test]
+fn error_kind_and_operation() {
+    let dir = Dir::tmp();
+    dir.symlink_file("missing", "a");
+
+    let wd = WalkDir::new(dir.join("nope"));
+    let r = dir.run_recursive(wd.into_classic());
+    let errs = r.errs();
+    assert_eq!(1, errs.len());
+    assert_eq!(ErrorKind::NotFound, errs[0].kind());
+    assert_eq!(Operation::Metadata, errs[0].operation());
+    assert_eq!(Some(&*dir.join("nope")), errs[0].path());
+
+    let wd = WalkDir::new(dir.path()).follow_links(true);
+    let r = dir.run_recursive(wd.into_classic());
+    let errs = r.errs();
+    assert_eq!(1, errs.len());
+    assert_eq!(ErrorKind::NotFound, errs[0].kind());
+    assert_eq!(Operation::FollowLink, errs[0].operation());
+    assert_eq!(Some(&*dir.join("a")), errs[0].path());
+    assert!(errs[0].to_string().contains("follow symlink"));
+}
+
+#[test]
+fn collect_errors() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b/c");
+    dir.touch("b/file");
+    dir.symlink_file("missing", "a/x");
+    dir.symlink_file("missing", "a/y");
+    dir.symlink_file("missing", "b/c/z");
+
+    let mut it = WalkDir::new(dir.path()).follow_links(true).into_iter().collect_errors();
+    let mut paths = vec![];
+    for pos in &mut it {
+        match pos {
+            Position::Entry(ent) => paths.push(ent.path().to_path_buf()),
+            Position::Error(err) => panic!("unexpected error: {}", err),
+            _ => {}
+        }
+    }
+    paths.sort();
+    assert_eq!(
+        vec![dir.path().to_path_buf(), dir.join("a"), dir.join("b"), dir.join("b/c"), dir.join("b/file")],
+        paths
+    );
+
+    let report = it.into_report();
+    assert_eq!(3, report.len());
+
+    let by_kind = report.by_kind();
+    assert_eq!(vec![ErrorKind::NotFound], by_kind.keys().copied().collect::<Vec<_>>());
+
+    let by_subtree: Vec<(PathBuf, usize)> = report
+        .by_subtree()
+        .into_iter()
+        .map(|(path, errs)| (path.unwrap().to_path_buf(), errs.len()))
+        .collect();
+    assert_eq!(vec![(dir.join("a"), 2), (dir.join("b"), 1)], by_subtree);
+
+    let summary = report.to_string();
+    assert!(summary.starts_with("3 error(s)\n"));
+    assert!(summary.contains("not found: 3 (follow symlink: 3)"));
+    assert_eq!(4, report.details().to_string().lines().count());
+}
+
+#[test]
+#[cfg(unix)]
+fn collect_errors_nested_subtree() {
+    use std::os::unix::fs::PermissionsExt;
+    use crate::error::into_path_err;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+    dir.touch("a/b/c/file");
+    fs::set_permissions(dir.join("a/b"), fs::Permissions::from_mode(0o000)).unwrap();
+    let readable = fs::read_dir(dir.join("a/b")).is_ok();
+
+    let mut it = WalkDir::new(dir.path()).into_iter().collect_errors();
+    for _ in &mut it {}
+    let mut report = it.into_report();
+    fs::set_permissions(dir.join("a/b"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    // Not reproducible with privileges which ignore permissions
+    if readable {
+        assert!(report.is_empty());
+    } else {
+        assert_eq!(1, report.len());
+        assert_eq!(Some(&*dir.join("a/b")), report.errors()[0].path());
+    }
+
+    // A failure to read the next entry of a/b/c, reported at the depth of its entries
+    let err = std::io::Error::new(std::io::ErrorKind::InvalidData, "next entry");
+    report.push(Error::from_inner(into_path_err::<DefaultDirEntry, _>(dir.join("a/b/c"), Operation::NextEntry, err), 4));
+
+    let subtrees: Vec<PathBuf> = report.by_subtree().keys().map(|path| path.unwrap().to_path_buf()).collect();
+    assert_eq!(vec![dir.join("a")], subtrees);
+}
+
+#[test]
+fn walk_profile() {
+    let dir = Dir::tmp();
+    dir.mkdirp("src/sub");
+    dir.mkdirp("target/debug");
+    dir.mkdirp(".git");
+    dir.touch("src/b.rs");
+    dir.touch("src/a.rs");
+    dir.touch("src/sub/c.rs");
+    dir.touch("src/notes.txt");
+    dir.touch("target/debug/x.rs");
+    dir.touch("Cargo.toml");
+
+    let mut profile = WalkProfile {
+        sort: Some(SortBy::Name),
+        include: vec!["*.rs".to_string(), "/Cargo.toml".to_string()],
+        exclude: vec!["target".to_string(), ".*".to_string(), "src/**/c.rs".to_string()],
+        ..WalkProfile::default()
+    };
+
+    let paths: Vec<PathBuf> = profile
+        .walk(dir.path())
+        .into_classic()
+        .map(|r| r.unwrap().path().to_path_buf())
+        .collect();
+    assert_eq!(
+        vec![
+            dir.path().to_path_buf(),
+            dir.join("Cargo.toml"),
+            dir.join("src"),
+            dir.join("src/a.rs"),
+            dir.join("src/b.rs"),
+            dir.join("src/sub"),
+        ],
+        paths
+    );
+
+    profile.options.max_depth = 1;
+    profile.sort = Some(SortBy::NameDesc);
+    profile.include.clear();
+    profile.exclude.clear();
+    let paths: Vec<PathBuf> = profile
+        .walk(dir.path())
+        .into_classic()
+        .map(|r| r.unwrap().path().to_path_buf())
+        .collect();
+    assert_eq!(
+        vec![dir.path().to_path_buf(), dir.join("target"), dir.join("src"), dir.join("Cargo.toml"), dir.join(".git")],
+        paths
+    );
+}
+
 #[test]
 fn skip_current_dir() {
     let dir = Dir::tmp();
@@ -938,7 +1597,7 @@ fn skip_current_dir() {
     while let Some(result) = it.next() {
         let ent = match result {
             Position::Entry(ent) => ent,
-            Position::OpenDir(_) | Position::CloseDir => continue,
+            Position::OpenDir(_) | Position::CloseDir(_) => continue,
             _ => panic!(),
         };
         paths.push(ent.path().to_path_buf());
@@ -957,6 +1616,62 @@ fn skip_current_dir() {
     assert_eq!(expected, paths);
 }
 
+#[test]
+fn skip_current_dir_positions() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/y");
+    dir.mkdirp("b");
+    dir.touch_all(&["a/x", "a/y/z", "b/c"]);
+
+    type Pos = Position<DirEntry, Vec<DirEntry>, Error>;
+
+    // Walks and calls skip_current_dir once, right after `skip_at` returns true
+    let walk = |contents_first: bool, skip_at: &dyn Fn(&Pos) -> bool| {
+        let mut it = WalkDir::new(dir.path())
+            .contents_first(contents_first)
+            .sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()))
+            .build();
+        let mut paths = vec![];
+        let mut skipped = false;
+        while let Some(pos) = it.next() {
+            let skip = !skipped && skip_at(&pos);
+            match pos {
+                Position::Entry(dent) => paths.push(dent.into_path()),
+                Position::Error(err) => panic!("{}", err),
+                _ => {}
+            }
+            if skip {
+                it.skip_current_dir();
+                skipped = true;
+            }
+        }
+        paths
+    };
+    let is_entry = |name: &'static str| {
+        move |pos: &Pos| matches!(pos, Position::Entry(dent) if dent.file_name() == name)
+    };
+    let paths = |names: &[&str]| names.iter().map(|name| dir.join(name)).collect::<Vec<_>>();
+    let all = paths(&["", "a", "a/x", "a/y", "a/y/z", "b", "b/c"]);
+
+    // After a dir entry: only its content is skipped
+    assert_eq!(paths(&["", "a", "b", "b/c"]), walk(false, &is_entry("a")));
+    // After OpenDir: the content of the opened dir is skipped
+    let open_a = |pos: &Pos| matches!(pos, Position::OpenDir(dent) if dent.file_name() == "a");
+    assert_eq!(paths(&["", "a", "b", "b/c"]), walk(false, &open_a));
+    // After a file: the rest of its dir is skipped
+    assert_eq!(paths(&["", "a", "a/x", "b", "b/c"]), walk(false, &is_entry("x")));
+    // After CloseDir: nothing is skipped
+    let close_a = |pos: &Pos| matches!(pos, Position::CloseDir(dent) if dent.file_name() == "a");
+    assert_eq!(all, walk(false, &close_a));
+
+    // In contents_first mode a dir entry is yielded after its content, so nothing is skipped
+    let mut expected = all.clone();
+    expected.sort();
+    let mut got = walk(true, &is_entry("a"));
+    got.sort();
+    assert_eq!(expected, got);
+}
+
 #[test]
 fn filter_entry() {
     let dir = Dir::tmp();
@@ -976,6 +1691,35 @@ fn filter_entry() {
     assert_eq!(expected, r.sorted_paths());
 }
 
+#[test]
+fn filter_entry_keeps_siblings() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/a/x");
+    dir.mkdirp("foo/b/y");
+    dir.touch_all(&["foo/c"]);
+
+    for &contents_first in &[false, true] {
+        let wd = WalkDir::new(dir.path())
+            .contents_first(contents_first)
+            .into_iter()
+            .filter_entry(|dent| dent.file_name() != "a");
+        let r = dir.run_recursive(wd.into_classic());
+        r.assert_no_errors();
+
+        let mut expected = vec![
+            dir.path().to_path_buf(),
+            dir.join("foo"),
+            dir.join("foo").join("b"),
+            dir.join("foo").join("b").join("y"),
+            dir.join("foo").join("c"),
+        ];
+        if contents_first {
+            expected.insert(2, dir.join("foo").join("a").join("x"));
+        }
+        assert_eq!(expected, r.sorted_paths());
+    }
+}
+
 #[test]
 fn sort() {
     let dir = Dir::tmp();
@@ -1038,7 +1782,7 @@ fn same_file_system() {
 
     // First, do a sanity check that things work without following symlinks.
     let wd = WalkDir::new(dir.path());
-    let r = dir.run_recursive(wd);
+    let r = dir.run_recursive(wd.into_classic());
     r.assert_no_errors();
 
     let expected = vec![dir.path().to_path_buf(), dir.join("a"), dir.join("sys-link")];
@@ -1046,11 +1790,41 @@ fn same_file_system() {
 
     // ... now follow symlinks and ensure we don't descend into /sys.
     let wd = WalkDir::new(dir.path()).same_file_system(true).follow_links(true);
-    let r = dir.run_recursive(wd);
+    let r = dir.run_recursive(wd.into_classic());
     r.assert_no_errors();
 
     let expected = vec![dir.path().to_path_buf(), dir.join("a"), dir.join("sys-link")];
     assert_eq!(expected, r.sorted_paths());
+
+    // ... and it is still a dir (for content filters, summaries etc).
+    let wd = WalkDir::new(dir.path())
+        .same_file_system(true)
+        .follow_links(true)
+        .content_filter(ContentFilter::DirsOnly)
+        .yield_close_dir_with_summary(true);
+    let mut paths = vec![];
+    for pos in wd {
+        match pos {
+            Position::Entry(dent) => paths.push(dent.into_path()),
+            Position::CloseDirWithSummary(dent, summary) if dent.depth() == 0 => {
+                assert_eq!(DirSummary { files: 1, dirs: 1, errors: 0, skipped: false }, summary);
+            }
+            Position::Error(err) => panic!("{}", err),
+            _ => {}
+        }
+    }
+    paths.sort();
+    assert_eq!(vec![dir.path().to_path_buf(), dir.join("sys-link")], paths);
+
+    // The lending walker classifies it the same way
+    let wd = WalkDir::new(dir.path()).same_file_system(true).follow_links(true).content_filter(ContentFilter::DirsOnly);
+    let mut walk = wd.into_lending().unwrap();
+    let mut paths = vec![];
+    while let Some(r) = walk.next_entry() {
+        paths.push(r.unwrap().path().to_path_buf());
+    }
+    paths.sort();
+    assert_eq!(vec![dir.path().to_path_buf(), dir.join("sys-link")], paths);
 }
 
 // Tests that skip_current_dir doesn't destroy internal invariants.
@@ -1072,3 +1846,1016 @@ fn regression_skip_current_dir() {
     wd.skip_current_dir();
     wd.next();
 }
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_roundtrip() {
+    use crate::walk::WalkDirConfig;
+    use crate::DirEntrySnapshot;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.touch("a/file");
+    dir.symlink_file("missing", "broken");
+
+    let wd = WalkDir::new(dir.path()).follow_links(true).content_order(ContentOrder::DirsFirst);
+    let opts = serde_json::to_string(&wd.config()).unwrap();
+    let opts: WalkDirConfig = serde_json::from_str(&opts).unwrap();
+    assert_eq!(wd.config(), opts);
+
+    let partial: WalkDirConfig = serde_json::from_str(r#"{"follow_links":true}"#).unwrap();
+    assert!(partial.follow_links);
+    assert_eq!(WalkDirConfig::VERSION, partial.version);
+    assert_eq!(WalkDirConfig::default().max_open, partial.max_open);
+    assert!(serde_json::from_str::<WalkDirConfig>(r#"{"version":2}"#).is_err());
+    assert!(serde_json::from_str::<WalkDirConfig>(r#"{"follow_link":true}"#).is_err());
+
+    for pos in WalkDir::new(dir.path()).with_config(&opts) {
+        let json = serde_json::to_string(&pos).unwrap();
+        match pos {
+            Position::Entry(ent) => {
+                let pos: Position<DirEntrySnapshot, (), Error> = serde_json::from_str(&json).unwrap();
+                let snapshot = match pos {
+                    Position::Entry(snapshot) => snapshot,
+                    _ => panic!("expected entry"),
+                };
+                assert_eq!(ent.path(), snapshot.path);
+                assert_eq!(ent.depth(), snapshot.depth);
+                assert_eq!(ent.file_type().is_dir(), snapshot.is_dir);
+            }
+            Position::Error(err) => {
+                let pos: Position<DirEntrySnapshot, (), Error> = serde_json::from_str(&json).unwrap();
+                let restored = match pos {
+                    Position::Error(err) => err,
+                    _ => panic!("expected error"),
+                };
+                assert_eq!(err.path(), restored.path());
+                assert_eq!(err.depth(), restored.depth());
+                assert_eq!(err.kind(), restored.kind());
+                assert_eq!(err.operation(), restored.operation());
+                assert_eq!(ErrorKind::NotFound, restored.kind());
+            }
+            _ => {}
+        }
+    }
+
+    // Kinds which std::io::ErrorKind doesn't have survive without an OS error code
+    for &kind in &[ErrorKind::NotADirectory, ErrorKind::TooManyOpenFiles, ErrorKind::Loop] {
+        let json = format!(
+            r#"{{"depth":1,"kind":{},"operation":"ReadDir","path":"x","loop_ancestor":null,"message":"m","raw_os_error":null}}"#,
+            serde_json::to_string(&kind).unwrap(),
+        );
+        let restored: Error = serde_json::from_str(&json).unwrap();
+        assert_eq!(kind, restored.kind());
+    }
+}
+
+#[test]
+fn walk_profile_glob_match() {
+    use crate::profile::glob_match;
+
+    assert!(glob_match("*.rs", "main.rs"));
+    assert!(!glob_match("*.rs", "src/main.rs"));
+    assert!(glob_match("src/*.rs", "src/main.rs"));
+    assert!(glob_match("**/*.rs", "main.rs"));
+    assert!(glob_match("**/*.rs", "src/a/main.rs"));
+    assert!(glob_match("src/**", "src/a/b"));
+    assert!(glob_match("a/**/b", "a/b"));
+    assert!(glob_match("a/**/b", "a/x/y/b"));
+    assert!(!glob_match("a/**/b", "a/xb"));
+    assert!(glob_match("?é*", "xé"));
+    assert!(!glob_match("?", "é/"));
+    assert!(!glob_match("a*b", "a/b"));
+    assert!(glob_match("a**b", "a/b"));
+
+    // Would take exponential time with naive backtracking
+    let name = "a".repeat(64);
+    assert!(!glob_match("*a*a*a*a*a*a*a*a*a*a*a*a*b", &name));
+    assert!(!glob_match("**a**a**a**a**a**a**a**a**a**a**b", &name));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn walk_profile_from_config() {
+    let toml_profile: WalkProfile = toml::from_str(
+        r#"
+            sort = "name_desc"
+            exclude = ["target"]
+
+            [options]
+            max_depth = 3
+            follow_links = true
+            content_filter = "files_only"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(3, toml_profile.options.max_depth);
+    assert!(toml_profile.options.follow_links);
+    assert_eq!(ContentFilter::FilesOnly, toml_profile.options.content_filter);
+    assert_eq!(Some(SortBy::NameDesc), toml_profile.sort);
+    assert_eq!(vec!["target".to_string()], toml_profile.exclude);
+    assert_eq!(Some(10), toml_profile.options.max_open);
+
+    let json = serde_json::to_string(&toml_profile).unwrap();
+    let json_profile: WalkProfile = serde_json::from_str(&json).unwrap();
+    assert_eq!(toml_profile, json_profile);
+
+    assert!(toml::from_str::<WalkProfile>("exclud = [\"target\"]").is_err());
+    assert!(toml::from_str::<WalkProfile>("sort = \"Name\"").is_err());
+}
+
+#[test]
+fn tree_diff() {
+    use crate::diff::{DiffEvent, Modification, TreeDiff};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("left/both/deep");
+    dir.mkdirp("left/gone/sub");
+    dir.mkdirp("left/kind");
+    dir.mkdirp("right/both/deep");
+    dir.mkdirp("right/new");
+    dir.touch_all(&["left/both/deep/same", "right/both/deep/same", "left/gone/sub/f", "right/kind"]);
+    fs::write(dir.join("left/both/size"), "1").unwrap();
+    fs::write(dir.join("right/both/size"), "22").unwrap();
+
+    let events: Vec<(String, PathBuf)> = TreeDiff::new(WalkDir::new(dir.join("left")), WalkDir::new(dir.join("right")))
+        .compare_mtime(false)
+        .map(|ev| {
+            let ev = ev.unwrap();
+            let path = ev.relative_path().to_path_buf();
+            let name = match ev {
+                DiffEvent::Added(_) => "added".to_string(),
+                DiffEvent::Removed(_) => "removed".to_string(),
+                DiffEvent::TypeChanged(_, _) => "type".to_string(),
+                DiffEvent::Modified(_, _, m) => {
+                    assert_eq!(Modification { size: true, mtime: false, mode: false }, m);
+                    "modified".to_string()
+                }
+            };
+            (name, path)
+        })
+        .collect();
+
+    assert_eq!(
+        vec![
+            ("modified".to_string(), PathBuf::from("both/size")),
+            ("removed".to_string(), PathBuf::from("gone")),
+            ("type".to_string(), PathBuf::from("kind")),
+            ("added".to_string(), PathBuf::from("new")),
+        ],
+        events
+    );
+
+    let same: Vec<_> = crate::diff::diff(dir.join("left"), dir.join("left")).collect();
+    assert!(same.is_empty());
+}
+
+#[test]
+fn manifest_roundtrip_and_verify() {
+    use crate::manifest::{ChangedFields, Difference, EntryType, Manifest, ManifestWriter};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("root/sub");
+    fs::write(dir.join("root/a\tb"), "hello").unwrap();
+    fs::write(dir.join("root/sub/c"), "x").unwrap();
+    dir.symlink_file("sub/c", "root/link");
+
+    let mut writer = ManifestWriter::new().hash_with(|path| Ok(fs::read(path)?.len().to_string()));
+    let mut text = vec![];
+    assert!(writer.write(dir.join("root"), &mut text).unwrap().is_empty());
+
+    let manifest = Manifest::read(&text[..]).unwrap();
+    assert_eq!(writer.manifest(dir.join("root")).unwrap().0, manifest);
+    let paths: Vec<_> = manifest.entries.iter().map(|e| (e.path.clone(), e.ty)).collect();
+    assert_eq!(
+        vec![
+            (PathBuf::from("a\tb"), EntryType::File),
+            (PathBuf::from("link"), EntryType::Symlink),
+            (PathBuf::from("sub"), EntryType::Dir),
+            (PathBuf::from("sub/c"), EntryType::File),
+        ],
+        paths
+    );
+    assert_eq!(Some(dir.join("sub/c")), manifest.entries[1].target);
+    assert_eq!(Some("5".to_string()), manifest.entries[0].hash);
+
+    let mut written = vec![];
+    manifest.write(&mut written).unwrap();
+    assert_eq!(text, written);
+
+    assert!(writer.verify(dir.join("root"), &text[..]).unwrap().0.is_empty());
+
+    fs::write(dir.join("root/sub/c"), "xyz").unwrap();
+    fs::remove_file(dir.join("root/a\tb")).unwrap();
+    dir.touch("root/new");
+
+    let mut writer = writer.compare_mtime(false);
+    let (differences, errors) = writer.verify(dir.join("root"), &text[..]).unwrap();
+    assert!(errors.is_empty());
+    let summary: Vec<String> = differences.iter().map(|d| d.to_string()).collect();
+    assert_eq!(vec!["missing: a\\tb", "extra: new", "changed: sub/c (size, hash)"], summary);
+    match differences[2] {
+        Difference::Changed(ref expected, ref actual, fields) => {
+            assert_eq!(1, expected.size);
+            assert_eq!(3, actual.size);
+            assert_eq!(ChangedFields { size: true, hash: true, ..ChangedFields::default() }, fields);
+        }
+        _ => panic!("expected change"),
+    }
+
+    // Errors of the walk are returned rather than aborting it
+    let (differences, errors) = writer.verify(dir.join("gone"), &text[..]).unwrap();
+    assert_eq!(1, errors.len());
+    assert_eq!(4, differences.len());
+
+    // ... and so are errors of hashing a file: the other entries are still written
+    let mut writer = ManifestWriter::new().hash_with(|path| {
+        if path.ends_with("new") {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "unreadable"))
+        } else {
+            Ok(String::new())
+        }
+    });
+    let mut text = vec![];
+    let errors = writer.write(dir.join("root"), &mut text).unwrap();
+    assert_eq!(1, errors.len());
+    let err = &errors.errors()[0];
+    assert_eq!(Some(dir.join("root/new").as_path()), err.path());
+    assert_eq!(Operation::ReadFile, err.operation());
+    assert_eq!(3, Manifest::read(&text[..]).unwrap().entries.len());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = std::ffi::OsStr::from_bytes(b"bad\xff\xe2\x82name\xe2\x82\xac");
+        dir.mkdirp("utf8");
+        fs::write(dir.join("utf8").join(name), "").unwrap();
+        let mut text = vec![];
+        ManifestWriter::new().write(dir.join("utf8"), &mut text).unwrap();
+        assert!(String::from_utf8_lossy(&text).contains("\tbad\\xff\\xe2\\x82name\u{20ac}\n"));
+        assert_eq!(std::path::Path::new(name), Manifest::read(&text[..]).unwrap().entries[0].path);
+    }
+}
+
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+#[test]
+fn hashing_content_processor() {
+    use crate::cp::{HashAlgorithm, HashingContentProcessor};
+    use crate::WalkDirBuilder;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("sub");
+    fs::write(dir.join("sub/abc"), "abc").unwrap();
+    fs::write(dir.join("big"), vec![0u8; 100]).unwrap();
+
+    let mut algorithms = vec![];
+    #[cfg(feature = "sha2")]
+    algorithms.push((HashAlgorithm::Sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"));
+    #[cfg(feature = "blake3")]
+    algorithms.push((HashAlgorithm::Blake3, "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"));
+
+    for (algorithm, expected) in algorithms {
+        for &threads in [0, 2].iter() {
+            let cp = HashingContentProcessor::new(algorithm).max_size(Some(10)).threads(threads);
+
+            // Digests are taken after the walk (and its hashing threads) is over
+            let entries: Vec<_> = WalkDirBuilder::<DefaultDirEntry, HashingContentProcessor>::new(dir.path())
+                .content_processor(cp)
+                .into_classic()
+                .map(|e| e.unwrap())
+                .collect();
+            let mut digests: Vec<(PathBuf, Option<String>)> = entries
+                .iter()
+                .map(|e| {
+                    let digest = e.digest().map(|d| d.as_ref().unwrap().to_string());
+                    (e.path().to_path_buf(), digest)
+                })
+                .collect();
+            digests.sort();
+
+            assert_eq!(
+                vec![
+                    (dir.path().to_path_buf(), None),
+                    (dir.join("big"), None),
+                    (dir.join("sub"), None),
+                    (dir.join("sub/abc"), Some(expected.to_string())),
+                ],
+                digests
+            );
+        }
+    }
+}
+
+#[test]
+fn find_duplicates() {
+    use crate::dupes::DuplicateFinder;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("one/sub");
+    dir.mkdirp("two");
+    fs::write(dir.join("one/a"), "hello").unwrap();
+    fs::write(dir.join("two/b"), "hello").unwrap();
+    fs::hard_link(dir.join("one/a"), dir.join("one/sub/a-link")).unwrap();
+    fs::write(dir.join("one/other"), "world").unwrap();
+    let mut big = vec![7u8; 5000];
+    fs::write(dir.join("one/big1"), &big).unwrap();
+    fs::write(dir.join("two/big2"), &big).unwrap();
+    big[4999] = 8;
+    fs::write(dir.join("two/big3"), &big).unwrap();
+    dir.touch_all(&["one/empty1", "two/empty2"]);
+
+    let dupes = DuplicateFinder::new().root(dir.join("one")).root(dir.join("two")).partial_size(1024).find();
+    assert!(dupes.walk_errors.is_empty());
+    assert!(dupes.read_errors.is_empty());
+
+    let groups: Vec<(u64, Vec<Vec<PathBuf>>)> = dupes.groups.iter().map(|g| (g.size, g.files.clone())).collect();
+    assert_eq!(
+        vec![
+            (5000, vec![vec![dir.join("one/big1")], vec![dir.join("two/big2")]]),
+            (5, vec![vec![dir.join("one/a"), dir.join("one/sub/a-link")], vec![dir.join("two/b")]]),
+        ],
+        groups
+    );
+    assert_eq!(5005, dupes.reclaimable());
+}
+
+#[test]
+fn disk_usage() {
+    use crate::du::{self, DiskUsageIter};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("c");
+    fs::write(dir.join("a/f1"), vec![1u8; 100]).unwrap();
+    fs::write(dir.join("a/b/f2"), vec![1u8; 1000]).unwrap();
+    fs::write(dir.join("c/f3"), vec![1u8; 10]).unwrap();
+    fs::hard_link(dir.join("a/b/f2"), dir.join("c/f2-link")).unwrap();
+
+    let dir_size = fs::metadata(dir.path()).unwrap().len();
+    // sorted, so the hard link is counted in "a/b" (the first one seen)
+    let sorted_walk = || WalkDir::new(dir.path()).sort_by(|a, b, _| a.0.path().cmp(b.0.path()));
+    let usages: Vec<(PathBuf, u64, u64, u64)> = DiskUsageIter::new(sorted_walk())
+        .map(|r| {
+            let (dent, usage) = r.unwrap();
+            (dent.path().to_path_buf(), usage.apparent - usage.dirs * dir_size, usage.files, usage.dirs)
+        })
+        .collect();
+
+    let mut sorted = usages.clone();
+    sorted.sort();
+    assert_eq!(
+        vec![
+            (dir.path().to_path_buf(), 1110, 3, 4),
+            (dir.join("a"), 1100, 2, 2),
+            (dir.join("a/b"), 1000, 1, 1),
+            (dir.join("c"), 10, 1, 1),
+        ],
+        sorted
+    );
+    // the root is closed last
+    assert_eq!(dir.path(), usages.last().unwrap().0);
+
+    let top = du::du(dir.path()).top(2);
+    assert!(top.errors.is_empty());
+    assert_eq!(2, top.dirs.len());
+    assert_eq!(dir.path(), top.dirs[0].0);
+    assert_eq!(top.total, top.dirs[0].1);
+    assert_eq!(3, top.total.files);
+    assert!(top.dirs[0].1.allocated >= top.dirs[1].1.allocated);
+}
+
+#[test]
+#[cfg(unix)]
+fn incremental_walk() {
+    use std::time::Duration;
+    use crate::WalkDirBuilder;
+    use crate::fs::{CacheStats, DirCache, IncrementalDirEntry};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("c");
+    dir.touch_all(&["a/f1", "a/b/f2", "c/f3"]);
+    dir.symlink_dir("a", "c/link");
+
+    let full = || dir.run_recursive(WalkDir::new(dir.path()).into_classic()).paths();
+    let types = || -> Vec<(PathBuf, bool, bool)> {
+        let r = dir.run_recursive(WalkDir::new(dir.path()).into_classic());
+        r.ents().iter().map(|e| (e.path().to_path_buf(), e.file_type().is_dir(), e.file_type().is_symlink())).collect()
+    };
+    let incremental_types = std::cell::RefCell::new(vec![]);
+    let incremental = |cache: DirCache| {
+        let mut iter = WalkDirBuilder::<IncrementalDirEntry, DirEntryContentProcessor>::with_context(dir.path(), cache, DirEntryContentProcessor::default()).build();
+        let mut paths = vec![];
+        incremental_types.borrow_mut().clear();
+        for pos in &mut iter {
+            match pos {
+                Position::Entry(dent) => {
+                    let ft = dent.file_type();
+                    incremental_types.borrow_mut().push((dent.path().to_path_buf(), ft.is_dir(), ft.is_symlink()));
+                    paths.push(dent.into_path());
+                }
+                Position::Error(err) => panic!("{}", err),
+                _ => {}
+            }
+        }
+        let mut cache = iter.into_context();
+        let stats = cache.stats();
+        cache.retain_visited();
+        (paths, stats, cache)
+    };
+
+    // listings are trusted only when the dirs were not changed just before they were read
+    std::thread::sleep(Duration::from_millis(50));
+    let (paths, stats, cache) = incremental(DirCache::new().racy_window(Duration::from_millis(10)));
+    assert_eq!(full(), paths);
+    assert_eq!(CacheStats { reused: 0, read: 4 }, stats);
+    assert_eq!(4, cache.len());
+
+    let (paths, stats, cache) = incremental(cache);
+    assert_eq!(full(), paths);
+    assert_eq!(CacheStats { reused: 4, read: 0 }, stats);
+    // file types are replayed from the cache too
+    assert_eq!(types(), *incremental_types.borrow());
+
+    dir.touch("c/f4");
+    fs::remove_file(dir.join("a/b/f2")).unwrap();
+    fs::remove_dir(dir.join("a/b")).unwrap();
+    let (paths, stats, cache) = incremental(cache);
+    assert_eq!(full(), paths);
+    assert!(paths.contains(&dir.join("c/f4")));
+    assert_eq!(CacheStats { reused: 1, read: 2 }, stats);
+    assert_eq!(3, cache.len());
+}
+
+#[test]
+fn filter_expr() {
+    use crate::expr::Expr;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("src/.git");
+    dir.mkdirp("target");
+    dir.touch_all(&["src/.git/config", "src/a.rs", "src/a.tmp", "target/b.rs"]);
+    fs::write(dir.join("src/big.rs"), vec![0u8; 2048]).unwrap();
+
+    let paths = |expr: &str| -> Vec<PathBuf> {
+        let expr: Expr = expr.parse().unwrap();
+        let r = dir.run_recursive(WalkDir::new(dir.path()).into_classic().filter(|e| e.as_ref().map_or(true, |e| expr.is_match(e))));
+        r.assert_no_errors();
+        r.sorted_paths()
+    };
+
+    assert_eq!(vec![dir.join("src/big.rs")], paths("type f and size > 1k and mtime < 1d"));
+    assert_eq!(vec![dir.join("src/a.rs"), dir.join("src/big.rs")], paths("type f and path 'src/*' not name \"*.tmp\""));
+    assert_eq!(vec![dir.join("src/a.tmp"), dir.join("target")], paths("name *.tmp or (type d and depth = 1 and !name s*)"));
+    assert!(paths("mtime > 1d").is_empty());
+
+    let prune: Expr = "name .git or name target".parse().unwrap();
+    let r = dir.run_recursive(WalkDir::new(dir.path()).into_iter().filter_entry(prune.prune_filter()).into_classic());
+    assert_eq!(
+        vec![dir.path().to_path_buf(), dir.join("src"), dir.join("src/a.rs"), dir.join("src/a.tmp"), dir.join("src/big.rs")],
+        r.sorted_paths()
+    );
+
+    let only: Expr = "type d or name '*.rs'".parse().unwrap();
+    let r = dir.run_recursive(WalkDir::new(dir.path()).into_iter().filter_entry(only.entry_filter()).into_classic());
+    assert_eq!(7, r.ents().len());
+
+    for &(bad, pos) in &[("type x", 5), ("size > 10Q", 7), ("name", 4), ("(type f", 7), ("type f )", 7), ("foo", 0), ("name 'x", 5)] {
+        assert_eq!(pos, Expr::parse(bad).unwrap_err().pos(), "{}", bad);
+    }
+
+    let deep = format!("{}type f{}", "(".repeat(100_000), ")".repeat(100_000));
+    assert_eq!(64, Expr::parse(&deep).unwrap_err().pos());
+    assert_eq!(256, Expr::parse(&"not ".repeat(100_000)).unwrap_err().pos());
+    let long = vec!["type f"; 100_000].join(" or ");
+    assert!(Expr::parse(&long).is_ok());
+}
+
+#[test]
+fn content_processor_combinators() {
+    use crate::WalkDirBuilder;
+    use crate::cp::{self, FsEntryMut};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.touch_all(&["a/f1", "a/b/f2"]);
+    fs::write(dir.join("a/big"), vec![0u8; 100]).unwrap();
+
+    let sorted = |mut v: Vec<PathBuf>| {
+        v.sort();
+        v
+    };
+
+    let sizes = cp::map(DirEntryContentProcessor::default(), |dent: DirEntry| (dent.path().to_path_buf(), dent.metadata().unwrap().len()));
+    let items: Vec<_> = WalkDirBuilder::with_context(dir.path(), (), sizes)
+        .track_siblings(true)
+        .into_classic()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(6, items.len());
+    assert!(items.iter().all(|item| item.sibling_info().is_some()));
+    let big = items.iter().find(|item| item.0 == dir.join("a/big")).unwrap();
+    assert_eq!(100, big.1);
+    assert!(!big.is_dir());
+
+    // dirs filtered out by filter_map are not descended into
+    let not_b = cp::filter_map(DirEntryContentProcessor::default(), |dent: DirEntry| {
+        if dent.file_name() == "b" { None } else { Some(dent.into_path()) }
+    });
+    let paths: Vec<PathBuf> = WalkDirBuilder::with_context(dir.path(), (), not_b).into_classic().map(|r| r.unwrap().into_value()).collect();
+    assert_eq!(vec![dir.path().to_path_buf(), dir.join("a"), dir.join("a/big"), dir.join("a/f1")], sorted(paths));
+
+    // ... in any order and at any depth
+    let not_b = || {
+        cp::filter_map(DirEntryContentProcessor::default(), |dent: DirEntry| {
+            if dent.file_name() == "b" { None } else { Some(dent.into_path()) }
+        })
+    };
+    let paths: Vec<PathBuf> = WalkDirBuilder::with_context(dir.path(), (), not_b())
+        .contents_first(true)
+        .track_siblings(true)
+        .into_classic()
+        .map(|r| r.unwrap().into_value())
+        .collect();
+    assert_eq!(&[dir.join("a"), dir.path().to_path_buf()], &paths[2..]);
+    assert_eq!(vec![dir.join("a/big"), dir.join("a/f1")], sorted(paths[..2].to_vec()));
+    let paths: Vec<PathBuf> = WalkDirBuilder::with_context(dir.path(), (), not_b())
+        .min_depth(2)
+        .track_siblings(true)
+        .into_classic()
+        .map(|r| r.unwrap().into_value())
+        .collect();
+    assert_eq!(vec![dir.join("a/big"), dir.join("a/f1")], sorted(paths));
+
+    let names = cp::from_fn(|fsdent: &mut FsEntryMut<DefaultDirEntry>, _ctx: &mut ()| {
+        assert_eq!(fsdent.depth() == 0, fsdent.fsdent().is_none());
+        Some((fsdent.depth(), fsdent.file_name()))
+    });
+    let pairs: Vec<_> = WalkDirBuilder::with_context(dir.path(), (), (names, DirEntryContentProcessor::default()))
+        .max_depth(1)
+        .into_classic()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(2, pairs.len());
+    for (name, dent) in &pairs {
+        assert_eq!(name.0, dent.depth());
+        assert_eq!(name.1, dent.file_name().to_os_string());
+        assert_eq!(name.is_dir(), dent.file_type().is_dir());
+    }
+}
+
+#[test]
+fn content_collection() {
+    use std::collections::{BTreeMap, HashMap};
+    use std::ffi::{OsStr, OsString};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("app/node_modules/dep");
+    dir.mkdirp("lib");
+    dir.touch_all(&["app/package.json", "app/node_modules/dep/package.json", "lib/index.js"]);
+
+    let wd = WalkDir::new(dir.path())
+        .yield_open_dir_with_content(true)
+        .collection::<BTreeMap<OsString, DirEntry>>();
+    let mut packages = vec![];
+    for pos in wd {
+        if let Position::OpenDirWithContent(dent, content) = pos {
+            if content.contains_key(OsStr::new("package.json")) {
+                packages.push(dent.path().to_path_buf());
+            }
+            for (name, dent) in &content {
+                assert_eq!(name, dent.file_name());
+            }
+        }
+    }
+    packages.sort();
+    assert_eq!(vec![dir.join("app"), dir.join("app/node_modules/dep")], packages);
+
+    let mut wd = WalkDir::new(dir.path()).collection::<HashMap<OsString, DirEntry>>().build();
+    // the root, then the first entry of the root dir
+    wd.next();
+    wd.next();
+    let content = wd.get_current_dir_content(ContentFilter::DirsOnly);
+    assert_eq!(2, content.len());
+    assert!(content[OsStr::new("app")].file_type().is_dir());
+}
+
+#[test]
+fn content_processor_parent_context() {
+    use std::ffi::OsString;
+    use crate::WalkDirBuilder;
+    use crate::cp::{ContentProcessor, ParentContext};
+    use crate::fs::FsRootDirEntry;
+    use crate::wd::Depth;
+
+    #[derive(Debug, Clone)]
+    struct Tagged {
+        path: PathBuf,
+        project: Option<OsString>,
+        is_dir: bool,
+        index: usize,
+    }
+
+    /// Tags entries with the name of the nearest `proj-*` ancestor and
+    /// doesn't descend into `target` dirs of projects
+    #[derive(Debug, Default)]
+    struct Projects;
+
+    impl ContentProcessor<DefaultDirEntry> for Projects {
+        type Item = Tagged;
+        type Collection = Vec<Tagged>;
+        type Scratch = Vec<OsString>;
+
+        fn allow_push(&self, fsdent: &DefaultDirEntry, parent: ParentContext<'_, Tagged, Vec<OsString>>) -> bool {
+            let in_project = parent.item().map_or(false, |item| item.project.is_some());
+            !(in_project && fsdent.file_name() == "target")
+        }
+
+        fn process_root_direntry(
+            &self,
+            fsdent: &mut <DefaultDirEntry as FsDirEntry>::RootDirEntry,
+            _follow_link: bool,
+            is_dir: bool,
+            _depth: Depth,
+            _ctx: &mut (),
+        ) -> Option<Tagged> {
+            Some(Tagged { path: fsdent.path().to_path_buf(), project: None, is_dir, index: 0 })
+        }
+
+        fn process_direntry(
+            &self,
+            fsdent: &mut DefaultDirEntry,
+            mut parent: ParentContext<'_, Tagged, Vec<OsString>>,
+            _follow_link: bool,
+            is_dir: bool,
+            _depth: Depth,
+            _ctx: &mut (),
+        ) -> Option<Tagged> {
+            let name = fsdent.file_name();
+            let project = match parent.item().and_then(|item| item.project.clone()) {
+                Some(project) => Some(project),
+                None if name.to_string_lossy().starts_with("proj-") => Some(name.clone()),
+                None => None,
+            };
+            // Each entry is processed once, so its index is the count of entries before it
+            let index = parent.scratch().len();
+            parent.scratch_mut().push(name.clone());
+            Some(Tagged { path: fsdent.path().to_path_buf(), project, is_dir, index })
+        }
+
+        fn is_dir(item: &Tagged) -> bool {
+            item.is_dir
+        }
+
+        fn collect(&self, iter: impl Iterator<Item = Tagged>) -> Vec<Tagged> {
+            iter.collect()
+        }
+
+        fn empty_collection() -> Vec<Tagged> {
+            vec![]
+        }
+    }
+
+    let dir = Dir::tmp();
+    dir.mkdirp("proj-a/src");
+    dir.mkdirp("proj-a/target");
+    dir.mkdirp("target");
+    dir.touch_all(&["proj-a/src/main.rs", "proj-a/target/out", "target/x", "other"]);
+
+    for &track_siblings in [false, true].iter() {
+        let items: Vec<Tagged> = WalkDirBuilder::with_context(dir.path(), (), Projects)
+            .track_siblings(track_siblings)
+            .into_classic()
+            .map(|r| r.unwrap())
+            .collect();
+
+        let project_of = |p: &str| {
+            items.iter().find(|item| item.path == dir.join(p)).map(|item| item.project.clone())
+        };
+        assert_eq!(Some(Some(OsString::from("proj-a"))), project_of("proj-a/src/main.rs"));
+        assert_eq!(Some(Some(OsString::from("proj-a"))), project_of("proj-a/target"));
+        assert_eq!(None, project_of("proj-a/target/out"));
+        assert_eq!(Some(None), project_of("target/x"));
+        assert_eq!(Some(None), project_of("other"));
+
+        // scratch is kept per dir: root's children are numbered 0, 1, 2
+        let mut indices: Vec<usize> = items.iter().filter(|item| item.path.parent() == Some(dir.path())).map(|item| item.index).collect();
+        indices.sort();
+        assert_eq!(vec![0, 1, 2], indices);
+    }
+
+    // map passes the items of the inner processor as parents
+    let mapped = crate::cp::map(Projects, |item: Tagged| (item.path, item.project));
+    let projects: Vec<(PathBuf, Option<OsString>)> = WalkDirBuilder::with_context(dir.path(), (), mapped)
+        .into_classic()
+        .map(|r| r.unwrap().into_value())
+        .collect();
+    assert!(projects.contains(&(dir.join("proj-a/src/main.rs"), Some(OsString::from("proj-a")))));
+    assert!(projects.contains(&(dir.join("target/x"), None)));
+    assert!(!projects.iter().any(|(path, _)| *path == dir.join("proj-a/target/out")));
+}
+
+#[test]
+fn path_content_processor() {
+    use crate::PathWalkDir;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.touch_all(&["a/f1", "a/b/f2"]);
+    dir.symlink_file("a/f1", "a/link");
+
+    let mut expected: Vec<PathBuf> = WalkDir::new(dir.path()).into_classic().map(|r| r.unwrap().into_path()).collect();
+    expected.sort();
+
+    let entries: Vec<_> = PathWalkDir::new(dir.path()).into_classic().map(|r| r.unwrap()).collect();
+    let mut paths: Vec<PathBuf> = entries.iter().map(|e| e.path().to_path_buf()).collect();
+    paths.sort();
+    assert_eq!(expected, paths);
+
+    let find = |p: &str| entries.iter().find(|e| e.path() == dir.join(p)).unwrap();
+    assert!(find("a/b").file_type().is_dir());
+    assert_eq!(3, find("a/b/f2").depth());
+    assert!(find("a/f1").file_type().is_file());
+    assert!(find("a/link").file_type().is_symlink());
+
+    let followed: Vec<_> = PathWalkDir::new(dir.path()).follow_links(true).into_classic().map(|r| r.unwrap()).collect();
+    assert!(followed.iter().find(|e| e.path() == dir.join("a/link")).unwrap().file_type().is_file());
+
+    // a file removed while its preceding sibling dir is walked can't be read: an error is yielded in its place
+    let mut yielded = vec![];
+    let mut errors = vec![];
+    let by_name = |a: (&DefaultDirEntry, &_), b: (&DefaultDirEntry, &_), _ctx: &mut _| a.0.file_name().cmp(&b.0.file_name());
+    for r in PathWalkDir::new(dir.path()).contents_first(true).sort_by(by_name).into_classic() {
+        match r {
+            Ok(e) => {
+                if e.path() == dir.join("a/b/f2") {
+                    fs::remove_file(dir.join("a/f1")).unwrap();
+                }
+                yielded.push(e.into_path());
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    assert!(!yielded.contains(&dir.join("a/f1")));
+    assert_eq!(Some(dir.path()), yielded.last().map(|p| p.as_path()));
+    assert_eq!(1, errors.len());
+    assert_eq!(Some(dir.join("a/f1").as_path()), errors[0].path());
+    assert_eq!(2, errors[0].depth());
+}
+
+#[test]
+fn lending_walk() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+    dir.mkdirp("d");
+    dir.touch_all(&["a/f1", "a/b/f2", "a/b/c/f3", "d/f4"]);
+    dir.symlink_dir("a", "a/b/loop");
+
+    let lending_paths = |wd: WalkDir| {
+        let mut walk = wd.into_lending().unwrap();
+        let mut paths = vec![];
+        let mut errors = 0;
+        while let Some(r) = walk.next_entry() {
+            match r {
+                Ok(entry) => paths.push(entry.path().to_path_buf()),
+                Err(_) => errors += 1,
+            }
+        }
+        (paths, errors)
+    };
+    let classic_paths = |wd: WalkDir| {
+        let mut paths = vec![];
+        let mut errors = 0;
+        for r in wd.into_classic() {
+            match r {
+                Ok(entry) => paths.push(entry.into_path()),
+                Err(_) => errors += 1,
+            }
+        }
+        (paths, errors)
+    };
+    let sorted = |(mut paths, errors): (Vec<PathBuf>, usize)| {
+        paths.sort();
+        (paths, errors)
+    };
+
+    let configs: Vec<fn(WalkDir) -> WalkDir> = vec![
+        |wd| wd,
+        |wd| wd.min_depth(2).max_depth(3),
+        |wd| wd.content_filter(ContentFilter::FilesOnly),
+        |wd| wd.follow_links(true),
+    ];
+    for config in configs {
+        assert_eq!(
+            sorted(classic_paths(config(WalkDir::new(dir.path())))),
+            sorted(lending_paths(config(WalkDir::new(dir.path())))),
+        );
+    }
+
+    // loop links are yielded without following them
+    let (paths, errors) = lending_paths(WalkDir::new(dir.path()).follow_links(true).yield_loop_links(true));
+    assert_eq!(0, errors);
+    assert!(paths.contains(&dir.join("a/b/loop")));
+    assert!(!paths.contains(&dir.join("a/b/loop/f1")));
+
+    // contents first: each dir after its content
+    let (paths, errors) = lending_paths(WalkDir::new(dir.path()).contents_first(true));
+    assert_eq!(0, errors);
+    assert_eq!(Some(&dir.path().to_path_buf()), paths.last());
+    let pos = |p: &str| paths.iter().position(|path| *path == dir.join(p)).unwrap();
+    assert!(pos("a/b/c/f3") < pos("a/b/c"));
+    assert!(pos("a/b/c") < pos("a/b"));
+
+    // skipped dirs are not descended into
+    let mut walk = WalkDir::new(dir.path()).into_lending().unwrap();
+    let mut names = vec![];
+    while let Some(r) = walk.next_entry() {
+        let entry = r.unwrap();
+        names.push(entry.file_name().to_os_string());
+        if entry.file_name() == "a" {
+            assert!(entry.is_dir());
+            assert_eq!(1, entry.depth());
+            walk.skip_current_dir();
+        }
+    }
+    assert!(names.contains(&"f4".into()));
+    assert!(!names.contains(&"f1".into()));
+
+    // after a dir yielded in contents_first mode, there is nothing to skip
+    let mut walk = WalkDir::new(dir.path()).contents_first(true).into_lending().unwrap();
+    let mut paths = vec![];
+    while let Some(r) = walk.next_entry() {
+        let entry = r.unwrap();
+        paths.push(entry.path().to_path_buf());
+        if entry.is_dir() {
+            walk.skip_current_dir();
+        }
+    }
+    assert_eq!(sorted(classic_paths(WalkDir::new(dir.path()))), sorted((paths, 0)));
+
+    // options the lending walker can't apply are rejected
+    assert!(WalkDir::new(dir.path()).sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name())).into_lending().is_err());
+    assert!(WalkDir::new(dir.path()).content_order(ContentOrder::DirsFirst).into_lending().is_err());
+}
+
+#[test]
+fn tree() {
+    use std::ffi::OsStr;
+    use crate::tree::{NodeType, Order, Tree};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("c");
+    dir.touch_all(&["a/b/f1", "a/f2", "c/f1"]);
+    fs::write(dir.join("a/f2"), b"12345").unwrap();
+
+    let (tree, errors) = Tree::build(WalkDir::new(dir.path())
+        .sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()))
+        .min_depth(2));
+    assert!(errors.is_empty());
+    assert_eq!(7, tree.len());
+    assert_eq!(dir.path(), tree.root_path());
+
+    let f2 = tree.find("a/f2").unwrap();
+    assert_eq!(NodeType::File, f2.node_type());
+    assert_eq!(5, f2.metadata().len);
+    assert_eq!(2, f2.depth());
+    assert_eq!(PathBuf::from("a/f2"), f2.relative_path());
+    assert_eq!(dir.join("a/f2"), f2.path());
+    assert_eq!(OsStr::new("a"), f2.parent().unwrap().name());
+    assert!(tree.find("a/missing").is_none());
+    assert!(tree.find("").unwrap().parent().is_none());
+
+    let a = tree.find("./a").unwrap();
+    assert!(a.is_dir());
+    let children: Vec<&OsStr> = a.children().map(|node| node.name()).collect();
+    assert_eq!(vec![OsStr::new("b"), OsStr::new("f2")], children);
+
+    let rel = |order| tree.iter(order).map(|node| node.relative_path()).collect::<Vec<_>>();
+    let paths = |ps: &[&str]| ps.iter().map(PathBuf::from).collect::<Vec<_>>();
+    assert_eq!(paths(&["", "a", "a/b", "a/b/f1", "a/f2", "c", "c/f1"]), rel(Order::PreOrder));
+    assert_eq!(paths(&["a/b/f1", "a/b", "a/f2", "a", "c/f1", "c", ""]), rel(Order::PostOrder));
+    assert_eq!(paths(&["", "a", "c", "a/b", "a/f2", "c/f1", "a/b/f1"]), rel(Order::BreadthFirst));
+    assert_eq!(rel(Order::PreOrder), tree.nodes().map(|node| node.relative_path()).collect::<Vec<_>>());
+    let sub: Vec<_> = a.descendants(Order::PostOrder).map(|node| node.relative_path()).collect();
+    assert_eq!(paths(&["a/b/f1", "a/b", "a/f2", "a"]), sub);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&tree).unwrap();
+        let tree: Tree = serde_json::from_str(&json).unwrap();
+        assert_eq!(5, tree.find("a/f2").unwrap().metadata().len);
+        assert_eq!(rel(Order::BreadthFirst), tree.iter(Order::BreadthFirst).map(|node| node.relative_path()).collect::<Vec<_>>());
+
+        // ids out of range or broken links are rejected instead of panicking later
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let corrupt = |field: &str, v: serde_json::Value| {
+            let mut value = value.clone();
+            value["nodes"][1][field] = v;
+            serde_json::from_value::<Tree>(value).is_err()
+        };
+        assert!(corrupt("name", 999.into()));
+        assert!(corrupt("parent", 999.into()));
+        assert!(corrupt("parent", serde_json::Value::Null));
+        assert!(corrupt("children", vec![999].into()));
+        assert!(corrupt("children", vec![0].into()));
+        assert!(corrupt("depth", 5.into()));
+    }
+}
+
+#[test]
+fn render_tree() {
+    use crate::render::{RenderSummary, TreeRenderer};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("c");
+    dir.touch_all(&["a/b/f1", "a/f2", "a/f3", "c/f4"]);
+    fs::write(dir.join("c/f4"), b"12345").unwrap();
+
+    let sorted = || WalkDir::new(dir.path()).sort_by(|(a, _), (b, _), _ctx| a.file_name().cmp(&b.file_name()));
+    let render = |renderer: TreeRenderer| {
+        let mut out = vec![];
+        let (summary, errors) = renderer.render(sorted(), &mut out).unwrap();
+        assert!(errors.is_empty());
+        let root = dir.path().display().to_string();
+        (summary, String::from_utf8(out).unwrap().replacen(&root, "ROOT", 1))
+    };
+
+    let (summary, out) = render(TreeRenderer::new());
+    assert_eq!(RenderSummary { dirs: 3, files: 4 }, summary);
+    let expected = "\
+ROOT
+├── a
+│   ├── b
+│   │   └── f1
+│   ├── f2
+│   └── f3
+└── c
+    └── f4
+
+3 directories, 4 files
+";
+    assert_eq!(expected, out);
+
+    let (summary, out) = render(TreeRenderer::new().ascii(true).file_counts(true).max_depth(1));
+    assert_eq!(RenderSummary { dirs: 2, files: 0 }, summary);
+    assert_eq!("ROOT (0 files)\n|-- a\n`-- c\n\n2 directories, 0 files\n", out);
+
+    let (_, out) = render(TreeRenderer::new().ascii(true).file_counts(true).summary(false));
+    let expected = "\
+ROOT (0 files)
+|-- a (2 files)
+|   |-- b (1 file)
+|   |   `-- f1
+|   |-- f2
+|   `-- f3
+`-- c (1 file)
+    `-- f4
+";
+    assert_eq!(expected, out);
+
+    let (_, out) = render(TreeRenderer::new().sizes(true).summary(false));
+    assert!(out.ends_with("└── [         5]  f4\n"), "{}", out);
+
+    // connectors follow the printed entries when the positions are filtered
+    let render_without = |names: &[&str]| {
+        let renderer = TreeRenderer::new();
+        let positions = renderer.configure(sorted()).build().filter(|pos| match pos {
+            Position::Entry(dent) => !names.iter().any(|name| dent.file_name() == *name),
+            _ => true,
+        });
+        let mut out = vec![];
+        let (summary, _) = renderer.render_positions(positions, &mut out).unwrap();
+        let root = dir.path().display().to_string();
+        (summary, String::from_utf8(out).unwrap().replacen(&root, "ROOT", 1))
+    };
+
+    let (summary, out) = render_without(&["c", "f4"]);
+    assert_eq!(RenderSummary { dirs: 2, files: 3 }, summary);
+    let expected = "\
+ROOT
+└── a
+    ├── b
+    │   └── f1
+    ├── f2
+    └── f3
+
+2 directories, 3 files
+";
+    assert_eq!(expected, out);
+
+    let (_, out) = render_without(&["b", "f3"]);
+    let expected = "\
+ROOT
+├── a
+│   ├── f1
+│   └── f2
+└── c
+    └── f4
+
+2 directories, 3 files
+";
+    assert_eq!(expected, out);
+}
@@ -0,0 +1,529 @@
+//! In-memory dir trees.
+//!
+//! A [`Tree`] is built by one walk and then gives random access to the
+//! structure: lookup by relative path, parent and children links, and
+//! traversal in pre-order, post-order or breadth-first order. Nodes are kept
+//! in one arena and refer to each other by [`NodeId`]; file names are
+//! interned, so each distinct name is stored once. With the `serde` feature
+//! the tree can be serialized and deserialized.
+//!
+//! ```no_run
+//! use walkdir2::tree::{self, Order};
+//!
+//! let (tree, errors) = tree::tree("foo");
+//! if let Some(node) = tree.find("src/lib.rs") {
+//!     println!("{} bytes", node.metadata().len);
+//! }
+//! for node in tree.iter(Order::PostOrder) {
+//!     println!("{}", node.relative_path().display());
+//! }
+//! # drop(errors);
+//! ```
+//!
+//! [`Tree`]: struct.Tree.html
+//! [`NodeId`]: struct.NodeId.html
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use std::ffi::{OsStr, OsString};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::cp::{DirEntry, DirEntryContentProcessor};
+use crate::fs;
+use crate::walk::{ErrorReport, WalkDirBuilder};
+use crate::wd::{ContentFilter, Depth, Position};
+
+/////////////////////////////////////////////////////////////////////////
+//// Node
+
+/// Index of a node in its [`Tree`]
+///
+/// [`Tree`]: struct.Tree.html
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeId(u32);
+
+impl NodeId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Index of an interned name
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct NameId(u32);
+
+/// A type of node
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeType {
+    /// Regular file
+    File,
+    /// Dir
+    Dir,
+    /// Symlink (not followed)
+    Symlink,
+    /// Anything else (fifo, socket, device etc.)
+    Other,
+}
+
+/// Metadata of a node (of the symlink target if links are followed)
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeMetadata {
+    /// Size in bytes
+    pub len: u64,
+    /// Last modification time, if available on this platform
+    pub modified: Option<SystemTime>,
+    /// Read-only flag
+    pub readonly: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Node {
+    name: NameId,
+    node_type: NodeType,
+    metadata: NodeMetadata,
+    depth: Depth,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Names
+
+/// Interned file names.
+///
+/// Each name is stored once: the index maps the hash of a name to the ids
+/// with this hash, and the names themselves are compared on lookup.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Vec<OsString>", into = "Vec<OsString>"))]
+struct Names {
+    names: Vec<OsString>,
+    index: HashMap<u64, Vec<NameId>>,
+    hasher: RandomState,
+}
+
+impl Names {
+    fn hash(&self, name: &OsStr) -> u64 {
+        let mut hasher = self.hasher.build_hasher();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn find(&self, hash: u64, name: &OsStr) -> Option<NameId> {
+        self.index.get(&hash)?.iter().copied().find(|&id| self.name(id) == name)
+    }
+
+    fn push(&mut self, hash: u64, name: OsString) -> NameId {
+        let id = NameId(self.names.len() as u32);
+        self.names.push(name);
+        self.index.entry(hash).or_default().push(id);
+        id
+    }
+
+    fn intern(&mut self, name: &OsStr) -> NameId {
+        let hash = self.hash(name);
+        match self.find(hash, name) {
+            Some(id) => id,
+            None => self.push(hash, name.to_os_string()),
+        }
+    }
+
+    fn get(&self, name: &OsStr) -> Option<NameId> {
+        self.find(self.hash(name), name)
+    }
+
+    fn name(&self, id: NameId) -> &OsStr {
+        &self.names[id.0 as usize]
+    }
+
+    #[cfg(feature = "serde")]
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+impl From<Vec<OsString>> for Names {
+    fn from(names: Vec<OsString>) -> Self {
+        let mut this = Self::default();
+        for name in names {
+            let hash = this.hash(&name);
+            this.push(hash, name);
+        }
+        this
+    }
+}
+
+impl From<Names> for Vec<OsString> {
+    fn from(names: Names) -> Self {
+        names.names
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Tree
+
+/// A dir tree kept in memory (see the [module docs])
+///
+/// [module docs]: index.html
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "TreeData"))]
+pub struct Tree {
+    /// Path of the root as given to the walk
+    root: PathBuf,
+    names: Names,
+    /// Nodes in the order of the walk (so the root is the first one, and
+    /// each parent goes before its children)
+    nodes: Vec<Node>,
+}
+
+/// A tree as deserialized, before the ids are checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct TreeData {
+    root: PathBuf,
+    names: Names,
+    nodes: Vec<Node>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<TreeData> for Tree {
+    type Error = String;
+
+    /// Checks that all ids are in range and the links form a tree, so that no lookup panics
+    fn try_from(data: TreeData) -> Result<Self, Self::Error> {
+        let TreeData { root, names, nodes } = data;
+        let mut children_count = 0;
+
+        for (index, node) in nodes.iter().enumerate() {
+            if node.name.0 as usize >= names.len() {
+                return Err(format!("node {}: name id {} is out of range", index, node.name.0));
+            }
+            match node.parent {
+                None if index != 0 => return Err(format!("node {}: only the root may have no parent", index)),
+                Some(_) if index == 0 => return Err("the root has a parent".to_string()),
+                Some(parent) if parent.index() >= index => {
+                    return Err(format!("node {}: parent {} doesn't go before it", index, parent.0))
+                }
+                Some(parent) if node.depth != nodes[parent.index()].depth + 1 => {
+                    return Err(format!("node {}: depth doesn't match the parent", index))
+                }
+                _ => {}
+            }
+            for child in &node.children {
+                if nodes.get(child.index()).map_or(true, |child| child.parent != Some(NodeId(index as u32))) {
+                    return Err(format!("node {}: child {} is out of range or has another parent", index, child.0));
+                }
+            }
+            children_count += node.children.len();
+        }
+
+        // Each node except the root is a child of its parent exactly once
+        if children_count + 1 != nodes.len().max(1) {
+            return Err("nodes are missing from or repeated in children".to_string());
+        }
+
+        Ok(Tree { root, names, nodes })
+    }
+}
+
+/// Walks the given root with default options (see [`Tree::build`]).
+///
+/// [`Tree::build`]: struct.Tree.html#method.build
+pub fn tree<P: AsRef<Path>>(root: P) -> (Tree, ErrorReport) {
+    Tree::build(WalkDirBuilder::new(root))
+}
+
+impl Tree {
+    /// Builds a tree with the walk described by the builder.
+    ///
+    /// `min_depth`, `contents_first` and `content_filter` are reset, since
+    /// every dir must be seen before its content. Entries which failed to
+    /// read are not in the tree; their errors are returned along with it.
+    pub fn build<E>(builder: WalkDirBuilder<E, DirEntryContentProcessor>) -> (Tree, ErrorReport<E>)
+    where
+        E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, FileName = OsString, Metadata = std::fs::Metadata>,
+    {
+        let walk = builder.min_depth(0).contents_first(false).content_filter(ContentFilter::None).build();
+
+        let mut tree = Tree { root: PathBuf::new(), names: Names::default(), nodes: vec![] };
+        let mut errors = ErrorReport::default();
+        // Ids of the dirs containing the last entry (the root first)
+        let mut dirs: Vec<NodeId> = vec![];
+
+        for pos in walk {
+            let dent = match pos {
+                Position::Entry(dent) => dent,
+                Position::Error(err) => {
+                    errors.push(err);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            dirs.truncate(dent.depth());
+            if dirs.len() != dent.depth() {
+                // The parent isn't in the tree
+                continue;
+            }
+            let parent = dirs.last().copied();
+
+            let id = tree.push(&dent, parent);
+            if dent.is_dir() {
+                dirs.push(id);
+            }
+        }
+
+        (tree, errors)
+    }
+
+    fn push<E>(&mut self, dent: &DirEntry<E>, parent: Option<NodeId>) -> NodeId
+    where
+        E: fs::FsDirEntry<Path = Path, PathBuf = PathBuf, FileName = OsString, Metadata = std::fs::Metadata>,
+    {
+        let md = dent.metadata_ref();
+        let ty = md.file_type();
+        let node_type = if ty.is_dir() {
+            NodeType::Dir
+        } else if ty.is_file() {
+            NodeType::File
+        } else if ty.is_symlink() {
+            NodeType::Symlink
+        } else {
+            NodeType::Other
+        };
+
+        let id = NodeId(self.nodes.len() as u32);
+        if parent.is_none() {
+            self.root = dent.path().to_path_buf();
+        }
+        self.nodes.push(Node {
+            name: self.names.intern(dent.file_name()),
+            node_type,
+            metadata: NodeMetadata {
+                len: md.len(),
+                modified: md.modified().ok(),
+                readonly: md.permissions().readonly(),
+            },
+            depth: dent.depth(),
+            parent,
+            children: vec![],
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent.index()].children.push(id);
+        }
+        id
+    }
+
+    /// Path of the root as given to the walk
+    pub fn root_path(&self) -> &Path {
+        &self.root
+    }
+
+    /// The root node (`None` if the root failed to read)
+    pub fn root(&self) -> Option<NodeRef<'_>> {
+        if self.nodes.is_empty() {
+            None
+        } else {
+            Some(self.node(NodeId(0)))
+        }
+    }
+
+    /// The node with the given id.
+    ///
+    /// # Panics
+    ///
+    /// If the id is from another tree.
+    pub fn node(&self, id: NodeId) -> NodeRef<'_> {
+        assert!(id.index() < self.nodes.len(), "node id out of range");
+        NodeRef { tree: self, id }
+    }
+
+    /// Count of nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The tree has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Finds a node by the path relative to the root (an empty path is the root itself)
+    pub fn find<P: AsRef<Path>>(&self, path: P) -> Option<NodeRef<'_>> {
+        let mut node = self.root()?;
+        for component in path.as_ref().components() {
+            match component {
+                Component::CurDir => continue,
+                Component::Normal(name) => {
+                    let name = self.names.get(name)?;
+                    node = node.node().children.iter().map(|&id| self.node(id)).find(|child| child.node().name == name)?;
+                }
+                _ => return None,
+            }
+        }
+        Some(node)
+    }
+
+    /// Iterates over all nodes in the given order
+    pub fn iter(&self, order: Order) -> Iter<'_> {
+        match self.root() {
+            Some(root) => root.descendants(order),
+            None => Iter { tree: self, order, queue: VecDeque::new() },
+        }
+    }
+
+    /// Iterates over all nodes in the order of the walk (the same as `Order::PreOrder`, but faster)
+    pub fn nodes(&self) -> impl Iterator<Item = NodeRef<'_>> + '_ {
+        (0..self.nodes.len()).map(move |i| self.node(NodeId(i as u32)))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// NodeRef
+
+/// A node borrowed from its [`Tree`]
+///
+/// [`Tree`]: struct.Tree.html
+#[derive(Debug, Clone, Copy)]
+pub struct NodeRef<'t> {
+    tree: &'t Tree,
+    id: NodeId,
+}
+
+impl<'t> NodeRef<'t> {
+    fn node(&self) -> &'t Node {
+        &self.tree.nodes[self.id.index()]
+    }
+
+    /// Id of this node
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// File name
+    pub fn name(&self) -> &'t OsStr {
+        self.tree.names.name(self.node().name)
+    }
+
+    /// Type of this node
+    pub fn node_type(&self) -> NodeType {
+        self.node().node_type
+    }
+
+    /// Is it a dir?
+    pub fn is_dir(&self) -> bool {
+        self.node().node_type == NodeType::Dir
+    }
+
+    /// Metadata of this node
+    pub fn metadata(&self) -> &'t NodeMetadata {
+        &self.node().metadata
+    }
+
+    /// Depth relative to the root
+    pub fn depth(&self) -> Depth {
+        self.node().depth
+    }
+
+    /// The parent node (`None` for the root)
+    pub fn parent(&self) -> Option<NodeRef<'t>> {
+        self.node().parent.map(|id| self.tree.node(id))
+    }
+
+    /// Child nodes in the order of the walk
+    pub fn children(&self) -> impl Iterator<Item = NodeRef<'t>> + 't {
+        let tree = self.tree;
+        self.node().children.iter().map(move |&id| tree.node(id))
+    }
+
+    /// Iterates over this node and all its descendants in the given order
+    pub fn descendants(&self, order: Order) -> Iter<'t> {
+        let mut queue = VecDeque::new();
+        queue.push_back((self.id, false));
+        Iter { tree: self.tree, order, queue }
+    }
+
+    /// Path relative to the root (empty for the root itself)
+    pub fn relative_path(&self) -> PathBuf {
+        let mut names = vec![];
+        let mut node = *self;
+        while let Some(parent) = node.parent() {
+            names.push(node.name());
+            node = parent;
+        }
+        names.iter().rev().collect()
+    }
+
+    /// Full path (the root path joined with the relative path)
+    pub fn path(&self) -> PathBuf {
+        self.tree.root.join(self.relative_path())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Iter
+
+/// Order of traversal
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Order {
+    /// Each dir before its content (depth-first)
+    PreOrder,
+    /// Each dir after its content (depth-first)
+    PostOrder,
+    /// Level by level: all nodes of one depth before the deeper ones
+    BreadthFirst,
+}
+
+/// An iterator over nodes of a [`Tree`] (see `Tree::iter`, `NodeRef::descendants`)
+///
+/// [`Tree`]: struct.Tree.html
+#[derive(Debug)]
+pub struct Iter<'t> {
+    tree: &'t Tree,
+    order: Order,
+    /// Nodes to visit, with the flag "children are already queued" (for post-order).
+    /// Used as a stack for depth-first orders.
+    queue: VecDeque<(NodeId, bool)>,
+}
+
+impl<'t> Iterator for Iter<'t> {
+    type Item = NodeRef<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (id, expanded) = match self.order {
+                Order::BreadthFirst => self.queue.pop_front()?,
+                Order::PreOrder | Order::PostOrder => self.queue.pop_back()?,
+            };
+            let children = &self.tree.nodes[id.index()].children;
+
+            match self.order {
+                Order::BreadthFirst => {
+                    self.queue.extend(children.iter().map(|&child| (child, false)));
+                }
+                Order::PreOrder => {
+                    self.queue.extend(children.iter().rev().map(|&child| (child, false)));
+                }
+                Order::PostOrder => {
+                    if !expanded && !children.is_empty() {
+                        self.queue.push_back((id, true));
+                        self.queue.extend(children.iter().rev().map(|&child| (child, false)));
+                        continue;
+                    }
+                }
+            }
+
+            return Some(self.tree.node(id));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::error::ErrorInner;
+use crate::fs;
+use crate::walk::rawdent::RawDirEntry;
+use crate::wd::{self, Depth, IntoOk};
+
+/////////////////////////////////////////////////////////////////////////
+//// Ancestor
+
+/// An ancestor is an item in the directory tree traversed by walkdir, and is
+/// used to check for loops in the tree when traversing symlinks.
+#[derive(Debug)]
+pub(crate) struct Ancestor<FS: fs::FsDirEntry> {
+    /// The path of this ancestor.
+    path: FS::PathBuf,
+    /// Fingerprint
+    fingerprint: FS::DirFingerprint,
+}
+
+impl<FS: fs::FsDirEntry> Ancestor<FS> {
+    /// Create a new ancestor from the given directory path.
+    pub fn new(
+        raw: &RawDirEntry<FS>,
+        ctx: &mut FS::Context,
+    ) -> wd::ResultInner<Self, FS> {
+        Self {
+            path: raw.pathbuf(),
+            fingerprint: raw.fingerprint(ctx)?
+        }.into_ok()
+    }
+
+    /// Returns true if and only if the given open file handle corresponds to
+    /// the same directory as this ancestor.
+    fn is_same(&self, rhs: &Self) -> bool {
+        FS::is_same( (&self.path, &self.fingerprint), (&rhs.path, &rhs.fingerprint))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// Checks
+
+/// Returns the depth of the ancestor which is the same dir as `raw` (i.e. `raw` is a loop link).
+///
+/// `ancestors` are all open dirs, from the root.
+pub(crate) fn check_loop<FS: fs::FsDirEntry>(
+    raw: &RawDirEntry<FS>,
+    ancestors: &[Ancestor<FS>],
+    ctx: &mut FS::Context,
+) -> wd::ResultInner<Option<Depth>, FS> {
+    let raw_as_ancestor = Ancestor::<FS>::new( raw, ctx )?;
+
+    for (index, ancestor) in ancestors.iter().enumerate().rev() {
+        if ancestor.is_same(&raw_as_ancestor) {
+            return Ok(Some(index));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Makes the error of the loop link `child` to the ancestor at `depth`
+pub(crate) fn make_loop_error<FS: fs::FsDirEntry>(
+    ancestors: &[Ancestor<FS>],
+    depth: Depth,
+    child: &FS::Path,
+) -> ErrorInner<FS> {
+    let ancestor = ancestors.get(depth).unwrap();
+
+    ErrorInner::<FS>::from_loop(&ancestor.path, child)
+}
+
+/// Is `dent` on the file system of the root?
+pub(crate) fn is_same_file_system<FS: fs::FsDirEntry>(
+    root_device: &FS::DeviceNum,
+    dent: &RawDirEntry<FS>,
+    ctx: &mut FS::Context,
+) -> wd::ResultInner<bool, FS> {
+    Ok(*root_device == dent.device_num(ctx)?)
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cp::ContentProcessor;
+use crate::error::{Error, ErrorKind, Operation};
+use crate::fs::{self, FsPath, FsPathBuf};
+use crate::walk::iter::WalkDirIter;
+use crate::walk::walk::WalkDirIteratorItem;
+use crate::wd::Position;
+
+/////////////////////////////////////////////////////////////////////////
+//// ErrorReport
+
+/// Errors accumulated during the walk.
+///
+/// Values of this type are produced by [`CollectErrors`]. The `Display` impl
+/// prints a compact summary (counts by kind and by top-level subtree), use
+/// [`details`] to print every error.
+///
+/// [`CollectErrors`]: struct.CollectErrors.html
+/// [`details`]: #method.details
+#[derive(Debug)]
+pub struct ErrorReport<E: fs::FsDirEntry = fs::DefaultDirEntry> {
+    errors: Vec<Error<E>>,
+}
+
+impl<E: fs::FsDirEntry> Default for ErrorReport<E> {
+    fn default() -> Self {
+        Self { errors: vec![] }
+    }
+}
+
+impl<E: fs::FsDirEntry> ErrorReport<E> {
+    /// Adds an error to the report
+    pub fn push(&mut self, err: Error<E>) {
+        self.errors.push(err);
+    }
+
+    /// Count of collected errors
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Is there no errors?
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// All collected errors in the order of occurrence
+    pub fn errors(&self) -> &[Error<E>] {
+        &self.errors
+    }
+
+    /// Unwraps collected errors
+    pub fn into_errors(self) -> Vec<Error<E>> {
+        self.errors
+    }
+
+    /// Errors grouped by kind
+    pub fn by_kind(&self) -> BTreeMap<ErrorKind, Vec<&Error<E>>> {
+        let mut groups: BTreeMap<ErrorKind, Vec<&Error<E>>> = BTreeMap::new();
+        for err in &self.errors {
+            groups.entry(err.kind()).or_default().push(err);
+        }
+        groups
+    }
+
+    /// Errors grouped by top-level subtree (i.e. by the entry at depth 1
+    /// containing the failed path).
+    ///
+    /// Errors at depth 0 are grouped under the root path, errors without
+    /// path are grouped under `None`.
+    pub fn by_subtree(&self) -> BTreeMap<Option<&E::Path>, Vec<&Error<E>>> {
+        let mut groups: BTreeMap<Option<&E::Path>, Vec<&Error<E>>> = BTreeMap::new();
+        for err in &self.errors {
+            groups.entry(Self::subtree(err)).or_default().push(err);
+        }
+        groups
+    }
+
+    /// Returns an object which displays every collected error grouped by kind
+    pub fn details(&self) -> ErrorReportDetails<'_, E> {
+        ErrorReportDetails { report: self }
+    }
+
+    fn subtree(err: &Error<E>) -> Option<&E::Path> {
+        let path = err.path()?;
+        // A failure to read the next entry carries the path of the dir being
+        // read, but the depth of its entries
+        let path_depth = match err.operation() {
+            Operation::NextEntry => err.depth().saturating_sub(1),
+            _ => err.depth(),
+        };
+        match path.split_tail(path_depth.saturating_sub(1)) {
+            Some((head, _)) => Some(head),
+            None => Some(path),
+        }
+    }
+}
+
+impl<E: fs::FsDirEntry> fmt::Display for ErrorReport<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} error(s)", self.errors.len())?;
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(f, "by kind:")?;
+        for (kind, errs) in self.by_kind() {
+            let mut ops: BTreeMap<Operation, usize> = BTreeMap::new();
+            for err in &errs {
+                *ops.entry(err.operation()).or_default() += 1;
+            }
+            let ops: Vec<String> = ops.into_iter().map(|(op, n)| format!("{}: {}", op, n)).collect();
+            writeln!(f, "  {}: {} ({})", kind, errs.len(), ops.join(", "))?;
+        }
+
+        writeln!(f, "by subtree:")?;
+        for (path, errs) in self.by_subtree() {
+            match path {
+                Some(path) => writeln!(f, "  {}: {}", path.to_path_buf().display(), errs.len())?,
+                None => writeln!(f, "  <no path>: {}", errs.len())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Displays every error of [`ErrorReport`] grouped by kind.
+///
+/// [`ErrorReport`]: struct.ErrorReport.html
+#[derive(Debug)]
+pub struct ErrorReportDetails<'r, E: fs::FsDirEntry> {
+    report: &'r ErrorReport<E>,
+}
+
+impl<'r, E: fs::FsDirEntry> fmt::Display for ErrorReportDetails<'r, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (kind, errs) in self.report.by_kind() {
+            writeln!(f, "{} ({}):", kind, errs.len())?;
+            for err in errs {
+                writeln!(f, "  {}", err)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// CollectErrors
+
+/// An iterator which passes entries through and collects errors.
+///
+/// Values of this type are created by calling [`.collect_errors()`] on any
+/// `WalkDirIter`. `Position::Error` is never yielded, errors are accumulated
+/// into [`ErrorReport`] instead.
+///
+/// [`.collect_errors()`]: trait.WalkDirIter.html#method.collect_errors
+/// [`ErrorReport`]: struct.ErrorReport.html
+#[derive(Debug)]
+pub struct CollectErrors<FS, CP, I>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    I: Iterator<Item = WalkDirIteratorItem<FS, CP>> + WalkDirIter<FS, CP>,
+{
+    inner: I,
+    report: ErrorReport<FS>,
+    _cp: std::marker::PhantomData<CP>,
+}
+
+impl<FS, CP, I> CollectErrors<FS, CP, I>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    I: Iterator<Item = WalkDirIteratorItem<FS, CP>> + WalkDirIter<FS, CP>,
+{
+    pub(crate) fn new(inner: I) -> Self {
+        Self { inner, report: ErrorReport::default(), _cp: std::marker::PhantomData }
+    }
+
+    /// Errors collected so far
+    pub fn report(&self) -> &ErrorReport<FS> {
+        &self.report
+    }
+
+    /// Unwraps collected errors
+    pub fn into_report(self) -> ErrorReport<FS> {
+        self.report
+    }
+}
+
+impl<FS, CP, I> Iterator for CollectErrors<FS, CP, I>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    I: Iterator<Item = WalkDirIteratorItem<FS, CP>> + WalkDirIter<FS, CP>,
+{
+    type Item = WalkDirIteratorItem<FS, CP>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Position::Error(err) => self.report.push(err),
+                item => return Some(item),
+            }
+        }
+    }
+}
+
+impl<FS, CP, I> WalkDirIter<FS, CP> for CollectErrors<FS, CP, I>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    I: Iterator<Item = WalkDirIteratorItem<FS, CP>> + WalkDirIter<FS, CP>,
+{
+    fn skip_current_dir(&mut self) {
+        self.inner.skip_current_dir();
+    }
+}
@@ -1,10 +1,10 @@
 use std::cmp::Ordering;
 use std::vec;
 
-use crate::wd::{self, ContentFilter, ContentOrder, Depth, FnCmp, IntoOk, InnerPosition, InnerPositionWithData};
+use crate::wd::{self, ContentFilter, ContentOrder, Depth, DirSummary, FnCmp, IntoOk, InnerPosition, InnerPositionWithData, LastFlags};
 use crate::fs;
 use crate::walk::rawdent::{RawDirEntry, ReadDir};
-use crate::cp::ContentProcessor;
+use crate::cp::{ContentProcessor, ParentContext};
 use crate::walk::opts::WalkDirOptionsImmut;
 use crate::error::{ErrorInner, Error};
 
@@ -21,22 +21,78 @@ pub struct FlatDirEntry<FS: fs::FsDirEntry> {
     /// - Some(index) => is loop to ancestor[index]
     /// - None => is not loop link
     pub loop_link: Option<Depth>,
+    /// This entry is a dir on another file system (with opts.same_file_system),
+    /// so it is yielded but not walked.
+    pub other_fs: bool,
+}
+
+/// What the content processor made of an entry: its item, or the error (if any)
+/// for which the entry was skipped
+type ContentItem<FS, CP> = Result<<CP as ContentProcessor<FS>>::Item, Option<<FS as fs::FsDirEntry>::Error>>;
+
+/// Makes the item of an entry, unless it was already made and kept in `item`
+fn process_flat<'i, FS, CP>(
+    flat: &mut FlatDirEntry<FS>,
+    item: &'i mut Option<ContentItem<FS, CP>>,
+    content_processor: &CP,
+    parent: &mut DirParent<FS, CP>,
+    depth: Depth,
+    ctx: &mut FS::Context,
+) -> &'i ContentItem<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
+    item.get_or_insert_with(|| {
+        match flat.raw.make_content_item(content_processor, parent.context(), flat.is_dir, depth, ctx) {
+            Some(item) => Ok(item),
+            None => Err(content_processor.take_error()),
+        }
+    })
 }
 
 /////////////////////////////////////////////////////////////////////////
 //// DirEntryRecord
 
-#[derive(Debug)]
-pub(crate) struct DirEntryRecord<FS: fs::FsDirEntry> {
+pub(crate) struct DirEntryRecord<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
     /// Value from ReadDir
     flat: wd::ResultInner<FlatDirEntry<FS>, FS>,
     /// This entry must be yielded first according to opts.content_order
     first_pass: bool,
     /// This entry will not be yielded according to opts.content_filter
     hidden: bool,
+    /// This entry will be yielded as Position::Entry (only with opts.track_siblings)
+    sibling: bool,
+    /// Item made before this entry is reached (to count siblings or to collect
+    /// the content of the dir), so the entry is processed only once
+    item: Option<ContentItem<FS, CP>>,
+}
+
+impl<FS, CP> std::fmt::Debug for DirEntryRecord<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirEntryRecord")
+            .field("flat", &self.flat)
+            .field("first_pass", &self.first_pass)
+            .field("hidden", &self.hidden)
+            .field("sibling", &self.sibling)
+            .field("processed", &self.item.is_some())
+            .finish()
+    }
 }
 
-impl<FS: fs::FsDirEntry> DirEntryRecord<FS> {
+impl<FS, CP> DirEntryRecord<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
     fn new(
         r_rawdent: wd::ResultInner<RawDirEntry<FS>, FS>,
         opts_immut: &WalkDirOptionsImmut,
@@ -69,14 +125,26 @@ impl<FS: fs::FsDirEntry> DirEntryRecord<FS> {
                     ContentFilter::SkipAll => true,
                 };
 
-                Self { flat: Ok(flat), first_pass, hidden }
+                Self { flat: Ok(flat), first_pass, hidden, sibling: false, item: None }
             }
-            Err(err) => Self { flat: Err(err), first_pass: false, hidden: false },
+            Err(err) => Self { flat: Err(err), first_pass: false, hidden: false, sibling: false, item: None },
         };
 
         Some(this)
     }
 
+    /// This entry will be yielded as Position::Entry (if the content processor makes an item of it)
+    fn is_sibling(&self, opts_immut: &WalkDirOptionsImmut) -> bool {
+        if self.hidden {
+            return false;
+        }
+
+        match self.flat {
+            Ok(ref flat) => flat.loop_link.is_none() || opts_immut.yield_loop_links,
+            Err(_) => false,
+        }
+    }
+
     fn can_be_yielded(&self) -> bool {
         if !self.hidden {
             return true;
@@ -86,7 +154,7 @@ impl<FS: fs::FsDirEntry> DirEntryRecord<FS> {
             return flat.is_dir;
         }
 
-        return false;
+        false
     }
 }
 
@@ -102,7 +170,7 @@ where
     /// Source of not consumed DirEntries
     rd: ReadDir<FS>,
     /// A list of already consumed DirEntries
-    content: Vec<DirEntryRecord<FS>>,
+    content: Vec<DirEntryRecord<FS, CP>>,
     /// Count of consumed entries = position of unconsumed in content
     current_pos: Option<usize>,
     _cp: std::marker::PhantomData<CP>,
@@ -185,8 +253,8 @@ where
             &mut FS::Context,
         ) -> Option<wd::ResultInner<FlatDirEntry<FS>, FS>>),
         ctx: &mut FS::Context,
-    ) -> Option<DirEntryRecord<FS>> {
-        let rec = DirEntryRecord::<FS>::new(r_rawdent, opts_immut, process_rawdent, ctx)?;
+    ) -> Option<DirEntryRecord<FS, CP>> {
+        let rec = DirEntryRecord::<FS, CP>::new(r_rawdent, opts_immut, process_rawdent, ctx)?;
 
         // if let Ok(ref mut dent) = rec.dent {
         //     dent.set_depth_mut( depth );
@@ -242,15 +310,16 @@ where
 
     /// Gets record at current position
     /// Doesn't change position.
-    pub fn get_current_rec(
-        &mut self,
+    pub fn get_current_rec<'r>(
+        &'r mut self,
         depth: Depth,
-    ) -> std::result::Result<FlatDirEntryRef<'_, FS, CP>, ErrorInnerRef<'_, FS>> {
+        parent: &'r mut DirParent<FS, CP>,
+    ) -> std::result::Result<FlatDirEntryRef<'r, FS, CP>, ErrorInnerRef<'r, FS>> {
         let pos = self.current_pos.unwrap();
         let rec = self.content.get_mut(pos).unwrap();
 
         match rec.flat {
-            Ok(ref mut flat) => Ok(FlatDirEntryRef::<FS, CP>::new(flat, depth, rec.hidden)),
+            Ok(ref mut flat) => Ok(FlatDirEntryRef::<FS, CP>::new(flat, &mut rec.item, depth, rec.hidden, parent)),
             Err(ref mut err) => Err(ErrorInnerRef::<FS>::new(err, depth)),
         }
     }
@@ -292,17 +361,69 @@ where
     //     self.content.iter().filter_map( f )
     // }
 
-    pub fn iter_content_flats<'s, F, T: 's>(
-        &'s mut self, 
-        f: F
-    ) -> impl Iterator<Item = &'s mut T>
-    where
-        F: FnMut(&mut FlatDirEntry<FS>) -> Option<&mut T>,
-    {
-        self.content
-            .iter_mut()
-            .filter_map(|rec: &mut DirEntryRecord<FS>| rec.flat.as_mut().ok())
-            .filter_map(f)
+    /// Counts all already loaded content.
+    /// Doesn't change position.
+    pub fn summary(&self) -> DirSummary {
+        let mut summary = DirSummary::default();
+        for rec in &self.content {
+            match rec.flat {
+                Ok(ref flat) if flat.is_dir => summary.dirs += 1,
+                Ok(_) => summary.files += 1,
+                Err(_) => summary.errors += 1,
+            }
+        }
+        summary
+    }
+
+    /// Marks all already loaded entries which will be yielded as Position::Entry
+    /// and returns their count (their items are kept for yielding).
+    /// Doesn't change position.
+    pub fn mark_siblings(
+        &mut self,
+        opts_immut: &WalkDirOptionsImmut,
+        content_processor: &CP,
+        parent: &mut DirParent<FS, CP>,
+        depth: Depth,
+        ctx: &mut FS::Context,
+    ) -> usize {
+        let mut count = 0;
+        for rec in self.content.iter_mut() {
+            rec.sibling = rec.is_sibling(opts_immut)
+                && match rec.flat {
+                    Ok(ref mut flat) => process_flat(flat, &mut rec.item, content_processor, parent, depth, ctx).is_ok(),
+                    Err(_) => false,
+                };
+            count += rec.sibling as usize;
+        }
+        count
+    }
+
+    /// Checks if record at current position will be yielded as Position::Entry.
+    /// Doesn't change position.
+    pub fn current_is_sibling(&self) -> bool {
+        match self.current_pos {
+            Some(pos) => self.content[pos].sibling,
+            None => false,
+        }
+    }
+
+    /// Collects copies of the items of all already loaded entries accepted by `keep`
+    /// (the items are kept for yielding). Doesn't change position.
+    pub fn clone_items(
+        &mut self,
+        keep: impl Fn(&FlatDirEntry<FS>) -> bool,
+        content_processor: &CP,
+        parent: &mut DirParent<FS, CP>,
+        depth: Depth,
+        ctx: &mut FS::Context,
+    ) -> CP::Collection {
+        let iter = self.content.iter_mut().filter_map(|rec| match rec.flat {
+            Ok(ref mut flat) if keep(flat) => {
+                process_flat(flat, &mut rec.item, content_processor, parent, depth, ctx).as_ref().ok().cloned()
+            }
+            _ => None,
+        });
+        content_processor.collect(iter)
     }
 }
 
@@ -315,10 +436,13 @@ where
     CP: ContentProcessor<FS>,
 {
     flat: &'r mut FlatDirEntry<FS>,
+    /// Item made before this entry was reached
+    item: &'r mut Option<ContentItem<FS, CP>>,
     depth: Depth,
     /// This entry will not be yielded according to opts.content_filter
     hidden: bool,
-    _cp: std::marker::PhantomData<CP>,
+    /// The dir containing this entry
+    parent: &'r mut DirParent<FS, CP>,
 }
 
 impl<'r, FS, CP> FlatDirEntryRef<'r, FS, CP>
@@ -326,23 +450,45 @@ where
     FS: fs::FsDirEntry,
     CP: ContentProcessor<FS>,
 {
-    fn new(flat: &'r mut FlatDirEntry<FS>, depth: Depth, hidden: bool) -> Self {
-        Self { flat, depth, hidden, _cp: std::marker::PhantomData }
+    fn new(
+        flat: &'r mut FlatDirEntry<FS>,
+        item: &'r mut Option<ContentItem<FS, CP>>,
+        depth: Depth,
+        hidden: bool,
+        parent: &'r mut DirParent<FS, CP>,
+    ) -> Self {
+        Self { flat, item, depth, hidden, parent }
     }
 
+    /// Makes the item of this entry (or takes the one made before)
     pub fn make_content_item (
         &mut self,
         content_processor: &CP,
         ctx: &mut FS::Context,
     ) -> Option<CP::Item> {
-        self.flat.raw.make_content_item( content_processor, self.flat.is_dir, self.depth, ctx )
+        match self.item.take() {
+            Some(Ok(item)) => Some(item),
+            Some(Err(err)) => {
+                *self.item = Some(Err(err));
+                None
+            }
+            None => self.flat.raw.make_content_item( content_processor, self.parent.context(), self.flat.is_dir, self.depth, ctx ),
+        }
+    }
+
+    /// Takes the error for which `make_content_item` made no item
+    pub fn take_error(&mut self, content_processor: &CP) -> Option<FS::Error> {
+        match self.item.take() {
+            Some(Err(err)) => err,
+            _ => content_processor.take_error(),
+        }
     }
 
     pub fn allow_push (
         &mut self,
         content_processor: &CP,
     ) -> bool {
-        self.flat.raw.allow_push( content_processor )
+        self.flat.raw.allow_push( content_processor, self.parent.context() )
     }
 
     pub fn as_flat(&self) -> &FlatDirEntry<FS> {
@@ -361,11 +507,52 @@ where
         self.flat.loop_link
     }
 
+    pub fn other_fs(&self) -> bool {
+        self.flat.other_fs
+    }
+
     pub fn path(&self) -> &FS::Path {
         self.flat.raw.path()
     }
 }
 
+/////////////////////////////////////////////////////////////////////////
+//// DirParent
+
+/// Processed item of an open dir and the content processor's scratch state for it
+pub struct DirParent<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
+    item: Option<CP::Item>,
+    scratch: CP::Scratch,
+}
+
+impl<FS, CP> DirParent<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
+    fn new(item: Option<CP::Item>) -> Self {
+        Self { item, scratch: CP::Scratch::default() }
+    }
+
+    fn context(&mut self) -> ParentContext<'_, CP::Item, CP::Scratch> {
+        ParentContext::new(self.item.as_ref(), &mut self.scratch)
+    }
+}
+
+impl<FS, CP> std::fmt::Debug for DirParent<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirParent").field("has_item", &self.item.is_some()).finish()
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////
 //// ErrorInnerRef
 
@@ -416,6 +603,18 @@ where
     pass: DirPass,
     /// Current position
     position: InnerPosition,
+    /// Remaining content was skipped
+    skipped: bool,
+    /// Skip remaining content after current entry
+    skip_after_current: bool,
+    /// Count of passed entries which are yielded as Position::Entry
+    siblings_passed: usize,
+    /// Count of all entries which are yielded as Position::Entry (only with opts.track_siblings)
+    siblings_count: usize,
+    /// Last-sibling flags of the current entry when it isn't (0) or is (1) the last one
+    last_flags: [LastFlags; 2],
+    /// Processed item of this dir and scratch state for its content
+    parent: DirParent<FS, CP>,
 
     /// Stub
     _cp: std::marker::PhantomData<CP>,
@@ -439,6 +638,8 @@ where
     ) {
         if let Some(cmp) = sorter {
             self.content.load_all_and_sort(opts_immut, cmp, process_rawdent, opened_count, ctx);
+        } else if opts_immut.track_siblings {
+            self.content.load_all(opts_immut, process_rawdent, opened_count, ctx);
         }
     }
 
@@ -460,6 +661,12 @@ where
             content: DirContent::<FS, CP>::new_once(raw)?,
             pass: get_initial_pass(opts_immut),
             position: InnerPosition::OpenDir,
+            skipped: false,
+            skip_after_current: false,
+            siblings_passed: 0,
+            siblings_count: 0,
+            last_flags: Default::default(),
+            parent: DirParent::new(None),
             _cp: std::marker::PhantomData,
         };
         this.init(opts_immut, sorter, process_rawdent, opened_count, ctx);
@@ -484,12 +691,55 @@ where
             content: DirContent::<FS, CP>::new(parent, opened_count, ctx)?,
             pass: get_initial_pass(opts_immut),
             position: InnerPosition::OpenDir,
+            skipped: false,
+            skip_after_current: false,
+            siblings_passed: 0,
+            siblings_count: 0,
+            last_flags: Default::default(),
+            parent: DirParent::new(None),
             _cp: std::marker::PhantomData,
         };
         this.init(opts_immut, sorter, process_rawdent, opened_count, ctx);
         this.into_ok()
     }
 
+    /// Sets processed item of this dir (it is passed to processing of its content)
+    pub fn set_parent_item(&mut self, item: Option<CP::Item>) {
+        self.parent.item = item;
+    }
+
+    /// Processed item of this dir (if the content processor made it)
+    pub fn parent_item(&self) -> Option<&CP::Item> {
+        self.parent.item.as_ref()
+    }
+
+    /// Takes the processed item of this dir (when it is closed)
+    pub fn take_parent_item(&mut self) -> Option<CP::Item> {
+        self.parent.item.take()
+    }
+
+    /// Counts the entries which will be yielded and prepares last-sibling flags
+    /// (only with opts.track_siblings, after the parent item is set).
+    /// `prefix` is the flags of the dir itself (`None` for the root).
+    pub fn init_siblings(
+        &mut self,
+        prefix: Option<&LastFlags>,
+        opts_immut: &WalkDirOptionsImmut,
+        content_processor: &CP,
+        ctx: &mut FS::Context,
+    ) {
+        self.siblings_count = self.content.mark_siblings(opts_immut, content_processor, &mut self.parent, self.depth, ctx);
+        if let Some(prefix) = prefix {
+            self.last_flags = [prefix.with(false), prefix.with(true)];
+        }
+    }
+
+    /// Makes the items of all loaded entries which will be yielded (they are kept until then).
+    /// Doesn't change position.
+    pub fn process_all(&mut self, opts_immut: &WalkDirOptionsImmut, content_processor: &CP, ctx: &mut FS::Context) {
+        self.content.mark_siblings(opts_immut, content_processor, &mut self.parent, self.depth, ctx);
+    }
+
     pub fn on_drop(&self, opened_count: &mut Depth) {
         self.content.on_drop( opened_count );
     }
@@ -536,6 +786,9 @@ where
                 };
 
                 if valid_pass && can_be_yielded {
+                    if self.content.current_is_sibling() {
+                        self.siblings_passed += 1;
+                    }
                     return true;
                 };
 
@@ -572,6 +825,12 @@ where
             return;
         };
 
+        if self.skip_after_current {
+            self.skip_after_current = false;
+            self.skip_all();
+            return;
+        };
+
         if self.shift_next(opts_immut, process_rawdent, opened_count, ctx) {
             // Remember: at this state current rec must exist
             self.position = InnerPosition::Entry;
@@ -589,7 +848,7 @@ where
             InnerPosition::OpenDir => InnerPositionWithData::OpenDir,
             InnerPosition::Entry => {
                 // At this state current rec must exist
-                match self.content.get_current_rec(self.depth) {
+                match self.content.get_current_rec(self.depth, &mut self.parent) {
                     Ok(flat) => InnerPositionWithData::Entry(flat),
                     Err(err) => InnerPositionWithData::Error(err),
                 }
@@ -614,39 +873,54 @@ where
     ) -> CP::Collection {
         self.content.load_all(opts_immut, process_rawdent, opened_count, ctx);
 
-        let depth = self.depth();
-
-        match filter {
-            ContentFilter::None => {
-                let iter = self
-                    .content
-                    .iter_content_flats(|flat| Some(flat))
-                    .filter_map(|flat| flat.raw.make_content_item( content_processor, flat.is_dir, depth, ctx ));
-                content_processor.collect(iter)
-            }
-            ContentFilter::DirsOnly => {
-                let iter = self
-                    .content
-                    .iter_content_flats(|flat| if flat.is_dir { Some(flat) } else { None })
-                    .filter_map(|flat| flat.raw.make_content_item( content_processor, flat.is_dir, depth, ctx ));
-                content_processor.collect(iter)
-            }
-            ContentFilter::FilesOnly => {
-                let iter = self
-                    .content
-                    .iter_content_flats(|flat| if !flat.is_dir { Some(flat) } else { None })
-                    .filter_map(|flat| flat.raw.make_content_item( content_processor, flat.is_dir, depth, ctx ));
-                content_processor.collect(iter)
-            }
-            ContentFilter::SkipAll => CP::empty_collection(),
-        }
+        let keep = |flat: &FlatDirEntry<FS>| match filter {
+            ContentFilter::None => true,
+            ContentFilter::DirsOnly => flat.is_dir,
+            ContentFilter::FilesOnly => !flat.is_dir,
+            ContentFilter::SkipAll => false,
+        };
+        self.content.clone_items(keep, content_processor, &mut self.parent, self.depth, ctx)
     }
 
     pub fn depth(&self) -> Depth {
         self.depth
     }
 
+    /// Gets summary of already read content.
+    /// Doesn't change position.
+    pub fn summary(&self) -> DirSummary {
+        DirSummary { skipped: self.skipped, ..self.content.summary() }
+    }
+
     pub fn skip_all(&mut self) {
+        if self.position != InnerPosition::CloseDir {
+            self.skipped = true;
+        }
         self.position = InnerPosition::CloseDir;
     }
+
+    /// Gets index of current entry among siblings and count of siblings.
+    /// Doesn't change position.
+    pub fn sibling_position(&self) -> (usize, usize) {
+        (self.siblings_passed.saturating_sub(1), self.siblings_count)
+    }
+
+    /// Checks if current entry is the last sibling.
+    /// Doesn't change position.
+    pub fn current_is_last_sibling(&self) -> bool {
+        self.siblings_passed >= self.siblings_count
+    }
+
+    /// Gets last-sibling flags of current entry (see SiblingInfo::last).
+    /// Doesn't change position.
+    pub fn current_last_flags(&self) -> &LastFlags {
+        &self.last_flags[self.current_is_last_sibling() as usize]
+    }
+
+    /// Skips remaining content after current entry is processed.
+    pub fn skip_after_current(&mut self) {
+        if self.position != InnerPosition::CloseDir {
+            self.skip_after_current = true;
+        }
+    }
 }
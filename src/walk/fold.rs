@@ -0,0 +1,110 @@
+use crate::cp::ContentProcessor;
+use crate::fs;
+use crate::walk::iter::WalkDirIter;
+use crate::walk::walk::WalkDirIteratorItem;
+use crate::wd::{self, Position};
+
+/////////////////////////////////////////////////////////////////////////
+//// FoldTree
+
+/// A bottom-up aggregation over the dirs tree.
+///
+/// Values of this type are created by calling [`.fold_tree()`] on any
+/// `WalkDirIter`.
+///
+/// The `leaf` function is applied to every yielded non-dir entry. When a dir
+/// is closed, the `combine` function receives this dir and the values of all
+/// its children (leaf values of its files and combined values of its subdirs)
+/// and its result is yielded together with the dir.
+///
+/// Errors are passed through as is. Entries which are not yielded by the
+/// underlying iterator (filtered out, hidden by `min_depth` or by content
+/// filter) do not contribute to the aggregated value. Dirs which are not
+/// descended into (e.g. at `max_depth`) are not combined either.
+///
+/// [`.fold_tree()`]: trait.WalkDirIter.html#method.fold_tree
+#[derive(Debug)]
+pub struct FoldTree<FS, CP, I, L, C, T>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    I: Iterator<Item = WalkDirIteratorItem<FS, CP>> + WalkDirIter<FS, CP>,
+    L: FnMut(&CP::Item) -> T,
+    C: FnMut(&CP::Item, Vec<T>) -> T,
+    T: Clone,
+{
+    inner: I,
+    leaf: L,
+    combine: C,
+    /// Collected children values of each opened dir
+    stack: Vec<Vec<T>>,
+    _cp: std::marker::PhantomData<CP>,
+}
+
+impl<FS, CP, I, L, C, T> FoldTree<FS, CP, I, L, C, T>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    I: Iterator<Item = WalkDirIteratorItem<FS, CP>> + WalkDirIter<FS, CP>,
+    L: FnMut(&CP::Item) -> T,
+    C: FnMut(&CP::Item, Vec<T>) -> T,
+    T: Clone,
+{
+    pub(crate) fn new(inner: I, leaf: L, combine: C) -> Self {
+        Self { inner, leaf, combine, stack: vec![], _cp: std::marker::PhantomData }
+    }
+
+    /// Skip all remaining content of current dir.
+    ///
+    /// The dir will still be combined from the values collected so far.
+    pub fn skip_current_dir(&mut self) {
+        self.inner.skip_current_dir();
+    }
+}
+
+impl<FS, CP, I, L, C, T> Iterator for FoldTree<FS, CP, I, L, C, T>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    I: Iterator<Item = WalkDirIteratorItem<FS, CP>> + WalkDirIter<FS, CP>,
+    L: FnMut(&CP::Item) -> T,
+    C: FnMut(&CP::Item, Vec<T>) -> T,
+    T: Clone,
+{
+    type Item = wd::Result<(CP::Item, T), FS>;
+
+    /// Advances the iterator and returns the next value.
+    ///
+    /// # Errors
+    ///
+    /// If the iterator fails to retrieve the next value, this method returns
+    /// an error value. The error will be wrapped in an `Option::Some`.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let dent = match self.inner.next()? {
+                Position::OpenDir(_) | Position::OpenDirWithContent(_, _) => {
+                    self.stack.push(vec![]);
+                    continue;
+                }
+                Position::Entry(dent) => {
+                    if !CP::is_dir(&dent) {
+                        if let Some(children) = self.stack.last_mut() {
+                            children.push((self.leaf)(&dent));
+                        }
+                    }
+                    continue;
+                }
+                Position::Error(err) => return Some(Err(err)),
+                Position::CloseDir(dent) | Position::CloseDirWithSummary(dent, _) => dent,
+            };
+
+            let children = self.stack.pop().unwrap_or_default();
+            let value = (self.combine)(&dent, children);
+            if let Some(parent) = self.stack.last_mut() {
+                parent.push(value.clone());
+            }
+
+            return Some(Ok((dent, value)));
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use crate::walk::classic_iter::ClassicIter;
+use crate::walk::collect::CollectErrors;
+use crate::walk::fold::FoldTree;
 use crate::cp::ContentProcessor;
 use crate::fs;
 use crate::walk::walk::{WalkDirIterator, WalkDirIteratorItem};
@@ -73,6 +75,65 @@ where
     fn into_classic(self) -> ClassicIter<FS, CP, Self> {
         ClassicIter::<FS, CP, Self>::new(self)
     }
+
+    /// Aggregates values over the dirs tree from bottom to top.
+    ///
+    /// `leaf` is called for each non-dir entry, and `combine` is called for
+    /// each dir when it is closed, with the values of all its children. The
+    /// combined value is yielded together with the dir (so the root dir is
+    /// yielded last).
+    ///
+    /// For example, to count files in each dir (including subdirs):
+    ///
+    /// ```no_run
+    /// use walkdir2::{WalkDir, WalkDirIter};
+    /// # use walkdir2::Error;
+    ///
+    /// # fn try_main() -> Result<(), Error> {
+    /// let counts = WalkDir::new("foo")
+    ///     .into_iter()
+    ///     .fold_tree(|_| 1usize, |_, children| children.into_iter().sum());
+    /// for result in counts {
+    ///     let (dir, count) = result?;
+    ///     println!("{}: {}", dir.path().display(), count);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See [`FoldTree`] for details.
+    ///
+    /// [`FoldTree`]: struct.FoldTree.html
+    fn fold_tree<L, C, T>(self, leaf: L, combine: C) -> FoldTree<FS, CP, Self, L, C, T>
+    where
+        L: FnMut(&CP::Item) -> T,
+        C: FnMut(&CP::Item, Vec<T>) -> T,
+        T: Clone,
+    {
+        FoldTree::new(self, leaf, combine)
+    }
+
+    /// Passes entries through and accumulates errors into a report.
+    ///
+    /// Errors are not yielded, use [`CollectErrors::report`] at the end of
+    /// the walk to get them grouped by kind and by top-level subtree:
+    ///
+    /// ```no_run
+    /// use walkdir2::{Position, WalkDir, WalkDirIter};
+    ///
+    /// let mut it = WalkDir::new("foo").into_iter().collect_errors();
+    /// for pos in &mut it {
+    ///     if let Position::Entry(entry) = pos {
+    ///         println!("{}", entry.path().display());
+    ///     }
+    /// }
+    /// eprint!("{}", it.report());
+    /// ```
+    ///
+    /// [`CollectErrors::report`]: struct.CollectErrors.html#method.report
+    fn collect_errors(self) -> CollectErrors<FS, CP, Self> {
+        CollectErrors::new(self)
+    }
 }
 
 impl<FS, CP> WalkDirIter<FS, CP> for WalkDirIterator<FS, CP>
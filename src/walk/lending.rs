@@ -0,0 +1,349 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::error::{into_path_err, Error, Operation};
+use crate::fs::{self, FileKind, FsDirEntry, FsFileType, FsRootDirEntry, ReadDirNames};
+use crate::walk::ancestor::{check_loop, is_same_file_system, make_loop_error, Ancestor};
+use crate::walk::opts::WalkDirOptionsImmut;
+use crate::walk::rawdent::RawDirEntry;
+use crate::wd::{self, ContentFilter, Depth};
+
+type Fs = fs::DefaultDirEntry;
+type FsRoot = <Fs as FsDirEntry>::RootDirEntry;
+type Context = <Fs as FsDirEntry>::Context;
+type DeviceNum = <Fs as FsDirEntry>::DeviceNum;
+
+/////////////////////////////////////////////////////////////////////////
+//// LendingEntry
+
+/// An entry yielded by [`LendingWalk`]. Its path is borrowed from the
+/// walker's shared path buffer, so it is valid until the next call of
+/// [`LendingWalk::next_entry`].
+///
+/// [`LendingWalk`]: struct.LendingWalk.html
+/// [`LendingWalk::next_entry`]: struct.LendingWalk.html#method.next_entry
+#[derive(Debug, Clone, Copy)]
+pub struct LendingEntry<'a> {
+    path: &'a Path,
+    file_type: FileKind,
+    is_dir: bool,
+    depth: Depth,
+}
+
+impl<'a> LendingEntry<'a> {
+    /// The full path that this entry represents (see `DirEntry::path`).
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    /// Return the file name of this entry (borrowed from the path).
+    pub fn file_name(&self) -> &'a OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    /// Return the file type for the file that this entry points to.
+    ///
+    /// If this is a symbolic link and [`follow_links`] is `true`, then this
+    /// returns the type of the target. The type is taken from the dir listing
+    /// when the OS reports it there, so usually no metadata is read.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    pub fn file_type(&self) -> FileKind {
+        self.file_type
+    }
+
+    /// Returns true if this entry is a dir which is (or was) descended into.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns the depth at which this entry was created relative to the root.
+    pub fn depth(&self) -> Depth {
+        self.depth
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// LendingWalk
+
+/// An open dir
+#[derive(Debug)]
+struct LendingDir {
+    /// `None` when the dir is exhausted (or skipped, or failed to open)
+    names: Option<ReadDirNames>,
+    /// File type of the dir itself
+    file_type: FileKind,
+    /// Depth of the dir itself
+    depth: Depth,
+    /// The dir is in the ancestors (only with opts.follow_links)
+    is_ancestor: bool,
+}
+
+/// A dir which is found and will be opened by the next call
+#[derive(Debug)]
+struct PendingDir {
+    file_type: FileKind,
+    depth: Depth,
+    /// Only with opts.follow_links
+    ancestor: Option<Ancestor<Fs>>,
+}
+
+/// A walker which doesn't allocate a path for each entry: all entries share
+/// one path buffer (parent prefix + name), and each one is lent until the
+/// next call of [`next_entry`] (see `WalkDirBuilder::into_lending`).
+///
+/// These options are taken into account: `min_depth`, `max_depth`,
+/// `follow_links`, `yield_loop_links`, `same_file_system`, `contents_first`
+/// and `content_filter`. Options which only affect positions (sibling
+/// tracking, `yield_open_dir_with_content` etc.) have no effect, and each
+/// open dir keeps its handle (`max_open` isn't applied).
+///
+/// ```no_run
+/// use walkdir2::WalkDir;
+///
+/// let mut walk = WalkDir::new("foo").into_lending().unwrap();
+/// while let Some(entry) = walk.next_entry() {
+///     println!("{}", entry.unwrap().path().display());
+/// }
+/// ```
+///
+/// [`next_entry`]: struct.LendingWalk.html#method.next_entry
+#[derive(Debug)]
+pub struct LendingWalk {
+    opts: WalkDirOptionsImmut,
+    ctx: Context,
+    /// Root (until the first call)
+    root: Option<PathBuf>,
+    /// Shared path buffer
+    path: PathBuf,
+    /// Open dirs (from the root)
+    stack: Vec<LendingDir>,
+    /// The buffer ends with the name of an entry which isn't an open dir
+    has_name: bool,
+    /// The last entry is a dir which will be opened
+    pending: Option<PendingDir>,
+    /// The last entry is a dir yielded after its content (with opts.contents_first)
+    yielded_closed: bool,
+    /// Only with opts.same_file_system
+    root_device: Option<DeviceNum>,
+    /// Open dirs (only with opts.follow_links)
+    ancestors: Vec<Ancestor<Fs>>,
+}
+
+impl LendingWalk {
+    pub(crate) fn new(opts: WalkDirOptionsImmut, ctx: Context, root: PathBuf) -> Self {
+        Self {
+            opts,
+            ctx,
+            root: Some(root),
+            path: PathBuf::new(),
+            stack: vec![],
+            has_name: false,
+            pending: None,
+            yielded_closed: false,
+            root_device: None,
+            ancestors: vec![],
+        }
+    }
+
+    /// Advances the walk and lends the next entry.
+    pub fn next_entry(&mut self) -> Option<wd::Result<LendingEntry<'_>, Fs>> {
+        self.yielded_closed = false;
+        if let Some(root) = self.root.take() {
+            self.path = root;
+            match self.open_root() {
+                Ok(Some((file_type, is_dir))) => return Some(Ok(self.entry(file_type, is_dir, 0))),
+                Ok(None) => {}
+                Err(err) => return Some(Err(Error::from_inner(err, 0))),
+            }
+        }
+
+        loop {
+            if let Some(pending) = self.pending.take() {
+                let (names, result) = match ReadDirNames::open(&self.path) {
+                    Ok(names) => (Some(names), Ok(())),
+                    Err(err) => (None, Err(into_path_err::<Fs, _>(&self.path, Operation::ReadDir, err))),
+                };
+                let is_ancestor = pending.ancestor.is_some();
+                if let Some(ancestor) = pending.ancestor {
+                    self.ancestors.push(ancestor);
+                }
+                self.stack.push(LendingDir { names, file_type: pending.file_type, depth: pending.depth, is_ancestor });
+                self.has_name = false;
+                match result {
+                    Ok(()) => continue,
+                    Err(err) => return Some(Err(Error::from_inner(err, pending.depth))),
+                }
+            }
+
+            if self.has_name {
+                self.path.pop();
+                self.has_name = false;
+            }
+
+            let top = self.stack.last_mut()?;
+            let depth = top.depth + 1;
+            let path = &mut self.path;
+
+            match top.names.as_mut().and_then(|names| names.next_into(path)) {
+                None => {
+                    // Now the buffer holds the path of the closed dir
+                    let dir = self.stack.pop().unwrap();
+                    if dir.is_ancestor {
+                        self.ancestors.pop();
+                    }
+                    self.has_name = dir.depth > 0;
+                    if self.opts.contents_first && self.allow_yield(true, dir.depth) {
+                        self.yielded_closed = true;
+                        return Some(Ok(self.entry(dir.file_type, true, dir.depth)));
+                    }
+                }
+                Some(Err(err)) => {
+                    top.names = None;
+                    let err = into_path_err::<Fs, _>(&self.path, Operation::NextEntry, err);
+                    return Some(Err(Error::from_inner(err, depth)));
+                }
+                Some(Ok(kind)) => {
+                    self.has_name = true;
+
+                    match self.process_entry(kind, depth) {
+                        Ok(Some((file_type, is_dir))) => return Some(Ok(self.entry(file_type, is_dir, depth))),
+                        Ok(None) => {}
+                        Err(err) => return Some(Err(Error::from_inner(err, depth))),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Skips the content of the most recently yielded dir (if it is going to
+    /// be descended into), or the rest of its parent dir otherwise. Does
+    /// nothing after a dir yielded in `contents_first` mode, since its content
+    /// was already yielded (as `WalkDirIterator::skip_current_dir` does).
+    pub fn skip_current_dir(&mut self) {
+        if self.yielded_closed || self.pending.take().is_some() {
+            return;
+        }
+        if let Some(top) = self.stack.last_mut() {
+            top.names = None;
+        }
+    }
+
+    /// Reads the root. Returns its file type if it must be yielded.
+    fn open_root(&mut self) -> wd::ResultInner<Option<(FileKind, bool)>, Fs> {
+        let root = RawDirEntry::<Fs>::from_path(&self.path, &mut self.ctx)?;
+        let mut file_type = FileKind::from(root.file_type());
+        // The root is always followed (see WalkDirIterator)
+        let is_dir = if root.is_symlink() {
+            let target = FileKind::from(root.file_type_follow(&mut self.ctx)?);
+            if self.opts.follow_links {
+                file_type = target;
+            }
+            target.is_dir()
+        } else {
+            file_type.is_dir()
+        };
+
+        if self.opts.same_file_system {
+            self.root_device = Some(root.device_num(&mut self.ctx)?);
+        }
+
+        if is_dir && self.opts.max_depth > 0 {
+            let ancestor = if self.opts.follow_links { Some(Ancestor::new(&root, &mut self.ctx)?) } else { None };
+            self.pending = Some(PendingDir { file_type, depth: 0, ancestor });
+            if self.opts.contents_first {
+                return Ok(None);
+            }
+        }
+
+        if self.allow_yield(is_dir, 0) {
+            Ok(Some((file_type, is_dir)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Processes an entry (its path is in the buffer). Returns its file type if it must be yielded.
+    fn process_entry(
+        &mut self,
+        kind: Option<FileKind>,
+        depth: Depth,
+    ) -> wd::ResultInner<Option<(FileKind, bool)>, Fs> {
+        let mut file_type = match kind {
+            Some(kind) => kind,
+            None => self.file_type(false)?,
+        };
+        let is_link = file_type.is_symlink();
+        if is_link && self.opts.follow_links {
+            file_type = self.file_type(true)?;
+        }
+
+        let is_dir = file_type.is_dir();
+        let mut descend = is_dir;
+        // Dirs are checked the same way as WalkDirIterator does (the checks need a raw entry)
+        let raw = if is_dir && (self.opts.same_file_system || self.opts.follow_links) {
+            Some(RawDirEntry::<Fs>::from_path(&self.path, &mut self.ctx)?)
+        } else {
+            None
+        };
+
+        if let (Some(raw), Some(root_device)) = (&raw, &self.root_device) {
+            if !is_same_file_system(root_device, raw, &mut self.ctx)? {
+                // Yield it as a dir, but don't descend into it
+                descend = false;
+            }
+        }
+
+        if descend && depth < self.opts.max_depth {
+            let loop_depth = match raw {
+                Some(ref raw) if is_link && self.opts.follow_links => check_loop(raw, &self.ancestors, &mut self.ctx)?,
+                _ => None,
+            };
+
+            if let Some(loop_depth) = loop_depth {
+                if !self.opts.yield_loop_links {
+                    return Err(make_loop_error(&self.ancestors, loop_depth, &self.path));
+                }
+            } else {
+                let ancestor = match raw {
+                    Some(ref raw) if self.opts.follow_links => Some(Ancestor::new(raw, &mut self.ctx)?),
+                    _ => None,
+                };
+                self.pending = Some(PendingDir { file_type, depth, ancestor });
+                if self.opts.contents_first {
+                    return Ok(None);
+                }
+            }
+        }
+
+        if self.allow_yield(is_dir, depth) {
+            Ok(Some((file_type, is_dir)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn allow_yield(&self, is_dir: bool, depth: Depth) -> bool {
+        let allowed = match self.opts.content_filter {
+            ContentFilter::None => true,
+            ContentFilter::DirsOnly => is_dir,
+            ContentFilter::FilesOnly => !is_dir,
+            ContentFilter::SkipAll => false,
+        };
+        allowed && depth >= self.opts.min_depth
+    }
+
+    fn entry(&self, file_type: FileKind, is_dir: bool, depth: Depth) -> LendingEntry<'_> {
+        LendingEntry { path: &self.path, file_type, is_dir, depth }
+    }
+
+    /// Reads the file type of the entry in the buffer (when the dir listing has none)
+    fn file_type(&mut self, follow_link: bool) -> wd::ResultInner<FileKind, Fs> {
+        let op = if follow_link { Operation::FollowLink } else { Operation::Metadata };
+        let file_type = match FsRoot::from_path(&self.path, &mut self.ctx) {
+            Ok(root) => root.file_type(follow_link, &mut self.ctx),
+            Err(err) => Err(err),
+        };
+        file_type.map(FileKind::from).map_err(|err| into_path_err::<Fs, _>(&self.path, op, err))
+    }
+}
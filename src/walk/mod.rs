@@ -1,12 +1,21 @@
 mod rawdent;
+mod ancestor;
 mod opts;
 mod dir;
 mod walk;
 mod iter;
 mod classic_iter;
+mod fold;
+mod collect;
+mod visit;
+mod lending;
 
 pub use rawdent::{RawDirEntry, ReadDir};
-pub use opts::{WalkDirBuilder, WalkDirOptions, WalkDirOptionsImmut};
+pub use opts::{WalkDirBuilder, WalkDirConfig, WalkDirOptions, WalkDirOptionsImmut};
 pub use walk::{WalkDirIterator, WalkDirIteratorItem};
 pub use iter::{FilterEntry, WalkDirIter};
+pub use fold::FoldTree;
+pub use collect::{CollectErrors, ErrorReport, ErrorReportDetails};
+pub use visit::{WalkControl, WalkVisitor};
+pub use lending::{LendingEntry, LendingWalk};
 pub use classic_iter::{ClassicFilterEntry, ClassicIter, ClassicWalkDirIter};
\ No newline at end of file
@@ -4,15 +4,19 @@ use std::result;
 use crate::cp::{self, ContentProcessor};
 use crate::fs::{self, FsPath};
 //use crate::fs::FsPath;
-use crate::wd::{ContentFilter, ContentOrder, Depth, FnCmp};
+use crate::error::Error;
+use crate::wd::{ContentFilter, ContentOrder, Depth, ErrorAction, FnCmp, FnErrorPolicy};
 use crate::walk::walk::{WalkDirIterator, WalkDirIteratorItem};
 use crate::walk::iter::{WalkDirIter};
 use crate::walk::classic_iter::ClassicIter;
+use crate::walk::lending::LendingWalk;
+use crate::walk::visit::{self, WalkControl, WalkVisitor};
 
 /////////////////////////////////////////////////////////////////////////
 //// WalkDirOptions
 
 /// Immutable options
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct WalkDirOptionsImmut
 {
     /// Check for same filesystem
@@ -37,6 +41,10 @@ pub struct WalkDirOptionsImmut
     pub yield_open_dir_with_content: bool,
     /// Filter content yielded in Position::OpenDir (in Position::Entry(...))
     pub open_dir_with_content_filter: ContentFilter,
+    /// Yield Position::CloseDirWithSummary(dir, summary) -- otherwise Position::CloseDir(dir) will be yielded
+    pub yield_close_dir_with_summary: bool,
+    /// Attach position among siblings to yielded entries (requires loading all content of each dir)
+    pub track_siblings: bool,
 }
 
 impl Default for WalkDirOptionsImmut {
@@ -53,10 +61,134 @@ impl Default for WalkDirOptionsImmut {
             content_order: ContentOrder::None,
             yield_open_dir_with_content: false,
             open_dir_with_content_filter: ContentFilter::None,
+            yield_close_dir_with_summary: false,
+            track_siblings: false,
         }
     }
 }
 
+/////////////////////////////////////////////////////////////////////////
+//// WalkDirConfig
+
+/// Serializable form of the immutable options (e.g. for config files).
+///
+/// Unlike [`WalkDirOptionsImmut`], this is a stable format: it carries a
+/// `version`, unknown fields are rejected and missing ones get their default
+/// values. Load it with serde (the `serde` feature) and pass it to
+/// [`WalkDirBuilder::with_config`]:
+///
+/// ```toml
+/// version = 1
+/// max_depth = 3
+/// follow_links = true
+/// ```
+///
+/// [`WalkDirOptionsImmut`]: struct.WalkDirOptionsImmut.html
+/// [`WalkDirBuilder::with_config`]: struct.WalkDirBuilder.html#method.with_config
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+#[non_exhaustive]
+pub struct WalkDirConfig {
+    /// Version of the format, always `WalkDirConfig::VERSION`
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_config_version"))]
+    pub version: u32,
+    /// See `WalkDirBuilder::same_file_system`
+    pub same_file_system: bool,
+    /// See `WalkDirBuilder::follow_links`
+    pub follow_links: bool,
+    /// See `WalkDirBuilder::yield_loop_links`
+    pub yield_loop_links: bool,
+    /// See `WalkDirBuilder::max_open` (`None` for no limit)
+    pub max_open: Option<Depth>,
+    /// See `WalkDirBuilder::min_depth`
+    pub min_depth: Depth,
+    /// See `WalkDirBuilder::max_depth`
+    pub max_depth: Depth,
+    /// See `WalkDirBuilder::contents_first`
+    pub contents_first: bool,
+    /// See `WalkDirBuilder::content_filter`
+    pub content_filter: ContentFilter,
+    /// See `WalkDirBuilder::content_order`
+    pub content_order: ContentOrder,
+    /// See `WalkDirBuilder::yield_open_dir_with_content`
+    pub yield_open_dir_with_content: bool,
+    /// See `WalkDirBuilder::open_dir_with_content_filter`
+    pub open_dir_with_content_filter: ContentFilter,
+    /// See `WalkDirBuilder::yield_close_dir_with_summary`
+    pub yield_close_dir_with_summary: bool,
+    /// See `WalkDirBuilder::track_siblings`
+    pub track_siblings: bool,
+}
+
+impl WalkDirConfig {
+    /// The only supported version of the format
+    pub const VERSION: u32 = 1;
+}
+
+impl Default for WalkDirConfig {
+    fn default() -> Self {
+        Self::from(&WalkDirOptionsImmut::default())
+    }
+}
+
+impl<'a> From<&'a WalkDirOptionsImmut> for WalkDirConfig {
+    fn from(opts: &'a WalkDirOptionsImmut) -> Self {
+        Self {
+            version: Self::VERSION,
+            same_file_system: opts.same_file_system,
+            follow_links: opts.follow_links,
+            yield_loop_links: opts.yield_loop_links,
+            max_open: opts.max_open,
+            min_depth: opts.min_depth,
+            max_depth: opts.max_depth,
+            contents_first: opts.contents_first,
+            content_filter: opts.content_filter,
+            content_order: opts.content_order,
+            yield_open_dir_with_content: opts.yield_open_dir_with_content,
+            open_dir_with_content_filter: opts.open_dir_with_content_filter,
+            yield_close_dir_with_summary: opts.yield_close_dir_with_summary,
+            track_siblings: opts.track_siblings,
+        }
+    }
+}
+
+impl<'a> From<&'a WalkDirConfig> for WalkDirOptionsImmut {
+    fn from(config: &'a WalkDirConfig) -> Self {
+        Self {
+            same_file_system: config.same_file_system,
+            follow_links: config.follow_links,
+            yield_loop_links: config.yield_loop_links,
+            max_open: config.max_open.filter(|&n| n > 0),
+            min_depth: std::cmp::min(config.min_depth, config.max_depth),
+            max_depth: config.max_depth,
+            contents_first: config.contents_first,
+            content_filter: config.content_filter,
+            content_order: config.content_order,
+            yield_open_dir_with_content: config.yield_open_dir_with_content,
+            open_dir_with_content_filter: config.open_dir_with_content_filter,
+            yield_close_dir_with_summary: config.yield_close_dir_with_summary,
+            track_siblings: config.track_siblings,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_config_version<'de, D: serde::Deserializer<'de>>(deserializer: D) -> result::Result<u32, D::Error> {
+    let version = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+    if version != WalkDirConfig::VERSION {
+        return Err(serde::de::Error::custom(format_args!(
+            "unsupported config version {} (expected {})",
+            version,
+            WalkDirConfig::VERSION
+        )));
+    }
+    Ok(version)
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// WalkDirOptions
+
 /// Options for WalkDir
 pub struct WalkDirOptions<E, CP>
 where
@@ -67,6 +199,8 @@ where
     pub immut: WalkDirOptionsImmut,
     /// Sorter object
     pub sorter: Option<FnCmp<E>>,
+    /// Error policy
+    pub error_policy: Option<FnErrorPolicy<E>>,
     /// Content processor
     pub content_processor: CP,
     /// The fs context
@@ -83,6 +217,7 @@ where
         Self {
             immut: WalkDirOptionsImmut::default(),
             sorter: None,
+            error_policy: None,
             content_processor: CP::default(),
             ctx: E::Context::default(), 
         }
@@ -102,6 +237,7 @@ where
         Self {
             immut: WalkDirOptionsImmut::default(),
             sorter: None,
+            error_policy: None,
             content_processor,
             ctx, 
         }
@@ -120,6 +256,12 @@ where
         } else {
             "None"
         };
+        let error_policy_str = if self.error_policy.is_some() {
+            // FnMut isn't `Debug`
+            "Some(...)"
+        } else {
+            "None"
+        };
         f.debug_struct("WalkDirOptions")
             .field("same_file_system", &self.immut.same_file_system)
             .field("follow_links", &self.immut.follow_links)
@@ -135,7 +277,13 @@ where
                 &self.immut.yield_open_dir_with_content,
             )
             .field("open_dir_with_content_filter", &self.immut.open_dir_with_content_filter)
+            .field(
+                "yield_close_dir_with_summary",
+                &self.immut.yield_close_dir_with_summary,
+            )
+            .field("track_siblings", &self.immut.track_siblings)
             .field("sorter", &sorter_str)
+            .field("error_policy", &error_policy_str)
             .field("content_processor", &self.content_processor)
             .field("ctx", &self.ctx)
             .finish()
@@ -274,6 +422,19 @@ where
         self.into_iter().into_classic()
     }
 
+    /// Walks the tree, calling the visitor methods for each position.
+    ///
+    /// Returns `WalkControl::Stop` if the walk was stopped by the visitor,
+    /// `WalkControl::Continue` otherwise. See [`WalkVisitor`] for details.
+    ///
+    /// [`WalkVisitor`]: trait.WalkVisitor.html
+    pub fn visit<V>(self, visitor: &mut V) -> WalkControl
+    where
+        V: WalkVisitor<FS, CP> + ?Sized,
+    {
+        visit::visit(self.build(), visitor)
+    }
+
     /// Do not cross file system boundaries.
     ///
     /// When this option is enabled, directory traversal will not descend into
@@ -400,6 +561,46 @@ where
         self
     }
 
+    /// Set a function which decides what to do with each error.
+    ///
+    /// The function gets the error and the count of retries already made for
+    /// the failed operation, and returns an [`ErrorAction`]: yield the error
+    /// (the default without a policy), skip it silently, retry the operation
+    /// after a delay or abort the walk.
+    ///
+    /// Only opening of dirs can be retried: any error of the root, and errors
+    /// of `Operation::ReadDir` and `Operation::Fingerprint` of other dirs.
+    /// Other errors (e.g. `Operation::NextEntry`, `Operation::Metadata` or
+    /// `Operation::FollowLink`) come from reading the content of a dir, which
+    /// may be done long before they are yielded, so `Retry` is rejected for
+    /// them: the error is yielded at once, as with `Yield`, and the function
+    /// is called for it only once.
+    ///
+    /// ```rust,no_run
+    /// use std::io;
+    /// use std::time::Duration;
+    /// use walkdir2::{ErrorAction, WalkDir};
+    ///
+    /// WalkDir::new("foo").error_policy(|err, attempt| {
+    ///     match err.io_error().map(|err| err.kind()) {
+    ///         Some(io::ErrorKind::NotFound) => ErrorAction::Skip,
+    ///         Some(io::ErrorKind::Interrupted) | Some(io::ErrorKind::WouldBlock) if attempt < 3 => {
+    ///             ErrorAction::Retry(Duration::from_millis(100 << attempt))
+    ///         }
+    ///         _ => ErrorAction::Yield,
+    ///     }
+    /// }).into_classic();
+    /// ```
+    ///
+    /// [`ErrorAction`]: enum.ErrorAction.html
+    pub fn error_policy<F>(mut self, policy: F) -> Self
+    where
+        F: FnMut(&Error<FS>, usize) -> ErrorAction + Send + Sync + 'static,
+    {
+        self.opts.error_policy = Some(Box::new(policy));
+        self
+    }
+
     /// Yield a directory's contents before the directory itself. By default,
     /// this is disabled.
     ///
@@ -494,6 +695,103 @@ where
         self
     }
 
+    /// Set yield_close_dir_with_summary flag.
+    ///
+    /// When `yes` is `true`, the iterator yields
+    /// `Position::CloseDirWithSummary(dir, summary)` instead of
+    /// `Position::CloseDir(dir)`, where `summary` counts the files, dirs and
+    /// errors read from this dir.
+    pub fn yield_close_dir_with_summary(mut self, yes: bool) -> Self {
+        self.opts.immut.yield_close_dir_with_summary = yes;
+        self
+    }
+
+    /// Set track_siblings flag.
+    ///
+    /// When `yes` is `true`, each yielded entry gets its position among its
+    /// siblings (see [`SiblingInfo`]), which is enough to draw `tree`-like
+    /// connectors. This requires loading all content of each dir before
+    /// yielding its first entry (as sorting does).
+    ///
+    /// Only entries which the walker yields are counted (i.e. not hidden by
+    /// the content filter nor dropped by the content processor). Entries
+    /// removed later by iterator adapters such as `filter_entry` are counted.
+    ///
+    /// [`SiblingInfo`]: struct.SiblingInfo.html
+    pub fn track_siblings(mut self, yes: bool) -> Self {
+        self.opts.immut.track_siblings = yes;
+        self
+    }
+
+    /// Replace all immutable options at once (e.g. loaded from a config file).
+    ///
+    /// Sorter, error policy, content processor and fs context are kept.
+    pub fn with_config(mut self, config: &WalkDirConfig) -> Self {
+        self.opts.immut = WalkDirOptionsImmut::from(config);
+        self
+    }
+
+    /// Current immutable options in their serializable form
+    pub fn config(&self) -> WalkDirConfig {
+        WalkDirConfig::from(&self.opts.immut)
+    }
+
+    /// Collect dir content (see `Position::OpenDirWithContent` and
+    /// `WalkDirIterator::get_current_dir_content`) into `C` instead of the
+    /// collection of the content processor, e.g. into a map keyed by file name:
+    ///
+    /// ```no_run
+    /// use std::collections::BTreeMap;
+    /// use std::ffi::OsString;
+    /// use walkdir2::{DirEntry, Position, WalkDir};
+    ///
+    /// let walk = WalkDir::new("foo")
+    ///     .yield_open_dir_with_content(true)
+    ///     .collection::<BTreeMap<OsString, DirEntry>>();
+    /// for pos in walk {
+    ///     if let Position::OpenDirWithContent(dir, content) = pos {
+    ///         if content.contains_key::<std::ffi::OsStr>("package.json".as_ref()) {
+    ///             println!("{}", dir.path().display());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn collection<C>(self) -> WalkDirBuilder<FS, cp::WithCollection<CP, C>>
+    where
+        C: cp::ContentCollection<CP::Item>,
+    {
+        let WalkDirOptions { immut, sorter, error_policy, content_processor, ctx } = self.opts;
+        WalkDirBuilder {
+            opts: WalkDirOptions {
+                immut,
+                sorter,
+                error_policy,
+                content_processor: cp::WithCollection::new(content_processor),
+                ctx,
+            },
+            root: self.root,
+        }
+    }
+}
+
+impl WalkDirBuilder<fs::DefaultDirEntry, cp::DirEntryContentProcessor> {
+    /// Into walker lending entries with a shared path buffer (no path allocation per entry).
+    ///
+    /// See [`LendingWalk`] for the options it supports. The builder is given
+    /// back if it has options the lending walker can't apply: a sorter, an
+    /// error policy or a content order.
+    ///
+    /// [`LendingWalk`]: struct.LendingWalk.html
+    pub fn into_lending(self) -> Result<LendingWalk, Self> {
+        if self.opts.sorter.is_some()
+            || self.opts.error_policy.is_some()
+            || self.opts.immut.content_order != ContentOrder::None
+        {
+            return Err(self);
+        }
+        let WalkDirOptions { immut, ctx, .. } = self.opts;
+        Ok(LendingWalk::new(immut, ctx, self.root))
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////
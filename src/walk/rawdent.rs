@@ -1,7 +1,7 @@
-use crate::error::{into_io_err, into_path_err, ErrorInner};
+use crate::error::{into_path_err, ErrorInner, Operation};
 use crate::fs::{self, FsRootDirEntry, FsReadDirIterator, FsFileType};
 use crate::wd::{self, FnCmp, IntoOk, IntoSome, IntoErr, Depth};
-use crate::cp::ContentProcessor;
+use crate::cp::{ContentProcessor, ParentContext};
 
 #[derive(Debug)]
 enum RawDirEntryKind<FS: fs::FsDirEntry> {
@@ -58,9 +58,9 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
         ctx: &mut FS::Context,
     ) -> wd::ResultInner<Self, FS> {
         let fsdent = FS::RootDirEntry::from_path( path, ctx )
-            .map_err(|err| into_path_err(path, err))?;
+            .map_err(|err| into_path_err(path, Operation::Metadata, err))?;
         let ty = fsdent.file_type(false, ctx)
-            .map_err(|err| into_path_err(path, err))?;
+            .map_err(|err| into_path_err(path, Operation::Metadata, err))?;
         Self {
             kind: RawDirEntryKind::<FS>::Root{ fsdent },
             follow_link: false,
@@ -74,7 +74,7 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
         ctx: &mut FS::Context,
     ) -> wd::ResultInner<Self, FS> {
         let ty = fsdent.file_type(false, ctx)
-            .map_err(into_io_err)?;
+            .map_err(|err| into_path_err(fsdent.path(), Operation::Metadata, err))?;
         Self {
             kind: RawDirEntryKind::<FS>::DirEntry{ fsdent },
             follow_link: false,
@@ -159,7 +159,7 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
             RawDirEntryKind::DirEntry { fsdent, .. } => {
                 fsdent.metadata( self.follow_link, ctx )
             },
-        }.map_err(into_io_err)
+        }.map_err(|err| into_path_err(self.path(), Operation::Metadata, err))
     }
 
     /// Return the type at the target of symlink.
@@ -174,7 +174,7 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
             RawDirEntryKind::DirEntry { fsdent, .. } => {
                 fsdent.file_type( true, ctx )
             },
-        }.map_err(|err| into_path_err(self.path(), err))
+        }.map_err(|err| into_path_err(self.path(), Operation::FollowLink, err))
     }
 
     /// Return the file type for the file that this entry points to.
@@ -273,8 +273,8 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
             RawDirEntryKind::DirEntry { fsdent, .. } => {
                 fsdent.read_dir( ctx )
             },
-        }.map_err(into_io_err)?;
-        ReadDir::<FS>::new(rd, opened_count).into_ok()
+        }.map_err(|err| into_path_err(self.path(), Operation::ReadDir, err))?;
+        ReadDir::<FS>::new(rd, self.pathbuf(), opened_count).into_ok()
     }
 
     fn as_fsdent_ty(&self) -> Option<(&FS, &FS::FileType)> {
@@ -300,6 +300,7 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
     pub fn make_content_item<CP: ContentProcessor<FS>>(
         &mut self,
         content_processor: &CP,
+        parent: ParentContext<'_, CP::Item, CP::Scratch>,
         is_dir: bool,
         depth: Depth,
         ctx: &mut FS::Context,
@@ -309,7 +310,7 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
                 content_processor.process_root_direntry( fsdent, self.follow_link, is_dir, depth, ctx )
             },
             RawDirEntryKind::DirEntry { fsdent, .. } => {
-                content_processor.process_direntry( fsdent, self.follow_link, is_dir, depth, ctx )
+                content_processor.process_direntry( fsdent, parent, self.follow_link, is_dir, depth, ctx )
             },
         }
     }
@@ -318,13 +319,14 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
     pub fn allow_push<CP: ContentProcessor<FS>>(
         &self,
         content_processor: &CP,
+        parent: ParentContext<'_, CP::Item, CP::Scratch>,
     ) -> bool {
         match &self.kind {
             RawDirEntryKind::Root { .. } => {
                 true
             },
             RawDirEntryKind::DirEntry { fsdent, .. } => {
-                content_processor.allow_push( fsdent )
+                content_processor.allow_push( fsdent, parent )
             },
         }
     }
@@ -345,7 +347,7 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
             RawDirEntryKind::DirEntry { fsdent, .. } => {
                 fsdent.fingerprint( ctx )
             },
-        }.map_err(into_io_err)
+        }.map_err(|err| into_path_err(self.path(), Operation::Fingerprint, err))
     }
 
     /// Get device num
@@ -360,7 +362,7 @@ impl<FS: fs::FsDirEntry> RawDirEntry<FS> {
             RawDirEntryKind::DirEntry { fsdent, .. } => {
                 fsdent.device_num(ctx)
             },
-        }.map_err(into_io_err)
+        }.map_err(|err| into_path_err(self.path(), Operation::DeviceNum, err))
     }
 
     /// Get parts
@@ -413,7 +415,9 @@ pub enum ReadDir<FS: fs::FsDirEntry> {
     /// [`Option<...>`]: https://doc.rust-lang.org/stable/std/option/enum.Option.html
     Opened { 
         /// Underlying ReadDir
-        rd: FS::ReadDir 
+        rd: FS::ReadDir,
+        /// Path of this dir (for errors)
+        path: FS::PathBuf,
     },
 
     /// A closed handle.
@@ -448,12 +452,14 @@ impl<FS: fs::FsDirEntry> ReadDir<FS> {
     /// Create new ReadDir
     fn new(
         rd: FS::ReadDir,
+        path: FS::PathBuf,
         opened_count: &mut Depth,
     ) -> Self {
         *opened_count += 1;
 
         Self::Opened { 
-            rd 
+            rd,
+            path,
         }
     }
 
@@ -487,8 +493,8 @@ impl<FS: fs::FsDirEntry> ReadDir<FS> {
                     None => vec![],
                 }
             },
-            ReadDir::Opened { rd } => {
-                let entries = ReadDirOpenedIterator::new( rd, process_rawdent, ctx )
+            ReadDir::Opened { rd, path } => {
+                let entries = ReadDirOpenedIterator::new( rd, path.as_ref(), process_rawdent, ctx )
                     .filter_map(|opt| opt)
                     .collect();
                 *self = ReadDir::<FS>::Closed;
@@ -520,12 +526,12 @@ impl<FS: fs::FsDirEntry> ReadDir<FS> {
                 let item = extract_enum!( self, ReadDir::Once{item}, ReadDir::<FS>::Closed );
                 item.into_ok().into_some()
             },
-            ReadDir::Opened { ref mut rd } => {
+            ReadDir::Opened { ref mut rd, ref path } => {
                 match rd.next_entry(ctx) {
                     Some(r) => {
                         match r {
                             Ok(fsdent)  => RawDirEntry::<FS>::from_fsdent( fsdent, ctx ),
-                            Err(e)      => Err(into_io_err(e)),
+                            Err(e)      => Err(into_path_err(path, Operation::NextEntry, e)),
                         }.into_some()
                     },
                     None => {
@@ -555,6 +561,7 @@ where
     P: (FnMut(wd::ResultInner<RawDirEntry<FS>, FS>, &mut FS::Context) -> Option<T>),
 {
     rd: &'c mut FS::ReadDir,
+    path: &'c FS::Path,
     process_rawdent: &'c mut P,
     ctx: &'c mut FS::Context,
 }
@@ -566,11 +573,13 @@ where
 {
     fn new(
         rd: &'c mut FS::ReadDir,
+        path: &'c FS::Path,
         process_rawdent: &'c mut P,
         ctx: &'c mut FS::Context,
     ) -> Self {
         Self {
             rd,
+            path,
             process_rawdent,
             ctx,
         }
@@ -587,7 +596,7 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         let rrawdent = match self.rd.next_entry(self.ctx)? {
             Ok(fsdent)  => RawDirEntry::<FS>::from_fsdent( fsdent, self.ctx ),
-            Err(e)      => Err(into_io_err(e)),
+            Err(e)      => Err(into_path_err(self.path, Operation::NextEntry, e)),
         };
         
         let t = (self.process_rawdent)( rrawdent, self.ctx );
@@ -0,0 +1,148 @@
+use crate::cp::ContentProcessor;
+use crate::error::Error;
+use crate::fs;
+use crate::walk::walk::WalkDirIterator;
+use crate::wd::{DirSummary, Position};
+
+/////////////////////////////////////////////////////////////////////////
+//// WalkControl
+
+/// A control value returned by [`WalkVisitor`] methods
+///
+/// [`WalkVisitor`]: trait.WalkVisitor.html
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WalkControl {
+    /// Carry on as normal
+    Continue,
+    /// Don't descend into this dir (or skip its remaining content, if it is already opened)
+    SkipSubtree,
+    /// Skip all remaining entries of the parent dir (after this one is processed)
+    SkipSiblings,
+    /// Stop walking immediately
+    Stop,
+}
+
+/////////////////////////////////////////////////////////////////////////
+//// WalkVisitor
+
+/// A push-based walker callbacks.
+///
+/// Each method is called for the corresponding `Position` yielded by the
+/// iterator, and returns a [`WalkControl`] value which tells the walker how
+/// to proceed. All methods return `WalkControl::Continue` by default.
+///
+/// ```no_run
+/// use walkdir2::{DirEntry, Error, WalkControl, WalkDir, WalkVisitor};
+/// use walkdir2::fs::DefaultDirEntry;
+/// use walkdir2::cp::DirEntryContentProcessor;
+///
+/// struct SkipTarget;
+///
+/// impl WalkVisitor<DefaultDirEntry, DirEntryContentProcessor> for SkipTarget {
+///     fn entry(&mut self, entry: &DirEntry) -> WalkControl {
+///         if entry.file_name() == "target" {
+///             return WalkControl::SkipSubtree;
+///         }
+///         println!("{}", entry.path().display());
+///         WalkControl::Continue
+///     }
+///
+///     fn error(&mut self, err: Error) -> WalkControl {
+///         eprintln!("ERROR: {}", err);
+///         WalkControl::Continue
+///     }
+/// }
+///
+/// WalkDir::new("foo").visit(&mut SkipTarget);
+/// ```
+///
+/// [`WalkControl`]: enum.WalkControl.html
+pub trait WalkVisitor<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
+    /// Called before content of dir.
+    ///
+    /// `SkipSubtree` skips the entire content of this dir (`close_dir` will
+    /// still be called), `SkipSiblings` skips the remaining entries of its
+    /// parent dir.
+    fn open_dir(&mut self, _dir: &CP::Item) -> WalkControl {
+        WalkControl::Continue
+    }
+
+    /// Called before content of dir when `yield_open_dir_with_content` is set.
+    fn open_dir_with_content(&mut self, dir: &CP::Item, _content: &CP::Collection) -> WalkControl {
+        self.open_dir(dir)
+    }
+
+    /// Called for each entry.
+    ///
+    /// `SkipSubtree` doesn't descend into this entry if it is a dir (in
+    /// `contents_first` mode it has no effect, since the dir was already
+    /// walked), `SkipSiblings` skips the remaining entries of the parent dir.
+    fn entry(&mut self, _entry: &CP::Item) -> WalkControl {
+        WalkControl::Continue
+    }
+
+    /// Called after content of dir.
+    ///
+    /// `SkipSiblings` skips the remaining entries of its parent dir,
+    /// `SkipSubtree` has no effect.
+    fn close_dir(&mut self, _dir: &CP::Item) -> WalkControl {
+        WalkControl::Continue
+    }
+
+    /// Called after content of dir when `yield_close_dir_with_summary` is set.
+    fn close_dir_with_summary(&mut self, dir: &CP::Item, _summary: &DirSummary) -> WalkControl {
+        self.close_dir(dir)
+    }
+
+    /// Called for each error.
+    ///
+    /// `SkipSiblings` skips the remaining entries of the dir where the error
+    /// occured, `SkipSubtree` has no effect.
+    fn error(&mut self, _err: Error<FS>) -> WalkControl {
+        WalkControl::Continue
+    }
+}
+
+/// Drives the visitor over the entire walk.
+/// Returns `WalkControl::Stop` if the walk was stopped by the visitor, `WalkControl::Continue` otherwise.
+pub(crate) fn visit<FS, CP, V>(
+    mut iter: WalkDirIterator<FS, CP>,
+    visitor: &mut V,
+) -> WalkControl
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+    V: WalkVisitor<FS, CP> + ?Sized,
+{
+    while let Some(pos) = iter.next() {
+        let (control, subtree_pending) = match pos {
+            Position::OpenDir(ref dir) => (visitor.open_dir(dir), true),
+            Position::OpenDirWithContent(ref dir, ref content) => {
+                (visitor.open_dir_with_content(dir, content), true)
+            }
+            Position::Entry(ref dent) => (visitor.entry(dent), CP::is_dir(dent)),
+            Position::CloseDir(ref dir) => (visitor.close_dir(dir), false),
+            Position::CloseDirWithSummary(ref dir, ref summary) => {
+                (visitor.close_dir_with_summary(dir, summary), false)
+            }
+            Position::Error(err) => (visitor.error(err), false),
+        };
+
+        match control {
+            WalkControl::Continue => {}
+            WalkControl::SkipSubtree => {
+                if subtree_pending {
+                    iter.skip_current_dir();
+                }
+            }
+            WalkControl::SkipSiblings => iter.skip_siblings(),
+            WalkControl::Stop => return WalkControl::Stop,
+        }
+    }
+
+    WalkControl::Continue
+}
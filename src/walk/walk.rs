@@ -2,12 +2,13 @@ use std::vec;
 
 use crate::cp::ContentProcessor;
 use crate::fs::{self, FsFileType};
+use crate::walk::ancestor::{check_loop, is_same_file_system, make_loop_error, Ancestor};
 use crate::walk::dir::{DirState, FlatDirEntry};
 use crate::walk::rawdent::{RawDirEntry};
-use crate::error::{ErrorInner, Error};
+use crate::error::{into_path_err, Error, Operation};
 use crate::walk::opts::{WalkDirOptions, WalkDirOptionsImmut};
 use crate::wd::{
-    self, ContentFilter, Depth, FnCmp, IntoOk, IntoSome, Position, InnerPositionWithData,
+    self, ContentFilter, Depth, ErrorAction, FnErrorPolicy, IntoOk, IntoSome, Position, InnerPositionWithData, SiblingInfo,
 };
 
 // /// Like try, but for iterators that return [`Option<Result<_, _>>`].
@@ -58,38 +59,6 @@ pub type WalkDirIteratorItem<FS, CP> = Position<
     Error<FS>,
 >;
 
-/////////////////////////////////////////////////////////////////////////
-//// Ancestor
-
-/// An ancestor is an item in the directory tree traversed by walkdir, and is
-/// used to check for loops in the tree when traversing symlinks.
-#[derive(Debug)]
-struct Ancestor<FS: fs::FsDirEntry> {
-    /// The path of this ancestor.
-    path: FS::PathBuf,
-    /// Fingerprint
-    fingerprint: FS::DirFingerprint,
-}
-
-impl<FS: fs::FsDirEntry> Ancestor<FS> {
-    /// Create a new ancestor from the given directory path.
-    pub fn new(
-        raw: &RawDirEntry<FS>,
-        ctx: &mut FS::Context,
-    ) -> wd::ResultInner<Self, FS> {
-        Self { 
-            path: raw.pathbuf(), 
-            fingerprint: raw.fingerprint(ctx)? 
-        }.into_ok()
-    }
-
-    /// Returns true if and only if the given open file handle corresponds to
-    /// the same directory as this ancestor.
-    fn is_same(&self, rhs: &Self) -> bool {
-        FS::is_same( (&self.path, &self.fingerprint), (&rhs.path, &rhs.fingerprint))
-    }
-}
-
 /////////////////////////////////////////////////////////////////////////
 //// IntoIter
 
@@ -102,6 +71,17 @@ enum TransitionState {
     AfterPopUp,
 }
 
+/// Kind of the most recently yielded item
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum YieldedKind {
+    /// OpenDir (OpenDirWithContent)
+    OpenDir,
+    /// Entry with dir
+    DirEntry,
+    /// Anything else
+    Other,
+}
+
 /// An iterator for recursively descending into a directory.
 ///
 /// A value with this type must be constructed with the [`WalkDir`] type, which
@@ -134,6 +114,12 @@ where
     states: Vec<DirState<FS, CP>>,
     /// before push down / after pop up
     transition_state: TransitionState,
+    /// Kind of the most recently yielded item
+    last_yielded: YieldedKind,
+    /// Item of the current dir entry: made when the entry is yielded and
+    /// kept for the dir state (or kept from the popped dir state in
+    /// contents_first mode), so the entry is processed only once
+    dir_item: Option<CP::Item>,
     /// A stack of file paths.
     ///
     /// This is *only* used when [`follow_links`] is enabled. In all other
@@ -169,6 +155,8 @@ where
             root: Some(root),
             states: vec![],
             transition_state: TransitionState::None,
+            last_yielded: YieldedKind::Other,
+            dir_item: None,
             ancestors: vec![],
             opened_count: 0,
             depth: 0,
@@ -200,7 +188,7 @@ where
         depth: Depth,
         opts_immut: &WalkDirOptionsImmut,
         root_device_opt: &Option<FS::DeviceNum>,
-        ancestors: &Vec<Ancestor<FS>>,
+        ancestors: &[Ancestor<FS>],
         ctx: &mut FS::Context,
     ) -> Option<wd::ResultInner<FlatDirEntry<FS>, FS>> {
         let (rawdent, loop_link) =
@@ -215,13 +203,15 @@ where
             };
 
         let mut is_normal_dir = !rawdent.is_symlink() && rawdent.is_dir();
+        let mut other_fs = false;
 
         if is_normal_dir {
             if opts_immut.same_file_system && depth > 0 {
                 let root_device = root_device_opt.as_ref().expect("BUG: called is_same_file_system without root device");
-                match Self::is_same_file_system(root_device, &rawdent, ctx) {
+                match is_same_file_system(root_device, &rawdent, ctx) {
                     Ok(true) => {},
-                    Ok(false) => return None,
+                    // Yield it, but don't descend into it
+                    Ok(false) => other_fs = true,
                     Err(err) => return Err(err).into_some(),    
                 }
             };
@@ -242,7 +232,8 @@ where
         FlatDirEntry { 
             raw: rawdent, 
             is_dir: is_normal_dir, 
-            loop_link 
+            loop_link,
+            other_fs,
         }.into_ok().into_some()
     }
 
@@ -313,13 +304,13 @@ where
     fn push_dir_1(
         flat: &FlatDirEntry<FS>,
         new_depth: Depth,
-        opts_immut: &WalkDirOptionsImmut,
-        sorter: &mut Option<FnCmp<FS>>,
+        opts: &mut WalkDirOptions<FS, CP>,
         root_device: &Option<FS::DeviceNum>,
-        ancestors: &Vec<Ancestor<FS>>,
+        ancestors: &[Ancestor<FS>],
         opened_count: &mut Depth,
-        ctx: &mut FS::Context,
     ) -> wd::ResultInner<PushDirData<FS, CP>, FS> {
+        let WalkDirOptions { immut: opts_immut, sorter, ctx, .. } = opts;
+
         // This is safe as we makes any changes strictly AFTER using dent_ptr.
         // Neither FS::read_dir nor Ancestor::new
 
@@ -364,12 +355,25 @@ where
     }
 
     fn push_dir_2(&mut self, data: PushDirData<FS, CP>) {
-        let (state, ancestor_opt) = data;
+        let (mut state, ancestor_opt) = data;
 
         if let Some(ancestor) = ancestor_opt {
             self.ancestors.push(ancestor);
         }
 
+        if self.opts.immut.track_siblings {
+            let prefix = self.states.last().map(|parent| parent.current_last_flags());
+            state.init_siblings(prefix, &self.opts.immut, &self.opts.content_processor, &mut self.opts.ctx);
+        } else if self.opts.content_processor.process_ahead() {
+            state.load_all(
+                &self.opts.immut,
+                &mut process_dent!(self, state.depth()),
+                &mut self.opened_count,
+                &mut self.opts.ctx,
+            );
+            state.process_all(&self.opts.immut, &self.opts.content_processor, &mut self.opts.ctx);
+        }
+
         // if self.opts.immut.max_open.is_some() && state.is_open() {
         //     self.opened_count += 1;
         // };
@@ -377,23 +381,59 @@ where
         self.states.push(state);
     }
 
-    fn pop_dir(&mut self) {
+    /// Returns the item of the popped dir
+    fn pop_dir(&mut self) -> Option<CP::Item> {
 
-        let last_state = self.states.pop().expect("BUG: cannot pop from empty stack");
+        let mut last_state = self.states.pop().expect("BUG: cannot pop from empty stack");
         last_state.on_drop(&mut self.opened_count);
 
         if self.opts.immut.follow_links {
             self.ancestors.pop().expect("BUG: list/path stacks out of sync");
         }
 
+        debug!(self.do_debug_checks());
+        last_state.take_parent_item()
+    }
+
+    /// Closes all opened dirs: the walk is over.
+    fn abort(&mut self) {
+        for state in self.states.drain(..) {
+            state.on_drop(&mut self.opened_count);
+        }
+        self.ancestors.clear();
+        self.transition_state = TransitionState::None;
+        self.dir_item = None;
+
         debug!(self.do_debug_checks());
     }
 
+    /// Asks error policy what to do with this error.
+    fn error_action(
+        error_policy: &mut Option<FnErrorPolicy<FS>>,
+        err: &Error<FS>,
+        attempt: usize,
+    ) -> ErrorAction {
+        match error_policy {
+            Some(policy) => policy(err, attempt),
+            None => ErrorAction::Yield,
+        }
+    }
+
     /// Skips the current directory.
     ///
-    /// This causes the iterator to stop traversing the contents of the least
-    /// recently yielded directory. This means any remaining entries in that
-    /// directory will be skipped (including sub-directories).
+    /// This causes the iterator to stop traversing the contents of the most
+    /// recently yielded directory (including sub-directories). What is skipped
+    /// depends on the most recently yielded item:
+    ///
+    /// - `Position::Entry` of a dir which is not descended into yet: the
+    ///   content of this dir (its siblings are still yielded);
+    /// - `Position::OpenDir` or an entry which isn't such a dir: the remaining
+    ///   entries of the dir containing it;
+    /// - `Position::CloseDir`, or a dir entry in `contents_first` mode: nothing,
+    ///   since the content of this dir was already yielded.
+    ///
+    /// Note that earlier versions also skipped the remaining siblings of a
+    /// yielded dir entry; use [`skip_siblings`] for that (see CHANGELOG.md).
     ///
     /// Note that the ergonomics of this method are questionable since it
     /// borrows the iterator mutably. Namely, you must write out the looping
@@ -432,22 +472,75 @@ where
     /// above.)
     ///
     /// [`filter_entry`]: #method.filter_entry
+    /// [`skip_siblings`]: #method.skip_siblings
     pub fn skip_current_dir(&mut self) {
-        if let Some(cur_state) = self.states.last_mut() {
-            cur_state.skip_all();
-            self.transition_state = TransitionState::None;
+        match self.transition_state {
+            // Yielded dir entry is not descended into yet: skip its content only
+            TransitionState::CloseOldestBeforePushDown | TransitionState::BeforePushDown => {
+                self.transition_state = TransitionState::AfterPopUp;
+            }
+            // Yielded dir was already closed (or its content was skipped)
+            TransitionState::BeforePopUp | TransitionState::AfterPopUp => {}
+            TransitionState::None => {
+                // Yielded dir entry was already descended into (in contents_first mode)
+                if self.last_yielded == YieldedKind::DirEntry {
+                    return;
+                }
+                if let Some(cur_state) = self.states.last_mut() {
+                    cur_state.skip_all();
+                }
+            }
+        }
+    }
+
+    /// Skips the remaining siblings of the most recently yielded item.
+    ///
+    /// Unlike [`skip_current_dir`], the most recently yielded item itself is
+    /// processed as usual (i.e. a dir is still descended into and closed),
+    /// but all the entries following it in its parent dir are skipped.
+    ///
+    /// [`skip_current_dir`]: #method.skip_current_dir
+    pub fn skip_siblings(&mut self) {
+        let len = self.states.len();
+        if len == 0 {
+            return;
+        }
+
+        match self.transition_state {
+            // Yielded dir entry is still current in the last state
+            TransitionState::CloseOldestBeforePushDown
+            | TransitionState::BeforePushDown
+            | TransitionState::AfterPopUp => {
+                self.states[len - 1].skip_after_current();
+            }
+            // Yielded dir is current in the parent state
+            TransitionState::BeforePopUp => {
+                if len > 1 {
+                    self.states[len - 2].skip_after_current();
+                }
+            }
+            TransitionState::None => {
+                if self.last_yielded == YieldedKind::OpenDir {
+                    if len > 1 {
+                        self.states[len - 2].skip_after_current();
+                    }
+                } else {
+                    // Position was already shifted to next entry
+                    self.states[len - 1].skip_all();
+                }
+            }
         }
     }
 
     fn follow(
         raw: RawDirEntry<FS>,
-        ancestors: &Vec<Ancestor<FS>>,
+        ancestors: &[Ancestor<FS>],
         ctx: &mut FS::Context,
     ) -> wd::ResultInner<(RawDirEntry<FS>, Option<Depth>), FS> {
         let dent = raw.follow(ctx)?;
 
         let loop_link = if dent.is_dir() && !ancestors.is_empty() {
-            Self::check_loop( &dent, ancestors, ctx )?
+            check_loop( &dent, ancestors, ctx )?
         } else {
             None
         };
@@ -455,61 +548,83 @@ where
         Ok((dent, loop_link))
     }
 
-    fn check_loop(
-        raw: &RawDirEntry<FS>,
-        ancestors: &Vec<Ancestor<FS>>,
-        ctx: &mut FS::Context,
-    ) -> wd::ResultInner<Option<Depth>, FS> {
-        let raw_as_ancestor = Ancestor::<FS>::new( raw, ctx )?;
+    /// The fs context
+    pub fn context(&self) -> &FS::Context {
+        &self.opts.ctx
+    }
 
-        for (index, ancestor) in ancestors.iter().enumerate().rev() {
-            if ancestor.is_same(&raw_as_ancestor) {
-                return Ok(Some(index));
-            }
-        }
+    /// The fs context (mutable)
+    pub fn context_mut(&mut self) -> &mut FS::Context {
+        &mut self.opts.ctx
+    }
 
-        Ok(None)
+    /// Consumes the iterator and returns the fs context (e.g. to reuse it in the next walk)
+    pub fn into_context(self) -> FS::Context {
+        self.opts.ctx
     }
 
-    fn make_loop_error(
-        ancestors: &Vec<Ancestor<FS>>,
-        depth: Depth,
-        child: &FS::Path,
-    ) -> ErrorInner<FS> {
-        let ancestor = ancestors.get(depth).unwrap();
+    /// Returns the items of all currently open dirs, from the root down to
+    /// the parent of the most recently yielded entry.
+    ///
+    /// Right after `Position::OpenDir(dir)` (and until `Position::CloseDir(dir)`
+    /// is yielded) the chain ends with `dir` itself. The items are made once,
+    /// when their dirs are opened (they are the parents passed to the content
+    /// processor), so this doesn't process any entries.
+    pub fn ancestors(&self) -> impl Iterator<Item = &CP::Item> + '_ {
+        self.states.iter().skip(1).filter_map(|state| state.parent_item())
+    }
 
-        ErrorInner::<FS>::from_loop(&ancestor.path, child)
+    /// Makes the error of the content processor which made no item of the entry
+    fn content_error(err: Option<FS::Error>, path: &FS::Path, depth: Depth) -> Option<Error<FS>> {
+        let err = err?;
+        Error::from_inner(into_path_err::<FS, _>(path, Operation::Metadata, err), depth).into_some()
     }
 
-    fn is_same_file_system(
-        root_device: &FS::DeviceNum,
-        dent: &RawDirEntry<FS>,
-        ctx: &mut FS::Context,
-    ) -> wd::ResultInner<bool, FS> {
-        Ok(*root_device == dent.device_num(ctx)?)
+    /// Attaches position among siblings to the item made from current entry at given depth
+    fn attach_sibling_info(&self, odent: Option<CP::Item>, depth: Depth) -> Option<CP::Item> {
+        if !self.opts.immut.track_siblings {
+            return odent;
+        }
+
+        let mut dent = odent?;
+        let (index, count) = self.states[depth].sibling_position();
+        let last = self.states[depth].current_last_flags().clone();
+        self.opts.content_processor.set_sibling_info(&mut dent, SiblingInfo { index, count, last });
+        Some(dent)
     }
 
     /// Gets content of current dir
     pub fn get_current_dir_content(&mut self, filter: ContentFilter) -> CP::Collection {
         let cur_state = self.states.last_mut().unwrap();
 
-        let content = cur_state.clone_all_content(
+        cur_state.clone_all_content(
             filter,
             &self.opts.immut,
             &mut self.opts.content_processor,
             &mut process_dent!(self, cur_state.depth()),
             &mut self.opened_count,
             &mut self.opts.ctx,
-        );
-
-        content
+        )
     }
 }
 
+macro_rules! content_error {
+    ($self:expr, $odent:expr, $cur_depth:expr, $rflat:expr) => {{
+        if $odent.is_none() {
+            let err = $rflat.take_error(&$self.opts.content_processor);
+            Self::content_error(err, $rflat.path(), $cur_depth)
+        } else {
+            None
+        }
+    }};
+}
+
 macro_rules! next_and_yield_rflat {
     ($self:expr, $cur_state:expr, $cur_depth:expr, $rflat:expr) => {{
         let odent = $rflat.make_content_item(&mut $self.opts.content_processor, &mut $self.opts.ctx);
-        $cur_state.next_position(
+        let err = content_error!($self, odent, $cur_depth, $rflat);
+        let odent = $self.attach_sibling_info(odent, $cur_depth);
+        $self.states.get_mut($cur_depth).unwrap().next_position(
             &$self.opts.immut,
             &mut process_dent!($self, $cur_depth),
             &mut $self.opened_count,
@@ -517,19 +632,52 @@ macro_rules! next_and_yield_rflat {
         );
         if let Some(dent) = odent {
             return Position::Entry(dent).into_some();
-        } else {
-            false
         }
+        if let Some(err) = err {
+            yield_error!($self, err);
+        }
+        false
+    }};
+}
+
+macro_rules! yield_error {
+    ($self:expr, $err:expr, $action:expr) => {{
+        let err = $err;
+        match $action {
+            // Only opening of dirs is retried (see WalkDirBuilder::error_policy)
+            ErrorAction::Yield | ErrorAction::Retry(_) => {
+                return Position::Error(err).into_some();
+            }
+            ErrorAction::Skip => continue,
+            ErrorAction::Abort => {
+                $self.abort();
+                return Position::Error(err).into_some();
+            }
+        }
+    }};
+    ($self:expr, $err:expr) => {{
+        let err = $err;
+        let action = Self::error_action(&mut $self.opts.error_policy, &err, 0);
+        yield_error!($self, err, action)
     }};
 }
 
-macro_rules! yield_rflat {
+macro_rules! yield_dir_rflat {
     ($self:expr, $cur_state:expr, $cur_depth:expr, $rflat:expr) => {{
         let odent = $rflat.make_content_item(&mut $self.opts.content_processor, &mut $self.opts.ctx);
+        let err = content_error!($self, odent, $cur_depth, $rflat);
+        let odent = $self.attach_sibling_info(odent, $cur_depth);
         if let Some(dent) = odent {
+            // Keep the item for the dir state, if the dir is descended into
+            if $self.transition_state == TransitionState::CloseOldestBeforePushDown {
+                $self.dir_item = Some(dent.clone());
+            }
             return Position::Entry(dent).into_some();
-        } else {
-            false
+        }
+        // If conversion to CP::Item failed, skip all children and jump to last step
+        $self.transition_state = TransitionState::AfterPopUp;
+        if let Some(err) = err {
+            yield_error!($self, err);
         }
     }};
 }
@@ -547,32 +695,46 @@ FS: fs::FsDirEntry,
     /// If the iterator fails to retrieve the next value, this method returns
     /// an error value. The error will be wrapped in an Option::Some.
     fn next(&mut self) -> Option<Self::Item> {
-        fn get_parent_dent<FS, CP>(this: &mut WalkDirIterator<FS, CP>, cur_depth: Depth) -> CP::Item
-        where
-            FS: fs::FsDirEntry,
-            CP: ContentProcessor<FS>,
-        {
-            let prev_state = this.states.get_mut(cur_depth - 1).unwrap();
-            match prev_state.get_current_position() {
-                InnerPositionWithData::Entry(mut rflat) => {
-                    rflat.make_content_item(&mut this.opts.content_processor, &mut this.opts.ctx).unwrap()
-                }
-                _ => unreachable!(),
-            }
-        }
+        let item = self.next_item();
+        self.last_yielded = match item {
+            Some(Position::OpenDir(_)) | Some(Position::OpenDirWithContent(_, _)) => YieldedKind::OpenDir,
+            Some(Position::Entry(ref dent)) if CP::is_dir(dent) => YieldedKind::DirEntry,
+            _ => YieldedKind::Other,
+        };
+        item
+    }
+}
 
+impl<FS, CP> WalkDirIterator<FS, CP>
+where
+    FS: fs::FsDirEntry,
+    CP: ContentProcessor<FS>,
+{
+    fn next_item(&mut self) -> Option<WalkDirIteratorItem<FS, CP>> {
         // Initial actions
         if let Some(root_path) = self.root.take() {
-            if let Err(e) = self.init(&root_path) {
+            let mut attempt = 0;
+            while let Err(e) = self.init(&root_path) {
+                let err = Error::from_inner(e, 0);
                 debug!(self.do_debug_checks());
-                return Position::Error(Error::from_inner(e, 0)).into_some();
                 // Here self.states is empty, so next call will always return None.
-            };
+                match Self::error_action(&mut self.opts.error_policy, &err, attempt) {
+                    ErrorAction::Retry(delay) => {
+                        std::thread::sleep(delay);
+                        attempt += 1;
+                    }
+                    ErrorAction::Skip => return None,
+                    ErrorAction::Yield | ErrorAction::Abort => {
+                        return Position::Error(err).into_some();
+                    }
+                }
+            }
         }
 
         loop {
             let cur_depth = match self.states.len() {
-                0 => unreachable!(),
+                // Walk is over (failed to init or aborted)
+                0 => return None,
                 len @ _ => (len - 1),
             };
 
@@ -604,6 +766,7 @@ FS: fs::FsDirEntry,
                         continue;
                     }
 
+                    let parent = cur_state.parent_item().expect("BUG: dir was pushed without its item").clone();
                     if self.opts.immut.yield_open_dir_with_content {
                         let content = cur_state.clone_all_content(
                             self.opts.immut.open_dir_with_content_filter,
@@ -613,14 +776,11 @@ FS: fs::FsDirEntry,
                             &mut self.opened_count,
                             &mut self.opts.ctx,
                         );
-                        let parent = get_parent_dent(self, cur_depth);
                         debug!(self.do_debug_checks());
                         return Position::OpenDirWithContent(parent, content).into_some();
-                    } else {
-                        let parent = get_parent_dent(self, cur_depth);
-                        debug!(self.do_debug_checks());
-                        return Position::OpenDir(parent).into_some();
                     }
+                    debug!(self.do_debug_checks());
+                    return Position::OpenDir(parent).into_some();
                 },
                 // At entry
                 InnerPositionWithData::Entry(mut rflat) => {
@@ -645,7 +805,9 @@ FS: fs::FsDirEntry,
                             // First step
                             TransitionState::None => {
                                 // If (cur_depth + 1) still in allowed range ...
-                                let allow_push = cur_depth < self.opts.immut.max_depth && rflat.allow_push(&self.opts.content_processor);
+                                let allow_push = cur_depth < self.opts.immut.max_depth
+                                    && !rflat.other_fs()
+                                    && rflat.allow_push(&self.opts.content_processor);
 
                                 if allow_push {
                                     // Check if rflat is loop link
@@ -655,16 +817,13 @@ FS: fs::FsDirEntry,
 
                                         // If yielding loop links not allowed, yield loop error
                                         if !self.opts.immut.yield_loop_links {
-                                            let err = Self::make_loop_error(
+                                            let err = make_loop_error(
                                                 &self.ancestors,
                                                 loop_depth,
                                                 rflat.path(),
                                             );
                                             debug!(self.do_debug_checks());
-                                            return Position::Error(Error::from_inner(
-                                                err, cur_depth,
-                                            ))
-                                            .into_some();
+                                            yield_error!(self, Error::from_inner(err, cur_depth));
                                         }
                                         continue;
                                     }
@@ -679,10 +838,7 @@ FS: fs::FsDirEntry,
 
                                 // In content_first mode: yield Position::Entry (if allowed) and shift to next entry
                                 if !self.opts.immut.contents_first && allow_yield {
-                                    if !yield_rflat!(self, cur_state, cur_depth, rflat) {
-                                        // If conversion to CP::Item failed, skip all children and jump to last step
-                                        self.transition_state = TransitionState::AfterPopUp;
-                                    }
+                                    yield_dir_rflat!(self, cur_state, cur_depth, rflat);
                                 };
                             }
                             // Third step: now we might to open handle, so we need to go deeper!
@@ -690,28 +846,67 @@ FS: fs::FsDirEntry,
                                 // Deeper dir must start with empty state
                                 self.transition_state = TransitionState::None;
 
-                                match Self::push_dir_1(
-                                    rflat.as_flat(),
-                                    cur_depth + 1,
-                                    &self.opts.immut,
-                                    &mut self.opts.sorter,
-                                    &self.root_device,
-                                    &self.ancestors,
-                                    &mut self.opened_count,
-                                    &mut self.opts.ctx,
-                                ) {
-                                    Ok(data) => {
-                                        self.push_dir_2(data);
+                                // Content of the deeper dir is processed with this item as parent:
+                                // it was kept when the entry was yielded (otherwise it is made here)
+                                let (parent_item, with_sibling_info) = match self.dir_item.take() {
+                                    Some(item) => (item, true),
+                                    None => match rflat.make_content_item(&self.opts.content_processor, &mut self.opts.ctx) {
+                                        Some(item) => (item, false),
+                                        // Dirs skipped by the content processor are not descended into
+                                        None => {
+                                            self.transition_state = TransitionState::AfterPopUp;
+                                            let err = rflat.take_error(&self.opts.content_processor);
+                                            let err = Self::content_error(err, rflat.path(), cur_depth);
+                                            // In content_first mode the error is yielded in place of the entry (if allowed)
+                                            if let Some(err) = err.filter(|_| !self.opts.immut.contents_first || !allow_yield) {
+                                                debug!(self.do_debug_checks());
+                                                yield_error!(self, err);
+                                            }
+                                            continue;
+                                        }
+                                    },
+                                };
+
+                                let mut attempt = 0;
+                                let result = loop {
+                                    match Self::push_dir_1(
+                                        rflat.as_flat(),
+                                        cur_depth + 1,
+                                        &mut self.opts,
+                                        &self.root_device,
+                                        &self.ancestors,
+                                        &mut self.opened_count,
+                                    ) {
+                                        Ok(data) => break Ok(data),
+                                        Err(err) => {
+                                            let err = Error::from_inner(err, cur_depth);
+                                            match Self::error_action(&mut self.opts.error_policy, &err, attempt) {
+                                                ErrorAction::Retry(delay) => {
+                                                    std::thread::sleep(delay);
+                                                    attempt += 1;
+                                                }
+                                                action => break Err((err, action)),
+                                            }
+                                        }
                                     }
-                                    Err(err) => {
+                                };
+
+                                match result {
+                                    Ok((mut state, ancestor)) => {
+                                        let parent_item = if with_sibling_info {
+                                            Some(parent_item)
+                                        } else {
+                                            self.attach_sibling_info(Some(parent_item), cur_depth)
+                                        };
+                                        state.set_parent_item(parent_item);
+                                        self.push_dir_2((state, ancestor));
+                                    }
+                                    Err((err, action)) => {
                                         // Jump to last step
                                         self.transition_state = TransitionState::AfterPopUp;
                                         // And yield an error
                                         debug!(self.do_debug_checks());
-                                        return Position::Error(Error::from_inner(
-                                            err, cur_depth,
-                                        ))
-                                        .into_some();
+                                        yield_error!(self, err, action);
                                     }
                                 }
                             }
@@ -719,9 +914,20 @@ FS: fs::FsDirEntry,
                             TransitionState::AfterPopUp => {
                                 // Clear state
                                 self.transition_state = TransitionState::None;
+                                let dir_item = self.dir_item.take();
 
                                 // In !content_first mode: yield Position::Entry (if allowed) and shift to next entry
                                 if self.opts.immut.contents_first && allow_yield {
+                                    // The item of a descended dir is the one of its popped state
+                                    if let Some(dent) = dir_item {
+                                        cur_state.next_position(
+                                            &self.opts.immut,
+                                            &mut process_dent!(self, cur_depth),
+                                            &mut self.opened_count,
+                                            &mut self.opts.ctx,
+                                        );
+                                        return Position::Entry(dent).into_some();
+                                    }
                                     next_and_yield_rflat!(self, cur_state, cur_depth, rflat);
                                 // If conversion to CP::Item failed, ignore it
                                 } else {
@@ -767,7 +973,7 @@ FS: fs::FsDirEntry,
                         &mut self.opts.ctx,
                     );
                     debug!(self.do_debug_checks());
-                    return Position::Error(err).into_some();
+                    yield_error!(self, err);
                 }
                 // After content of dir
                 InnerPositionWithData::CloseDir => {
@@ -781,14 +987,25 @@ FS: fs::FsDirEntry,
                     match self.transition_state {
                         // First step
                         TransitionState::None => {
-                            // Just yield Position::CloseDir
+                            // Just yield Position::CloseDir (CloseDirWithSummary)
                             self.transition_state = TransitionState::BeforePopUp;
+
+                            let parent = cur_state.parent_item().expect("BUG: dir was pushed without its item").clone();
+                            if self.opts.immut.yield_close_dir_with_summary {
+                                let summary = cur_state.summary();
+                                debug!(self.do_debug_checks());
+                                return Position::CloseDirWithSummary(parent, summary).into_some();
+                            }
                             debug!(self.do_debug_checks());
-                            return Position::CloseDir.into_some();
+                            return Position::CloseDir(parent).into_some();
                         }
                         // Second step: surface to parent
                         TransitionState::BeforePopUp => {
-                            self.pop_dir();
+                            let dir_item = self.pop_dir();
+                            // In contents_first mode the entry of this dir is yielded next
+                            if self.opts.immut.contents_first {
+                                self.dir_item = dir_item;
+                            }
                             // Clear state
                             self.transition_state = TransitionState::AfterPopUp;
                         }
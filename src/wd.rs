@@ -1,3 +1,6 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
 use crate::fs;
 
 // use crate::cp::ContentProcessor;
@@ -68,6 +71,29 @@ pub type FnCmp<E> = Box<
         + 'static,
 >;
 
+/// An action to take on error (returned by error policy).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorAction {
+    /// Yield Position::Error (default)
+    Yield,
+    /// Ignore the error silently
+    Skip,
+    /// Sleep for given duration and retry the failed operation.
+    /// Only opening of dirs can be retried -- otherwise it is the same as Yield
+    /// (see `WalkDirBuilder::error_policy`).
+    Retry(std::time::Duration),
+    /// Yield Position::Error and stop the walk
+    Abort,
+}
+
+/// An error policy function: gets an error and count of retries already made, returns an action.
+pub type FnErrorPolicy<E> = Box<
+    dyn FnMut(&Error<E>, usize) -> ErrorAction
+        + Send
+        + Sync
+        + 'static,
+>;
+
 // Convert FsReadDir.next() to some Option<T>.
 // - Some(T) -- add T to collected vec,
 // - None -- entry must be ignored
@@ -81,6 +107,8 @@ pub type FnCmp<E> = Box<
 
 /// A variants for filtering content
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ContentFilter {
     /// No filter, all content will be yielded (default)
     None,
@@ -94,6 +122,8 @@ pub enum ContentFilter {
 
 /// A variants for ordering content
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ContentOrder {
     /// No arrange (default)
     None,
@@ -103,8 +133,92 @@ pub enum ContentOrder {
     DirsFirst,
 }
 
+/// A summary of dir content (yielded in Position::CloseDirWithSummary)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirSummary {
+    /// Count of files (not dirs, i.e. including symlinks) read from this dir
+    pub files: usize,
+    /// Count of dirs read from this dir
+    pub dirs: usize,
+    /// Count of errors occured while reading this dir
+    pub errors: usize,
+    /// Remaining content of this dir was skipped (so the counters may be incomplete)
+    pub skipped: bool,
+}
+
+/// A position of entry among its siblings (see WalkDirBuilder::track_siblings)
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SiblingInfo {
+    /// Index of this entry among the entries yielded from its parent dir
+    pub index: usize,
+    /// Count of entries yielded from its parent dir
+    pub count: usize,
+    /// For each depth from 1 to the depth of this entry: the entry (or its ancestor at this depth) is the last one in its parent dir
+    pub last: LastFlags,
+}
+
+impl SiblingInfo {
+    /// This entry is the last one in its parent dir
+    pub fn is_last(&self) -> bool {
+        self.index + 1 >= self.count
+    }
+}
+
+/// Last-sibling flags of an entry and its ancestors (see `SiblingInfo::last`).
+///
+/// The flags are shared by all entries of a dir, so cloning them doesn't allocate.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Vec<bool>", into = "Vec<bool>"))]
+pub struct LastFlags(Arc<[bool]>);
+
+impl LastFlags {
+    /// These flags followed by one more
+    pub(crate) fn with(&self, last: bool) -> Self {
+        let mut flags = Vec::with_capacity(self.0.len() + 1);
+        flags.extend_from_slice(&self.0);
+        flags.push(last);
+        flags.into()
+    }
+}
+
+impl Default for LastFlags {
+    fn default() -> Self {
+        Vec::new().into()
+    }
+}
+
+impl Deref for LastFlags {
+    type Target = [bool];
+
+    fn deref(&self) -> &[bool] {
+        &self.0
+    }
+}
+
+impl From<Vec<bool>> for LastFlags {
+    fn from(flags: Vec<bool>) -> Self {
+        Self(flags.into())
+    }
+}
+
+impl From<&[bool]> for LastFlags {
+    fn from(flags: &[bool]) -> Self {
+        Self(flags.into())
+    }
+}
+
+impl From<LastFlags> for Vec<bool> {
+    fn from(flags: LastFlags) -> Self {
+        flags.0.to_vec()
+    }
+}
+
 /// A position in dirs tree
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Position<EN, C, ER> {
     /// Before content of current dir
     OpenDir(EN),
@@ -114,8 +228,10 @@ pub enum Position<EN, C, ER> {
     Entry(EN),
     /// An error
     Error(ER),
-    /// After content of current dir
-    CloseDir,
+    /// After content of current dir (with the same dir as in OpenDir)
+    CloseDir(EN),
+    /// After content of current dir with summary of its content
+    CloseDirWithSummary(EN, DirSummary),
 }
 
 /// A position in dirs tree
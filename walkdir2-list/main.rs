@@ -20,7 +20,9 @@ use std::result;
 use std::time::Instant;
 
 use bstr::BString;
-use walkdir::{Depth, WalkDir};
+use walkdir2::expr::Expr;
+use walkdir2::render::TreeRenderer;
+use walkdir2::{fs::FsDirEntry, Depth, DirEntry, Position, WalkDir};
 
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
@@ -61,9 +63,10 @@ where
 {
     let mut count: u64 = 0;
     for dir in &args.dirs {
-        for result in args.walkdir(dir) {
+        for result in args.walkdir(dir).into_classic() {
             match result {
-                Ok(_) => count += 1,
+                Ok(dent) if args.is_selected(&dent) => count += 1,
+                Ok(_) => {}
                 Err(err) => {
                     if !args.ignore_errors {
                         writeln!(stderr, "ERROR: {}", err)?;
@@ -96,7 +99,7 @@ where
     W1: io::Write,
     W2: io::Write,
 {
-    for result in args.walkdir(dir) {
+    for result in args.walkdir(dir).into_classic() {
         let dent = match result {
             Ok(dent) => dent,
             Err(err) => {
@@ -106,6 +109,9 @@ where
                 continue;
             }
         };
+        if !args.is_selected(&dent) {
+            continue;
+        }
         write_path(&mut stdout, dent.path())?;
         stdout.write_all(b"\n")?;
     }
@@ -117,19 +123,21 @@ where
     W1: io::Write,
     W2: io::Write,
 {
-    for result in args.walkdir(dir) {
-        let dent = match result {
-            Ok(dent) => dent,
-            Err(err) => {
-                if !args.ignore_errors {
-                    writeln!(stderr, "ERROR: {}", err)?;
-                }
-                continue;
-            }
-        };
-        stdout.write_all("  ".repeat(dent.depth()).as_bytes())?;
-        write_os_str(&mut stdout, dent.file_name())?;
-        stdout.write_all(b"\n")?;
+    let renderer = TreeRenderer::new()
+        .ascii(args.ascii)
+        .sizes(args.sizes)
+        .file_counts(args.file_counts)
+        .summary(!args.no_report);
+    let positions = renderer.configure(args.walkdir(dir)).build().filter(|pos| match pos {
+        // The root and the dirs are always shown (as `tree -P` does), so each entry has its parent
+        Position::Entry(dent) => dent.depth() == 0 || dent.file_type().is_dir() || args.is_selected(dent),
+        _ => true,
+    });
+    let (_, errors) = renderer.render_positions(positions, &mut stdout)?;
+    if !args.ignore_errors {
+        for err in errors.errors() {
+            writeln!(stderr, "ERROR: {}", err)?;
+        }
     }
     Ok(())
 }
@@ -142,12 +150,17 @@ struct Args {
     max_depth: Option<Depth>,
     max_open: Option<usize>,
     tree: bool,
+    ascii: bool,
+    sizes: bool,
+    file_counts: bool,
+    no_report: bool,
     ignore_errors: bool,
     sort: bool,
     depth_first: bool,
     same_file_system: bool,
     timeit: bool,
     count: bool,
+    filter: Option<Expr>,
 }
 
 impl Args {
@@ -184,7 +197,29 @@ impl Args {
                     .default_value("10")
                     .help("Use at most this many open file descriptors."),
             )
-            .arg(Arg::with_name("tree").long("tree").help("Show file paths in a tree."))
+            .arg(
+                Arg::with_name("tree")
+                    .long("tree")
+                    .conflicts_with_all(&["min-depth", "depth-first"])
+                    .help("Show file paths in a tree."),
+            )
+            .arg(Arg::with_name("ascii").long("ascii").help("Draw the tree with ASCII characters (with --tree)."))
+            .arg(
+                Arg::with_name("sizes")
+                    .long("sizes")
+                    .short("s")
+                    .help("Show the size of each file in bytes (with --tree)."),
+            )
+            .arg(
+                Arg::with_name("file-counts")
+                    .long("file-counts")
+                    .help("Show the count of files in each directory (with --tree)."),
+            )
+            .arg(
+                Arg::with_name("no-report")
+                    .long("no-report")
+                    .help("Don't print the count of directories and files (with --tree)."),
+            )
             .arg(
                 Arg::with_name("ignore-errors")
                     .long("ignore-errors")
@@ -210,6 +245,16 @@ impl Args {
                     .short("c")
                     .help("Print only a total count of all file paths."),
             )
+            .arg(
+                Arg::with_name("filter")
+                    .long("filter")
+                    .short("f")
+                    .takes_value(true)
+                    .help(
+                        "Only show entries matching this expression, e.g. 'type f and size > 1M'. \
+                         With --tree, directories are always shown.",
+                    ),
+            )
             .get_matches();
 
         let dirs = match parsed.values_of_os("dirs") {
@@ -223,12 +268,17 @@ impl Args {
             max_depth: parse_usize(&parsed, "max-depth")?,
             max_open: parse_usize(&parsed, "max-open")?,
             tree: parsed.is_present("tree"),
+            ascii: parsed.is_present("ascii"),
+            sizes: parsed.is_present("sizes"),
+            file_counts: parsed.is_present("file-counts"),
+            no_report: parsed.is_present("no-report"),
             ignore_errors: parsed.is_present("ignore-errors"),
             sort: parsed.is_present("sort"),
             depth_first: parsed.is_present("depth-first"),
             same_file_system: parsed.is_present("same-file-system"),
             timeit: parsed.is_present("timeit"),
             count: parsed.is_present("count"),
+            filter: parse_expr(&parsed, "filter")?,
         })
     }
 
@@ -247,10 +297,14 @@ impl Args {
             walkdir = walkdir.max_open(x);
         }
         if self.sort {
-            walkdir = walkdir.sort_by(|a, b| a.file_name().cmp(b.file_name()));
+            walkdir = walkdir.sort_by(|a, b, _| a.0.file_name().cmp(&b.0.file_name()));
         }
         walkdir
     }
+
+    fn is_selected(&self, dent: &DirEntry) -> bool {
+        self.filter.as_ref().map_or(true, |expr| expr.is_match(dent))
+    }
 }
 
 fn parse_usize(parsed: &clap::ArgMatches, flag: &str) -> Result<Option<usize>> {
@@ -262,6 +316,16 @@ fn parse_usize(parsed: &clap::ArgMatches, flag: &str) -> Result<Option<usize>> {
     }
 }
 
+fn parse_expr(parsed: &clap::ArgMatches, flag: &str) -> Result<Option<Expr>> {
+    match parsed.value_of_lossy(flag) {
+        None => Ok(None),
+        Some(x) => match x.parse() {
+            Ok(expr) => Ok(Some(expr)),
+            Err(e) => err!("failed to parse --{}: {}", flag, e),
+        },
+    }
+}
+
 fn write_path<W: io::Write>(wtr: W, path: &Path) -> io::Result<()> {
     write_os_str(wtr, path.as_os_str())
 }